@@ -4,6 +4,20 @@ import {File} from "./filesRepository.js";
 import ProjectRepository from "./projectRepository.js";
 import {Project} from "./projectRepository.js";
 
+/**
+ * Websocket subprotocol of editor messages, server uses legacy protocol if it is not requested
+ */
+const EDITOR_PROTOCOL = "online-editor.v1.json";
+
+/**
+ * Delay in milliseconds before reconnecting after connection to server was lost
+ */
+const RECONNECT_DELAY = 1000;
+/**
+ * How long cursor has to stay in place before its position is sent to other users, in milliseconds
+ */
+const PRESENCE_DELAY = 100;
+
 export default class EditorController {
 	/**
 	 * View for editor
@@ -27,6 +41,14 @@ export default class EditorController {
 	 */
 	files;
 
+	/**
+	 * Folders in edited project. Keys are folder ids and values are objects
+	 * with fields id, name and parent_id
+	 *
+	 * @type {Map.<number, object>}
+	 */
+	folders;
+
 	/**
 	 * Curently edited project
 	 *
@@ -62,18 +84,104 @@ export default class EditorController {
 
 	/**
 	 * Map of all active sessions editing this project. Keys are session ids and
-	 * values are objects with 2 fields: id and name.
+	 * values are objects with fields id, name, active_file_id and presence (last presence message of session).
 	 *
-	 * @type {Map.<string, object>}
+	 * @type {Map.<number, object>}
 	 */
 	sessions
 
 	/**
-	 * Id of last applied change send by remote server. Used for Operation Transformation
+	 * Revision of opened file known to this client. Server numbers changes of every file with
+	 * consecutive revisions and uses this one for Operation Transformation
 	 * https://en.wikipedia.org/wiki/Operational_transformation
-	 * @type {number} id of last applied change. Id is generated by remote server.
+	 * @type {number}
+	 */
+	revision;
+
+	/**
+	 * Changes sent to server and not acknowledged yet. Keys are client change ids.
+	 *
+	 * @type {Map.<number, Change>}
+	 */
+	pendingChanges = new Map();
+
+	/**
+	 * Id given to next change sent to server
+	 *
+	 * @type {number}
+	 */
+	nextClientChangeId = 1;
+
+	/**
+	 * Token sent by server in welcome message, used to resume session after connection is lost
+	 *
+	 * @type {string}
+	 */
+	resumeToken;
+
+	/**
+	 * Id of last project event received from server
+	 *
+	 * @type {number}
+	 */
+	lastEventId;
+
+	/**
+	 * Checksum of opened file content when connection was lost, if all changes were acknowledged then.
+	 * Server uses it to merge changes made offline when it can't resume session.
+	 *
+	 * @type {Promise<?string>}
+	 */
+	offlineBaseChecksum;
+
+	/**
+	 * Timeout of sending presence after cursor moved
+	 *
+	 * @type {?number}
+	 */
+	presenceTimeout = null;
+
+	/**
+	 * Id of session of this client
+	 *
+	 * @type {?number}
+	 */
+	sessionId = null;
+
+	/**
+	 * Id of session followed by this client. Editor opens files opened by that session and scrolls with it.
+	 *
+	 * @type {?number}
+	 */
+	leaderId = null;
+
+	/**
+	 * Chat messages received from server, from the oldest one
+	 *
+	 * @type {object[]}
+	 */
+	chatMessages = [];
+
+	/**
+	 * Comment threads in project. Keys are thread ids.
+	 *
+	 * @type {Map.<number, object>}
+	 */
+	commentThreads = new Map();
+
+	/**
+	 * Suggested changes waiting for review of project owner. Keys are suggestion ids.
+	 *
+	 * @type {Map.<number, object>}
+	 */
+	suggestions = new Map();
+
+	/**
+	 * Role of user in project: "owner", "editor" or "suggester"
+	 *
+	 * @type {?string}
 	 */
-	lastAppliedChangeId;
+	role = null;
 
 	/**
 	 * Creates new controller for editor
@@ -103,7 +211,7 @@ export default class EditorController {
 	 * @return {Promise<void>}
 	 */
 	async createNewFile(name) {
-		this.webosocket.send(`1${name}`);
+		this._send({type: "create_file", name: name});
 	}
 
 	/**
@@ -115,7 +223,7 @@ export default class EditorController {
 	 * @param name {string} new name for file with this id
 	 */
 	async renameFile(id, name) {
-		this.webosocket.send(`3${name}`);
+		this._send({type: "rename_file", file_id: id, name: name});
 	}
 
 	/**
@@ -127,16 +235,65 @@ export default class EditorController {
 	 */
 	async deleteFile(id) {
 		console.log("Deleteing file " + id);
-		this.webosocket.send(`2${id}`);
+		this._send({type: "delete_file", file_id: id});
+	}
+
+	/**
+	 * Creates folder in given folder
+	 *
+	 * @param parentId {number | null} id of parent folder, null for root of project
+	 * @param name {string}
+	 */
+	async createFolder(parentId, name) {
+		this._send({type: "create_folder", name: name, parent_id: parentId});
+	}
+
+	/**
+	 * @param id {number} id of folder
+	 * @param name {string} new name of folder
+	 */
+	async renameFolder(id, name) {
+		this._send({type: "rename_folder", folder_id: id, name: name});
+	}
+
+	/**
+	 * @param id {number} id of folder
+	 * @param parentId {number | null} id of new parent folder, null for root of project
+	 */
+	async moveFolder(id, parentId) {
+		this._send({type: "move_folder", folder_id: id, parent_id: parentId});
+	}
+
+	/**
+	 * Deletes folder with all its subfolders and files
+	 *
+	 * @param id {number} id of folder
+	 */
+	async deleteFolder(id) {
+		this._send({type: "delete_folder", folder_id: id});
+	}
+
+	/**
+	 * @param id {number} id of file
+	 * @param folderId {number | null} id of folder, null for root of project
+	 */
+	async moveFile(id, folderId) {
+		this._send({type: "move_file", file_id: id, folder_id: folderId});
 	}
 
 	connect = (projectId) => {
-		const wsUri =
+		let wsUri =
 			(window.location.protocol === 'https:' ? 'wss://' : 'ws://') +
 			"localhost:5000" +
 			'/projects/' + projectId + "/edit"
+		if (this.resumeToken != null) {
+			wsUri += "?resume_token=" + this.resumeToken;
+			if (this.lastEventId != null) {
+				wsUri += "&last_event_id=" + this.lastEventId;
+			}
+		}
 		console.log("Logging to project session " + projectId)
-		this.webosocket = new WebSocket(wsUri)
+		this.webosocket = new WebSocket(wsUri, [EDITOR_PROTOCOL])
 		console.log('Connecting...')
 
 		const t = this;
@@ -151,87 +308,303 @@ export default class EditorController {
 			console.log('Disconnected.');
 			console.log(e);
 			t.webosocket = null;
+			if (e.reason) {
+				t.view.showError(e.reason);
+				t.view.hideEditor();
+			} else if (t.resumeToken != null) {
+				console.log('Connection lost, reconnecting...');
+				if (t.offlineBaseChecksum == null) {
+					t.offlineBaseChecksum = t.openedFile != null && t.pendingChanges.size === 0
+						? sha256(t.view.getContent())
+						: Promise.resolve(null);
+				}
+				setTimeout(() => t.connect(projectId), RECONNECT_DELAY);
+			}
 		}
 
 		this.webosocket.onerror = (e) => {
-			t.view.showError("Please make sure you are logged in and you have access to this project");
-			t.webosocket = null;
+			if (t.resumeToken == null) {
+				t.view.showError("Please make sure you are logged in and you have access to this project");
+			}
 		}
 	}
 
 	/**
-	 * Converts change to format acceptable by remote server
+	 * Converts change to message sent to remote server
 	 *
 	 * @param {Change} change
-	 * @returns {string} change converted to server readable string
+	 * @returns {object} change_in_file message
 	 * @private
 	 */
 	_convertChangeToMessage = (change) => {
-		return `5${this.openedFile.id} ${change.start.row} ${change.start.column} ${change.end.row} ${change.end.column} ${change.lastChangeApplied} ${change.lines.join("\n")}`
+		return {
+			type: "change_in_file",
+			file_id: this.openedFile.id,
+			start: change.start,
+			end: change.end,
+			lines: change.lines,
+			revision: change.revision ?? null,
+			client_change_id: change.clientChangeId
+		};
 	}
 
 	/**
-	 * Parses message from remote server and invokes special handler for message with
-	 * @param message
+	 * Sends message to remote server
+	 *
+	 * @param message {object} message with type field
+	 * @private
+	 */
+	_send(message) {
+		if (this.webosocket == null || this.webosocket.readyState !== WebSocket.OPEN) {
+			console.log(`Not connected, message ${message.type} was not sent`);
+			return;
+		}
+		this.webosocket.send(JSON.stringify(message));
+	}
+
+	/**
+	 * Parses message from remote server and invokes special handler for message with its type
+	 * @param message {string} message serialized as JSON
 	 */
 	parseMessage = (message) => {
-		switch (message[0]) {
-			case "1":
-				this._handleNewSessionPackage(message.substring(1));
+		const data = JSON.parse(message);
+		if (data.event_id != null) {
+			this.lastEventId = data.event_id;
+		}
+		switch (data.type) {
+			case "welcome":
+				this._handleWelcomePackage(data);
+				break;
+			case "session_joined":
+				this._handleNewSessionPackage(data);
+				break;
+			case "session_left":
+				this._handleSessionDisconnectedPackage(data.session_id);
+				break;
+			case "comment_threads":
+				this._handleCommentThreadsPackage(data);
+				break;
+			case "suggestions":
+				this._handleSuggestionsPackage(data);
+				break;
+			case "suggestion_created":
+				this._handleSuggestionCreatedPackage(data);
 				break;
-			case "2":
-				this._handleSessionDisconnectedPackage(message.substring(1));
+			case "suggestion_reviewed":
+				this._handleSuggestionReviewedPackage(data);
 				break;
-			case "3":
-				this._handleNewFilePackage(message.substring(1));
+			case "comment_thread_changed":
+				this._handleCommentThreadChangedPackage(data);
 				break;
-			case "4":
-				this._handleFileDeletedPackage(message.substring(1));
+			case "chat_message":
+				this._handleChatMessagePackage(data);
 				break;
-			case "5":
-				this._handleFileContentPackage(message.substring(1));
+			case "chat_history":
+				this._handleChatHistoryPackage(data);
 				break;
-			case "6":
-				this._handleChangeInFilePackage(message.substring(1));
+			case "follow_changed":
+				this._handleFollowChangedPackage(data);
 				break;
-			case "9":
-				this._handleProjectData(JSON.parse(message.substring(1)));
+			case "leader_file_changed":
+				this._handleLeaderFileChangedPackage(data);
 				break;
-			case "a":
-				this._handleErrorPackage(message.substring(1));
+			case "active_file_changed":
+				this._handleActiveFileChangedPackage(data);
 				break;
+			case "file_created":
+				this._handleNewFilePackage(data);
+				break;
+			case "file_deleted":
+				this._handleFileDeletedPackage(data.file_id);
+				break;
+			case "file_content":
+				this._handleFileContentPackage(data);
+				break;
+			case "change_in_file":
+				this._handleChangeInFilePackage(data);
+				break;
+			case "change_acknowledged":
+				this._handleChangeAcknowledgedPackage(data);
+				break;
+			case "presence":
+				this._handlePresencePackage(data);
+				break;
+			case "offline_changes_result":
+				this._handleOfflineChangesResultPackage(data);
+				break;
+			case "file_checksum":
+				this._handleFileChecksumPackage(data);
+				break;
+			case "file_renamed":
+				this._handleFileRenamedPackage(data);
+				break;
+			case "project_updated":
+				this._handleProjectUpdatedPackage(data.project);
+				break;
+			case "project_info":
+				this._handleProjectData(data);
+				break;
+			case "error":
+				this._handleErrorPackage(data.message);
+				break;
+			case "folder_changed":
+				this._handleFolderChangedPackage(data.folder);
+				break;
+			case "folder_deleted":
+				this._handleFolderDeletedPackage(data.folder_id);
+				break;
+			case "file_moved":
+				this._handleFileMovedPackage(data);
+				break;
+		}
+	}
+
+	/**
+	 * After reconnecting, changes not acknowledged by server are sent again if session was resumed.
+	 * Otherwise server doesn't know this client anymore, so changes are sent as one batch of offline
+	 * changes which server merges with changes made in the meantime, or content of opened file is
+	 * loaded again if there are no changes.
+	 *
+	 * @param message {object} welcome message
+	 * @private
+	 */
+	_handleWelcomePackage(message) {
+		console.log(`Joined project as session ${message.session_id}, protocol version ${message.version}`);
+		const reconnected = this.resumeToken != null;
+		this.sessionId = message.session_id;
+		const baseChecksum = this.offlineBaseChecksum;
+		this.resumeToken = message.resume_token;
+		this.offlineBaseChecksum = null;
+		if (message.resumed) {
+			// Server ignores changes it already applied
+			this.pendingChanges.forEach(change => this._send(this._convertChangeToMessage(change)));
+		} else if (reconnected) {
+			this.lastEventId = null;
+			this.leaderId = null;
+			if (this.openedFile == null) {
+				return;
+			}
+			if (this.pendingChanges.size > 0) {
+				console.log("Session could not be resumed, sending changes made offline");
+				let _ = this._sendOfflineChanges(baseChecksum);
+			} else {
+				this._loadFileContent();
+			}
 		}
 	}
 
-	_handleChangeInFilePackage = (message) => {
-		let fileIdRangesAndChangeId = message.split(" ", 6);
-		if (fileIdRangesAndChangeId.length !== 6) {
-			console.error("Could not extract changes position, id and file id from incoming change in file package");
+	/**
+	 * Sends all not acknowledged changes in opened file as one batch made on revision of the first of them
+	 *
+	 * @param baseChecksum {Promise<?string>} checksum of file content in that revision, if known
+	 * @private
+	 */
+	async _sendOfflineChanges(baseChecksum) {
+		const fileId = this.openedFile.id;
+		const changes = Array.from(this.pendingChanges.values());
+		const message = {
+			type: "offline_changes",
+			file_id: fileId,
+			revision: changes[0].revision,
+			base_checksum: baseChecksum != null ? await baseChecksum : null,
+			changes: changes.map(change => ({
+				start: change.start,
+				end: change.end,
+				lines: change.lines,
+				client_change_id: change.clientChangeId
+			}))
+		};
+		this._send(message);
+	}
+
+	/**
+	 * @param message {object} offline_changes_result message
+	 * @private
+	 */
+	_handleOfflineChangesResultPackage(message) {
+		if (!message.merged) {
+			this.view.showError("Changes made offline could not be merged, file was reloaded");
+		} else if (message.conflicts.length > 0) {
+			const rows = message.conflicts.map(conflict => conflict.start.row + 1).join(", ");
+			this.view.showError(`Changes made offline conflict with changes of other users in lines: ${rows}`);
 		}
-		let [fileId, startRow, startColumn, endRow, endColumn, changeId] = fileIdRangesAndChangeId.map((str) => parseInt(str));
-		let startingIndexOfChangeContent = 6 + fileIdRangesAndChangeId.reduce((total, currentStr) => {
-			return total + currentStr.length
-		}, 0);
-		let changeContent = message.substring(startingIndexOfChangeContent);
-		if (this.openedFile == null || fileId !== this.openedFile.id) {
+	}
+
+	/**
+	 * @param change {object} change_in_file message
+	 * @private
+	 */
+	_handleChangeInFilePackage = (change) => {
+		if (this.openedFile == null || change.file_id !== this.openedFile.id) {
 			return;
 		}
-		this.lastAppliedChangeId = changeId;
-		const range = new ace.Range(startRow, startColumn, endRow, endColumn)
-		this.view.replaceText(range, changeContent)
+		this.revision = change.revision;
+		// Server applies not acknowledged changes after this one, so it is moved past them in editor
+		// and they are rebased onto it like server will do
+		let incoming = {start: change.start, end: change.end, lines: change.lines};
+		this.pendingChanges.forEach((pending, clientChangeId) => {
+			const transformedIncoming = transformChange(incoming, pending, false);
+			this.pendingChanges.set(clientChangeId, {
+				...transformChange(pending, incoming, true),
+				revision: change.revision,
+				clientChangeId: clientChangeId
+			});
+			incoming = transformedIncoming;
+		});
+		const range = new ace.Range(incoming.start.row, incoming.start.column, incoming.end.row, incoming.end.column)
+		this.view.replaceText(range, incoming.lines.join("\n"))
 	}
 
+	/**
+	 * Server applied change sent by this client
+	 *
+	 * @param message {object} change_acknowledged message
+	 * @private
+	 */
+	_handleChangeAcknowledgedPackage(message) {
+		this.pendingChanges.delete(message.client_change_id);
+		if (this.openedFile != null && message.file_id === this.openedFile.id) {
+			this.revision = message.revision;
+		}
+	}
+
+	/**
+	 * Compares checksum of opened file computed by server with checksum of content shown in editor.
+	 * If they differ, checksum is sent to server which verifies it and sends file content again.
+	 *
+	 * @param message {object} file_checksum message
+	 * @private
+	 */
+	async _handleFileChecksumPackage(message) {
+		if (this.openedFile == null || message.file_id !== this.openedFile.id) {
+			return;
+		}
+		// Content with not acknowledged changes can't be compared with server revision
+		if (message.revision !== this.revision || this.pendingChanges.size > 0) {
+			return;
+		}
+		const checksum = await sha256(this.view.getContent());
+		if (checksum !== message.checksum && message.revision === this.revision) {
+			console.log(`Content of file ${message.file_id} differs from server content`);
+			this._send({type: "file_checksum", file_id: message.file_id, revision: message.revision, checksum: checksum});
+		}
+	}
 
 	_handleFileContentPackage(message) {
-		const indexOfFirstSpace = message.indexOf(" ");
-		const fileId = parseInt(message.substring(0, indexOfFirstSpace));
+		const fileId = message.file_id;
 		if (fileId !== this.openedFile.id) {
 			console.log(`Recived contetn of file ${fileId} but currently opened file is ${this.openedFile.id}`);
 			return;
 		}
-		this.realFileContentSession = ace.createEditSession(message.substring(indexOfFirstSpace + 1));
+		this.revision = message.revision;
+		this.pendingChanges.clear();
+		this.realFileContentSession = ace.createEditSession(message.content);
 		this.view.showFileContent(this.realFileContentSession.getValue());
 		this.view.setEditorMode(this.getEditorMode(this.openedFile.name));
+		this._showPresences();
+		this._showCommentThreads();
+		this._showSuggestions();
+		this.handleCursorChange();
 	}
 
 	/**
@@ -266,11 +639,29 @@ export default class EditorController {
 		}
 	}
 
-	_handleFileDeletedPackage(message) {
-		const fileId = parseInt(message);
+	/**
+	 * @param {number} fileId id of deleted file
+	 * @private
+	 */
+	_handleFileDeletedPackage(fileId) {
 		console.log(`File ${fileId} was deleted`);
 		this.files = this.files.filter(file => file.id !== fileId);
-		this.view.showFilesList(this.files);
+		this.commentThreads.forEach((thread, threadId) => {
+			if (thread.file_id === fileId) {
+				this.commentThreads.delete(threadId);
+			}
+		});
+		this.suggestions.forEach((suggestion, suggestionId) => {
+			if (suggestion.file_id === fileId) {
+				this.suggestions.delete(suggestionId);
+			}
+		});
+		for (const session of this.sessions.values()) {
+			if (session.active_file_id === fileId) {
+				session.active_file_id = null;
+			}
+		}
+		this._showFiles();
 		if (this.openedFile != null && fileId === this.openedFile.id) {
 			this.view.hideEditor();
 		}
@@ -285,52 +676,519 @@ export default class EditorController {
 		this.view.showError(message);
 	}
 
+	/**
+	 * File was renamed on server
+	 *
+	 * @param {object} message file_renamed message with file_id and name
+	 * @private
+	 */
+	_handleFileRenamedPackage(message) {
+		const id = message.file_id;
+		const name = message.name;
+		const file = this.files.find(file => file.id === id);
+		if (file == null) {
+			console.log(`Recived new name of unknown file ${id}`);
+			return;
+		}
+		file.name = name;
+		this._showFiles();
+		if (this.openedFile != null && id === this.openedFile.id) {
+			this.openedFile.name = name;
+			this.view.setEditorMode(this.getEditorMode(name));
+		}
+	}
+
 	/**
 	 * New file was created on server
 	 *
-	 * @param {string} message string containing id and new file name separated by space
+	 * @param {object} message file_created message with file_id, name and folder_id
 	 * @private
 	 */
 	_handleNewFilePackage(message) {
-		const indexOfFirstSpace = message.indexOf(" ");
-		const id = parseInt(message.substring(0, indexOfFirstSpace));
-		const name = message.substring(indexOfFirstSpace + 1);
-		this.files.push(new File(id, name))
+		this.files.push(new File(message.file_id, message.name, message.folder_id))
+		this._showFiles();
+	}
+
+	/**
+	 * File was moved to other folder
+	 *
+	 * @param {object} message file_moved message with file_id and folder_id (null for root)
+	 * @private
+	 */
+	_handleFileMovedPackage(message) {
+		const id = message.file_id;
+		const file = this.files.find(file => file.id === id);
+		if (file == null) {
+			console.log(`Recived new folder of unknown file ${id}`);
+			return;
+		}
+		file.folderId = message.folder_id;
+		this._showFiles();
+	}
+
+	/**
+	 * Folder was created, renamed or moved
+	 *
+	 * @param folder {object}
+	 * @private
+	 */
+	_handleFolderChangedPackage(folder) {
+		this.folders.set(folder.id, folder);
+		this._showFiles();
+	}
+
+	/**
+	 * Folder was deleted. Deleted files are announced in separate packages.
+	 *
+	 * @param {number} folderId id of deleted folder
+	 * @private
+	 */
+	_handleFolderDeletedPackage(folderId) {
+		const deletedIds = [folderId];
+		while (deletedIds.length > 0) {
+			const id = deletedIds.pop();
+			this.folders.delete(id);
+			this.folders.forEach(folder => {
+				if (folder.parent_id === id) {
+					deletedIds.push(folder.id);
+				}
+			});
+		}
+		this._showFiles();
+	}
+
+	/**
+	 * Updates paths of files and shows them sorted by path
+	 *
+	 * @private
+	 */
+	_showFiles() {
+		this.files.forEach(file => {
+			let path = file.name;
+			let folder = this.folders.get(file.folderId);
+			while (folder != null) {
+				path = `${folder.name.trim()}/${path}`;
+				folder = this.folders.get(folder.parent_id);
+			}
+			file.path = path;
+		});
 		this.files.sort(((a, b) => {
-			if (a.name > b.name) {
+			if (a.path > b.path) {
 				return 1
-			} else if (a.name < b.name) {
+			} else if (a.path < b.path) {
 				return -1
 			}
 			return 0;
 		}))
-		this.view.showFilesList(this.files);
+		this.view.showFilesList(this.files, this._sessionsByFile());
+	}
+
+	/**
+	 * @return {Map.<number, object[]>} sessions which have file opened, keys are file ids
+	 * @private
+	 */
+	_sessionsByFile() {
+		const sessionsByFile = new Map();
+		if (this.sessions == null) {
+			return sessionsByFile;
+		}
+		for (const session of this.sessions.values()) {
+			if (session.active_file_id == null) {
+				continue;
+			}
+			if (!sessionsByFile.has(session.active_file_id)) {
+				sessionsByFile.set(session.active_file_id, []);
+			}
+			sessionsByFile.get(session.active_file_id).push(session);
+		}
+		return sessionsByFile;
 	}
 
 	/**
+	 * Reads folders and files from tree of project files
 	 *
-	 * @param {string} sessionId
+	 * @param nodes {object[]} nodes of tree, each has type "folder" or "file"
+	 * @param parentId {number | null} id of folder containing nodes
+	 * @private
+	 */
+	_readFilesTree(nodes, parentId) {
+		nodes.forEach(node => {
+			if (node.type === "folder") {
+				this.folders.set(node.id, {id: node.id, name: node.name, parent_id: parentId});
+				this._readFilesTree(node.children, node.id);
+			} else {
+				this.files.push(new File(node.id, node.name, parentId));
+			}
+		});
+	}
+
+	/**
+	 *
+	 * @param {number} sessionId
 	 * @private
 	 */
 	_handleSessionDisconnectedPackage(sessionId) {
 		this.sessions.delete(sessionId);
-		this.view.showSessions(this.sessions.values());
+		this._showSessions();
+		this._showPresences();
+		this._showFiles();
+	}
+
+	/**
+	 * @param {object} message active_file_changed message with session_id and file_id
+	 * @private
+	 */
+	_handleActiveFileChangedPackage(message) {
+		const session = this.sessions.get(message.session_id);
+		if (session == null) {
+			return;
+		}
+		session.active_file_id = message.file_id;
+		this._showFiles();
+	}
+
+	/**
+	 * @param {object} presence presence message with session_id, file_id, cursor and selections
+	 * @private
+	 */
+	_handlePresencePackage(presence) {
+		const session = this.sessions.get(presence.session_id);
+		if (session == null) {
+			return;
+		}
+		session.presence = presence;
+		this._showPresences();
+		if (presence.session_id === this.leaderId && presence.viewport != null && this.openedFile != null
+			&& presence.file_id === this.openedFile.id) {
+			this.view.scrollToRow(presence.viewport.first_row);
+		}
+	}
+
+	/**
+	 * Starts comment thread about range of opened file
+	 *
+	 * @param start {Range}
+	 * @param end {Range}
+	 * @param content {string} first comment
+	 */
+	createCommentThread(start, end, content) {
+		if (this.openedFile == null) {
+			return;
+		}
+		this._send({type: "create_comment_thread", file_id: this.openedFile.id, start: start, end: end, content: content});
+	}
+
+	/**
+	 * @param threadId {number}
+	 * @param content {string}
+	 */
+	addComment(threadId, content) {
+		this._send({type: "add_comment", thread_id: threadId, content: content});
+	}
+
+	/**
+	 * @param threadId {number}
+	 * @param resolved {boolean} false reopens thread
+	 */
+	resolveCommentThread(threadId, resolved) {
+		this._send({type: "resolve_comment_thread", thread_id: threadId, resolved: resolved});
+	}
+
+	/**
+	 * @param {object} message comment_threads message with all threads in project
+	 * @private
+	 */
+	_handleCommentThreadsPackage(message) {
+		this.commentThreads = new Map(message.threads.map(thread => [thread.id, thread]));
+		this._showCommentThreads();
+	}
+
+	/**
+	 * @param {object} thread comment thread which was created or changed
+	 * @private
+	 */
+	_handleCommentThreadChangedPackage(thread) {
+		this.commentThreads.set(thread.id, thread);
+		this._showCommentThreads();
+	}
+
+	/**
+	 * Shows comment threads of opened file
+	 * @private
+	 */
+	_showCommentThreads() {
+		const fileId = this.openedFile != null ? this.openedFile.id : null;
+		const threads = Array.from(this.commentThreads.values())
+			.filter(thread => thread.file_id === fileId)
+			.sort((a, b) => a.start.row - b.start.row);
+		this.view.showCommentThreads(threads);
+	}
+
+	/**
+	 * Suggests replacing range of opened file with text. Change is applied when project owner accepts it.
+	 *
+	 * @param start {Range}
+	 * @param end {Range}
+	 * @param text {string}
+	 */
+	suggestChange(start, end, text) {
+		if (this.openedFile == null) {
+			return;
+		}
+		this._send({
+			type: "suggest_change",
+			file_id: this.openedFile.id,
+			start: start,
+			end: end,
+			lines: text.split("\n"),
+			revision: this.revision ?? null
+		});
+	}
+
+	/**
+	 * @param suggestionId {number}
+	 * @param accept {boolean} true applies suggested change, false rejects it
+	 */
+	reviewSuggestion(suggestionId, accept) {
+		this._send({type: "review_suggestion", suggestion_id: suggestionId, accept: accept});
+	}
+
+	/**
+	 * @param {object} message suggestions message with all suggestions waiting for review
+	 * @private
+	 */
+	_handleSuggestionsPackage(message) {
+		this.suggestions = new Map(message.suggestions.map(suggestion => [suggestion.id, suggestion]));
+		this._showSuggestions();
+	}
+
+	/**
+	 * @param {object} suggestion suggested change
+	 * @private
+	 */
+	_handleSuggestionCreatedPackage(suggestion) {
+		this.suggestions.set(suggestion.id, suggestion);
+		this._showSuggestions();
 	}
 
 	/**
+	 * Accepted suggestion is applied by server, its change comes as change_in_file message
 	 *
-	 * @param {string} message Message containing id and name of user separated by space
+	 * @param {object} message suggestion_reviewed message
+	 * @private
+	 */
+	_handleSuggestionReviewedPackage(message) {
+		this.suggestions.delete(message.suggestion_id);
+		this._showSuggestions();
+	}
+
+	/**
+	 * Shows suggestions in opened file, only project owner can review them
+	 * @private
+	 */
+	_showSuggestions() {
+		const fileId = this.openedFile != null ? this.openedFile.id : null;
+		const suggestions = Array.from(this.suggestions.values())
+			.filter(suggestion => suggestion.file_id === fileId)
+			.sort((a, b) => a.start.row - b.start.row);
+		this.view.showSuggestions(suggestions, this.role === "owner");
+	}
+
+	/**
+	 * Loads checkpoints of project and shows them
+	 */
+	async loadCheckpoints() {
+		try {
+			const checkpoints = await this.projectsRepository.getCheckpoints(this.project.id);
+			this.view.showCheckpoints(checkpoints);
+		} catch (e) {
+			this.view.showError(`Failed to load checkpoints: ${e}`);
+		}
+	}
+
+	/**
+	 * @param name {string} name of new checkpoint
+	 */
+	async createCheckpoint(name) {
+		try {
+			await this.projectsRepository.createCheckpoint(this.project.id, name);
+		} catch (e) {
+			this.view.showError(`Failed to create checkpoint: ${e}`);
+		}
+		await this.loadCheckpoints();
+	}
+
+	/**
+	 * Restores files to checkpoint. Restored content comes from server as changes in files.
+	 *
+	 * @param checkpointId {number}
+	 * @param onlyOpenedFile {boolean} restores only opened file instead of whole project
+	 */
+	async restoreCheckpoint(checkpointId, onlyOpenedFile) {
+		if (onlyOpenedFile && this.openedFile == null) {
+			return;
+		}
+		try {
+			await this.projectsRepository.restoreCheckpoint(this.project.id, checkpointId, onlyOpenedFile ? this.openedFile.id : null);
+		} catch (e) {
+			this.view.showError(`Failed to restore checkpoint: ${e}`);
+		}
+	}
+
+	/**
+	 * @param content {string} message to send to project chat
+	 */
+	sendChatMessage(content) {
+		this._send({type: "chat_message", content: content});
+	}
+
+	/**
+	 * Requests chat messages older than the oldest received one
+	 */
+	loadOlderChatMessages() {
+		const beforeId = this.chatMessages.length > 0 ? this.chatMessages[0].id : null;
+		this._send({type: "get_chat_history", before_id: beforeId});
+	}
+
+	/**
+	 * @param {object} message chat_message message
+	 * @private
+	 */
+	_handleChatMessagePackage(message) {
+		this.chatMessages.push(message);
+		this.view.showChatMessages(this.chatMessages, true);
+		if (message.mentions_you) {
+			console.log(`${message.author.name} mentioned you in chat`);
+		}
+	}
+
+	/**
+	 * Adds older messages before received ones. History sent after joining project replaces them.
+	 *
+	 * @param {object} message chat_history message with messages from the oldest one
+	 * @private
+	 */
+	_handleChatHistoryPackage(message) {
+		const knownIds = new Set(this.chatMessages.map(chatMessage => chatMessage.id));
+		const olderMessages = message.messages.filter(chatMessage => !knownIds.has(chatMessage.id));
+		const isOlder = this.chatMessages.length > 0 && olderMessages.length > 0
+			&& olderMessages[olderMessages.length - 1].id < this.chatMessages[0].id;
+		this.chatMessages = isOlder ? olderMessages.concat(this.chatMessages) : message.messages;
+		this.view.showChatMessages(this.chatMessages, !isOlder);
+	}
+
+	/**
+	 * Starts following session, editor will show the same file and part of it as user of that session
+	 *
+	 * @param sessionId {?number} id of session to follow, null stops following
+	 */
+	follow(sessionId) {
+		this._send({type: "follow", session_id: sessionId});
+	}
+
+	/**
+	 * @param {object} message follow_changed message with session_id and leader_id
+	 * @private
+	 */
+	_handleFollowChangedPackage(message) {
+		const session = this.sessions.get(message.session_id);
+		if (session != null) {
+			session.leader_id = message.leader_id;
+		}
+		if (message.session_id === this.sessionId) {
+			this.leaderId = message.leader_id;
+		}
+		this._showSessions();
+	}
+
+	/**
+	 * Followed session opened other file, so it is opened here too
+	 *
+	 * @param {object} message leader_file_changed message with leader_id and file_id
+	 * @private
+	 */
+	_handleLeaderFileChangedPackage(message) {
+		if (message.leader_id !== this.leaderId || message.file_id == null) {
+			return;
+		}
+		if (this.openedFile == null || this.openedFile.id !== message.file_id) {
+			this.view.showEditor();
+			this.fileSelectionChanged(message.file_id);
+		}
+	}
+
+	/**
+	 * @private
+	 */
+	_showSessions() {
+		this.view.showSessions(this.sessions.values(), this.sessionId, this.leaderId);
+	}
+
+	/**
+	 * Shows cursors of other users who have the same file opened
+	 * @private
+	 */
+	_showPresences() {
+		const fileId = this.openedFile != null ? this.openedFile.id : null;
+		const presences = Array.from(this.sessions.values())
+			.map(session => session.presence)
+			.filter(presence => presence != null && presence.file_id != null && presence.file_id === fileId);
+		this.view.showPresences(presences);
+	}
+
+	/**
+	 * Sends position of cursor to other users when it stops moving
+	 */
+	handleCursorChange() {
+		if (this.presenceTimeout != null) {
+			clearTimeout(this.presenceTimeout);
+		}
+		this.presenceTimeout = setTimeout(() => {
+			this.presenceTimeout = null;
+			this._sendPresence();
+		}, PRESENCE_DELAY);
+	}
+
+	/**
+	 * @private
+	 */
+	_sendPresence() {
+		if (this.openedFile == null) {
+			this._send({type: "presence", file_id: null, cursor: null, selections: []});
+			return;
+		}
+		const selections = this.view.getSelections();
+		this._send({
+			type: "presence",
+			file_id: this.openedFile.id,
+			cursor: selections.cursor,
+			selections: selections.selections,
+			viewport: this.view.getViewport()
+		});
+	}
+
+	/**
+	 *
+	 * @param {object} message session_joined message with session_id and name of user
 	 * @private
 	 */
 	_handleNewSessionPackage(message) {
-		const indexOfFirstSpace = message.indexOf(" ");
-		const sessionId = message.substring(0, indexOfFirstSpace);
-		const name = message.substring(indexOfFirstSpace + 1);
-		this.sessions.set(sessionId, {
-			id: sessionId,
-			name: name
+		this.sessions.set(message.session_id, {
+			id: message.session_id,
+			name: message.name
 		});
-		this.view.showSessions(this.sessions.values());
+		this._showSessions();
+	}
+
+	/**
+	 * Name or description of project was changed
+	 *
+	 * @param project {Project}
+	 * @private
+	 */
+	_handleProjectUpdatedPackage(project) {
+		this.project = project;
+		this.view.showProjectInfo(project);
 	}
 
 	/**
@@ -339,11 +1197,15 @@ export default class EditorController {
 	 * @param projectData {object}
 	 * @param {Project} projectData.project
 	 * @param {object[]} projectData.sessions list of active session(containing current session)
-	 * @param {string} projectData.sessions[].id id of session
+	 * @param {number} projectData.sessions[].id id of session
 	 * @param {string} projectData.sessions[].name  Name of user for given session
-	 * @param {object []} projectData.files list of files in this project
-	 * @param {number} projectData.files[].id id of file
-	 * @param {string} projectData.files[].name name of file
+	 * @param {?number} projectData.sessions[].active_file_id id of file opened in session
+	 * @param {?object} projectData.sessions[].presence last presence of session, with its opened file and cursor
+	 * @param {object []} projectData.files tree of folders and files in this project
+	 * @param {number} projectData.files[].id id of file or folder
+	 * @param {string} projectData.files[].name name of file or folder
+	 * @param {"file" | "folder"} projectData.files[].type
+	 * @param {object []} projectData.files[].children content of folder
 	 * @private
 	 */
 	_handleProjectData(projectData) {
@@ -351,17 +1213,24 @@ export default class EditorController {
 		projectData.sessions.forEach(session => {
 			this.sessions.set(session.id, session);
 		})
-		this.view.showSessions(this.sessions.values());
+		const ownSession = this.sessions.get(this.sessionId);
+		this.role = ownSession != null ? ownSession.role : null;
+		// Suggesters can't change files directly, they only suggest changes
+		this.view.setReadOnly(this.role === "suggester");
+		this._showSuggestions();
+		this._showSessions();
+		this._showPresences();
 
 		this.project = projectData.project;
 		this.view.showProjectInfo(this.project);
+		let _ = this.loadCheckpoints();
 
+		this.files = [];
+		this.folders = new Map();
 		if (projectData.files != null) {
-			this.files = projectData.files;
-		} else {
-			this.files = [];
+			this._readFilesTree(projectData.files, null);
 		}
-		this.view.showFilesList(this.files);
+		this._showFiles();
 	}
 
 	/**
@@ -386,7 +1255,7 @@ export default class EditorController {
 	 * @private
 	 */
 	_loadFileContent() {
-		this.webosocket.send(`4${this.openedFile.id}`);
+		this._send({type: "get_file_content", file_id: this.openedFile.id});
 	}
 
 	realFileContent
@@ -417,10 +1286,11 @@ export default class EditorController {
 			},
 			end: fileChange.action === "remove" ? fileChange.end : fileChange.start,
 			lines: fileChange.action === "remove" ? [] : fileChange.lines,
-			lastChangeApplied: this.lastAppliedChangeId
+			revision: this.revision,
+			clientChangeId: this.nextClientChangeId++
 		}
-		const message = this._convertChangeToMessage(change);
-		this.webosocket.send(message);
+		this.pendingChanges.set(change.clientChangeId, change);
+		this._send(this._convertChangeToMessage(change));
 	}
 
 	// disconnect = () => {
@@ -432,6 +1302,75 @@ export default class EditorController {
 	// }
 }
 
+/**
+ * @param {string} text
+ * @return {Promise<string>} SHA-256 checksum of text encoded as UTF-8, as lowercase hex string
+ */
+async function sha256(text) {
+	const digest = await crypto.subtle.digest("SHA-256", new TextEncoder().encode(text));
+	return Array.from(new Uint8Array(digest))
+		.map(byte => byte.toString(16).padStart(2, "0"))
+		.join("");
+}
+
+/**
+ * Transforms change so it can be applied after `applied` change, although it was made without
+ * knowledge about it. Works like transformation on server (operational_transformation.rs).
+ *
+ * @param {{start: Range, end: Range, lines: string[]}} change
+ * @param {{start: Range, end: Range, lines: string[]}} applied
+ * @param {boolean} moveAfterInsertion whether change inserting text in the same place as `applied` goes after its text
+ * @return {{start: Range, end: Range, lines: string[]}} transformed change
+ */
+function transformChange(change, applied, moveAfterInsertion) {
+	const isInsertion = comparePositions(change.start, change.end) === 0;
+	const start = transformPosition(change.start, applied, moveAfterInsertion);
+	return {
+		start: start,
+		end: isInsertion ? start : transformPosition(change.end, applied, false),
+		lines: change.lines
+	};
+}
+
+/**
+ * Moves position so it points to the same place in text after `applied` change
+ *
+ * @param {Range} position
+ * @param {{start: Range, end: Range, lines: string[]}} applied
+ * @param {boolean} moveAfterInsertion whether position should be placed after text inserted exactly in this position
+ * @return {Range}
+ */
+function transformPosition(position, applied, moveAfterInsertion) {
+	const appliedIsInsertion = comparePositions(applied.start, applied.end) === 0;
+	if (comparePositions(position, applied.start) < 0) {
+		return position;
+	}
+	if (comparePositions(position, applied.start) === 0 && (!appliedIsInsertion || !moveAfterInsertion)) {
+		return position;
+	}
+	if (comparePositions(position, applied.end) < 0) {
+		// text at this position was deleted
+		return applied.start;
+	}
+	const lastLine = applied.lines.length > 0 ? applied.lines[applied.lines.length - 1] : "";
+	const insertedTextEnd = applied.lines.length <= 1
+		? {row: applied.start.row, column: applied.start.column + [...lastLine].length}
+		: {row: applied.start.row + applied.lines.length - 1, column: [...lastLine].length};
+	if (position.row === applied.end.row) {
+		return {row: insertedTextEnd.row, column: insertedTextEnd.column + position.column - applied.end.column};
+	}
+	return {row: position.row - applied.end.row + insertedTextEnd.row, column: position.column};
+}
+
+/**
+ * @param {Range} first
+ * @param {Range} second
+ * @return {number} negative if first position is before second one, 0 if they are equal, positive otherwise
+ */
+function comparePositions(first, second) {
+	return first.row !== second.row ? first.row - second.row : first.column - second.column;
+}
+
 const fileExtensionToEditorMode = {
 // "abc.js": "",
 // "actionscript.js": "",
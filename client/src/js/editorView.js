@@ -15,6 +15,28 @@ export default class EditorView {
 
 	editedFileId = null;
 
+	/**
+	 * Markers showing cursors and selections of other users, with anchors keeping them in place
+	 * when text changes
+	 *
+	 * @type {{id: number, range: object}[]}
+	 */
+	presenceMarkers = [];
+
+	/**
+	 * Markers showing ranges of comment threads
+	 *
+	 * @type {{id: number, range: object}[]}
+	 */
+	commentMarkers = [];
+
+	/**
+	 * Markers showing ranges of suggested changes
+	 *
+	 * @type {{id: number, range: object}[]}
+	 */
+	suggestionMarkers = [];
+
 	constructor() {
 		this.controller = new EditorController(this);
 		this.editor = ace.edit("editor");
@@ -37,11 +59,59 @@ export default class EditorView {
 			}
 			this.controller.handleChange(c);
 		})
+		this.editor.selection.on("changeCursor", () => this.controller.handleCursorChange());
+		this.editor.selection.on("changeSelection", () => this.controller.handleCursorChange());
+		this.editor.session.on("changeScrollTop", () => this.controller.handleCursorChange());
 
 		document.getElementById("new-file-button").addEventListener("click", (_) => {
 			const newName = document.getElementById("new-file-name").value;
 			this.controller.createNewFile(newName);
 		})
+
+		const chatInput = document.getElementById("chat-message-input");
+		const sendChatMessage = () => {
+			if (chatInput.value.trim().length > 0) {
+				this.controller.sendChatMessage(chatInput.value);
+				chatInput.value = "";
+			}
+		};
+		document.getElementById("chat-send-button").addEventListener("click", (_) => sendChatMessage());
+		chatInput.addEventListener("keydown", (event) => {
+			if (event.key === "Enter") {
+				sendChatMessage();
+			}
+		});
+		document.getElementById("new-comment-button").addEventListener("click", (_) => {
+			const range = this.editor.selection.getRange();
+			const content = prompt("Comment");
+			if (content != null && content.trim().length > 0) {
+				this.controller.createCommentThread(
+					{row: range.start.row, column: range.start.column},
+					{row: range.end.row, column: range.end.column},
+					content
+				);
+			}
+		})
+		document.getElementById("suggest-change-button").addEventListener("click", (_) => {
+			const range = this.editor.selection.getRange();
+			const text = prompt("Replace selected text with", this.editor.session.getTextRange(range));
+			if (text != null) {
+				this.controller.suggestChange(
+					{row: range.start.row, column: range.start.column},
+					{row: range.end.row, column: range.end.column},
+					text
+				);
+			}
+		})
+		document.getElementById("new-checkpoint-button").addEventListener("click", (_) => {
+			const name = prompt("Checkpoint name");
+			if (name != null && name.trim().length > 0) {
+				let _ = this.controller.createCheckpoint(name);
+			}
+		})
+		document.getElementById("older-chat-messages-button").addEventListener("click", (_) => {
+			this.controller.loadOlderChatMessages();
+		})
 	}
 
 	simulateChange(range, content){
@@ -76,11 +146,13 @@ export default class EditorView {
 	 */
 
 	/**
-	 * Show session list
+	 * Show session list with buttons to follow other sessions
 	 *
 	 * @param {IterableIterator<Session>} sessions
+	 * @param {?number} ownSessionId id of session of this client
+	 * @param {?number} leaderId id of session followed by this client
 	 */
-	showSessions(sessions) {
+	showSessions(sessions, ownSessionId, leaderId) {
 		let sessionListElement = document.getElementById("users-list");
 		while (sessionListElement.firstChild) {
 			sessionListElement.removeChild(sessionListElement.firstChild);
@@ -88,16 +160,50 @@ export default class EditorView {
 		for (const session of sessions) {
 			const sessionDomElement = document.createElement("li");
 			sessionDomElement.textContent = session.name;
+			if (session.id !== ownSessionId) {
+				/** @type {HTMLButtonElement} */
+				const followButton = document.createElement("button");
+				followButton.classList.add("option-button");
+				followButton.textContent = session.id === leaderId ? "Unfollow" : "Follow";
+				followButton.onclick = (_) => this.controller.follow(session.id === leaderId ? null : session.id);
+				sessionDomElement.appendChild(followButton);
+			}
 			sessionListElement.appendChild(sessionDomElement);
 		}
 	}
 
 	/**
-	 * Displays files in left panel
+	 * Shows chat messages, replacing previously shown ones
+	 *
+	 * @param messages {object[]} chat messages from the oldest one
+	 * @param scrollToEnd {boolean} whether the newest message should be scrolled into view
+	 */
+	showChatMessages(messages, scrollToEnd) {
+		const listElement = document.getElementById("chat-messages");
+		while (listElement.firstChild) {
+			listElement.removeChild(listElement.firstChild);
+		}
+		messages.forEach(message => {
+			const messageElement = document.createElement("li");
+			const sentAt = new Date(message.sent_at).toLocaleTimeString();
+			messageElement.textContent = `[${sentAt}] ${message.author.name}: ${message.content}`;
+			if (message.mentions_you) {
+				messageElement.classList.add("chat-message-mentioning-you");
+			}
+			listElement.appendChild(messageElement);
+		});
+		if (scrollToEnd) {
+			listElement.scrollTop = listElement.scrollHeight;
+		}
+	}
+
+	/**
+	 * Displays files in left panel with initials of users who have them opened
 	 *
 	 * @param files {File []}
+	 * @param sessionsByFile {Map.<number, Session[]>} sessions which have file opened, keys are file ids
 	 */
-	showFilesList(files) {
+	showFilesList(files, sessionsByFile) {
 		const listElement = document.getElementById("project-files-list");
 		while (listElement.firstChild) {
 			listElement.removeChild(listElement.firstChild);
@@ -109,20 +215,33 @@ export default class EditorView {
 
 			/** @type {HTMLDivElement} */
 			const fileNameDiv = document.createElement("div");
-			fileNameDiv.textContent = file.name;
-			fileNameDiv.title = file.name;
+			fileNameDiv.textContent = file.path;
+			fileNameDiv.title = file.path;
 			fileNameDiv.onclick = (_) => this._handleFileClick(file.id);
 			fileNameDiv.classList.add("file-name-label")
 			fileListElement.appendChild(fileNameDiv);
 
+			(sessionsByFile.get(file.id) || []).forEach(session => {
+				const avatar = document.createElement("span");
+				avatar.classList.add("file-avatar");
+				avatar.textContent = session.name.charAt(0).toUpperCase();
+				avatar.title = session.name;
+				fileListElement.appendChild(avatar);
+			});
 
 			/** @type {HTMLButtonElement} */
 			const renameButton = document.createElement("button");
 			renameButton.classList.add("option-button");
 			renameButton.textContent = "R";
-			renameButton.title = "Rename(not implemented)"
+			renameButton.title = "Rename file"
 			renameButton.dataset["id"] = `${file.id}`
 			fileListElement.appendChild(renameButton);
+			renameButton.onclick = (_) => {
+				const newName = prompt("New file name", file.name.trim());
+				if (newName != null) {
+					this.controller.renameFile(file.id, newName);
+				}
+			};
 
 			/** @type {HTMLButtonElement} */
 			const deleteButton = document.createElement("button");
@@ -142,11 +261,18 @@ export default class EditorView {
 	 * @private
 	 */
 	_handleFileClick(fileId) {
+		this.showEditor();
+		this.controller.fileSelectionChanged(fileId);
+	}
+
+	/**
+	 * Shows editor hidden by hideEditor
+	 */
+	showEditor() {
 		const possibleDisabledEditor = document.getElementById("editor-disabled");
 		if (possibleDisabledEditor != null) {
 			possibleDisabledEditor.id = "editor";
 		}
-		this.controller.fileSelectionChanged(fileId);
 	}
 
 	/**
@@ -163,6 +289,242 @@ export default class EditorView {
 	 *
 	 * @param {string} content file content as string
 	 */
+	/**
+	 * @return {string} text currently shown in editor
+	 */
+	getContent() {
+		return this.editor.getValue();
+	}
+
+	/**
+	 * @return {{cursor: Range, selections: {start: Range, end: Range}[]}} cursor and not empty selections of user
+	 */
+	getSelections() {
+		const cursor = this.editor.selection.getCursor();
+		const ranges = this.editor.selection.getAllRanges ? this.editor.selection.getAllRanges() : [this.editor.selection.getRange()];
+		return {
+			cursor: {row: cursor.row, column: cursor.column},
+			selections: ranges
+				.filter(range => !range.isEmpty())
+				.map(range => ({
+					start: {row: range.start.row, column: range.start.column},
+					end: {row: range.end.row, column: range.end.column}
+				}))
+		};
+	}
+
+	/**
+	 * @return {{first_row: number, last_row: number}} rows of file visible in editor
+	 */
+	getViewport() {
+		return {first_row: this.editor.getFirstVisibleRow(), last_row: this.editor.getLastVisibleRow()};
+	}
+
+	/**
+	 * Scrolls editor so given row is at the top
+	 *
+	 * @param row {number}
+	 */
+	scrollToRow(row) {
+		this.editor.renderer.scrollToRow(row);
+	}
+
+	/**
+	 * Shows comment threads of opened file in list and marks their ranges in editor
+	 *
+	 * @param threads {object[]} comment threads of opened file
+	 */
+	showCommentThreads(threads) {
+		this._removeMarkers(this.commentMarkers);
+		this.commentMarkers = [];
+		const listElement = document.getElementById("comment-threads");
+		while (listElement.firstChild) {
+			listElement.removeChild(listElement.firstChild);
+		}
+		threads.forEach(thread => {
+			if (!thread.resolved) {
+				this.commentMarkers.push(this._addMarker(thread.start, thread.end, "comment-range"));
+			}
+			const threadElement = document.createElement("li");
+			if (thread.resolved) {
+				threadElement.classList.add("comment-thread-resolved");
+			}
+			const rangeElement = document.createElement("div");
+			rangeElement.textContent = `Line ${thread.start.row + 1}`;
+			rangeElement.onclick = (_) => this.editor.gotoLine(thread.start.row + 1, thread.start.column);
+			threadElement.appendChild(rangeElement);
+			thread.comments.forEach(comment => {
+				const commentElement = document.createElement("div");
+				commentElement.textContent = `${comment.author.name}: ${comment.content}`;
+				threadElement.appendChild(commentElement);
+			});
+
+			/** @type {HTMLButtonElement} */
+			const replyButton = document.createElement("button");
+			replyButton.classList.add("option-button");
+			replyButton.textContent = "Reply";
+			replyButton.onclick = (_) => {
+				const content = prompt("Reply");
+				if (content != null && content.trim().length > 0) {
+					this.controller.addComment(thread.id, content);
+				}
+			};
+			threadElement.appendChild(replyButton);
+
+			/** @type {HTMLButtonElement} */
+			const resolveButton = document.createElement("button");
+			resolveButton.classList.add("option-button");
+			resolveButton.textContent = thread.resolved ? "Reopen" : "Resolve";
+			resolveButton.onclick = (_) => this.controller.resolveCommentThread(thread.id, !thread.resolved);
+			threadElement.appendChild(resolveButton);
+
+			listElement.appendChild(threadElement);
+		});
+	}
+
+	/**
+	 * Shows suggested changes in opened file in list and marks their ranges in editor
+	 *
+	 * @param suggestions {object[]} suggestions in opened file
+	 * @param canReview {boolean} shows buttons accepting and rejecting suggestions
+	 */
+	showSuggestions(suggestions, canReview) {
+		this._removeMarkers(this.suggestionMarkers);
+		this.suggestionMarkers = [];
+		const listElement = document.getElementById("suggestions");
+		while (listElement.firstChild) {
+			listElement.removeChild(listElement.firstChild);
+		}
+		suggestions.forEach(suggestion => {
+			this.suggestionMarkers.push(this._addMarker(suggestion.start, suggestion.end, "suggestion-range"));
+			const suggestionElement = document.createElement("li");
+			const rangeElement = document.createElement("div");
+			rangeElement.textContent = `${suggestion.author.name}, line ${suggestion.start.row + 1}`;
+			rangeElement.onclick = (_) => this.editor.gotoLine(suggestion.start.row + 1, suggestion.start.column);
+			suggestionElement.appendChild(rangeElement);
+			const textElement = document.createElement("pre");
+			textElement.textContent = suggestion.lines.join("\n");
+			suggestionElement.appendChild(textElement);
+
+			if (canReview) {
+				/** @type {HTMLButtonElement} */
+				const acceptButton = document.createElement("button");
+				acceptButton.classList.add("option-button");
+				acceptButton.textContent = "Accept";
+				acceptButton.onclick = (_) => this.controller.reviewSuggestion(suggestion.id, true);
+				suggestionElement.appendChild(acceptButton);
+
+				/** @type {HTMLButtonElement} */
+				const rejectButton = document.createElement("button");
+				rejectButton.classList.add("option-button");
+				rejectButton.textContent = "Reject";
+				rejectButton.onclick = (_) => this.controller.reviewSuggestion(suggestion.id, false);
+				suggestionElement.appendChild(rejectButton);
+			}
+
+			listElement.appendChild(suggestionElement);
+		});
+	}
+
+	/**
+	 * Shows checkpoints of project with buttons restoring them
+	 *
+	 * @param checkpoints {object[]} checkpoints from the newest
+	 */
+	showCheckpoints(checkpoints) {
+		const listElement = document.getElementById("checkpoints");
+		while (listElement.firstChild) {
+			listElement.removeChild(listElement.firstChild);
+		}
+		checkpoints.forEach(checkpoint => {
+			const checkpointElement = document.createElement("li");
+			const nameElement = document.createElement("div");
+			const createdAt = new Date(checkpoint.created_at).toLocaleString();
+			nameElement.textContent = `${checkpoint.name} (${checkpoint.author.name}, ${createdAt})`;
+			checkpointElement.appendChild(nameElement);
+
+			/** @type {HTMLButtonElement} */
+			const restoreFileButton = document.createElement("button");
+			restoreFileButton.classList.add("option-button");
+			restoreFileButton.textContent = "Restore file";
+			restoreFileButton.onclick = (_) => {
+				if (confirm(`Restore opened file to checkpoint ${checkpoint.name}?`)) {
+					let _ = this.controller.restoreCheckpoint(checkpoint.id, true);
+				}
+			};
+			checkpointElement.appendChild(restoreFileButton);
+
+			/** @type {HTMLButtonElement} */
+			const restoreProjectButton = document.createElement("button");
+			restoreProjectButton.classList.add("option-button");
+			restoreProjectButton.textContent = "Restore project";
+			restoreProjectButton.onclick = (_) => {
+				if (confirm(`Restore all files to checkpoint ${checkpoint.name}?`)) {
+					let _ = this.controller.restoreCheckpoint(checkpoint.id, false);
+				}
+			};
+			checkpointElement.appendChild(restoreProjectButton);
+
+			listElement.appendChild(checkpointElement);
+		});
+	}
+
+	/**
+	 * @param readOnly {boolean} true blocks changes in editor, user can only suggest them
+	 */
+	setReadOnly(readOnly) {
+		this.editor.setReadOnly(readOnly);
+	}
+
+	/**
+	 * Shows cursors and selections of other users in opened file, replacing previously shown ones
+	 *
+	 * @param presences {object[]} presence messages of sessions which have opened file shown in editor
+	 */
+	showPresences(presences) {
+		this._removeMarkers(this.presenceMarkers);
+		this.presenceMarkers = [];
+		presences.forEach(presence => {
+			presence.selections.forEach(selection => {
+				this.presenceMarkers.push(this._addMarker(selection.start, selection.end, "remote-selection"));
+			});
+			if (presence.cursor != null) {
+				const end = {row: presence.cursor.row, column: presence.cursor.column + 1};
+				this.presenceMarkers.push(this._addMarker(presence.cursor, end, "remote-cursor"));
+			}
+		});
+	}
+
+	/**
+	 * Adds marker with anchors keeping it in place when text changes
+	 *
+	 * @param start {Range}
+	 * @param end {Range}
+	 * @param className {string} CSS class of marker
+	 * @return {{id: number, range: object}} added marker
+	 * @private
+	 */
+	_addMarker(start, end, className) {
+		const document = this.editor.session.getDocument();
+		const range = new ace.Range(start.row, start.column, end.row, end.column);
+		range.start = document.createAnchor(range.start);
+		range.end = document.createAnchor(range.end);
+		const id = this.editor.session.addMarker(range, className, "text", false);
+		return {id: id, range: range};
+	}
+
+	/**
+	 * @param markers {{id: number, range: object}[]} markers added by _addMarker
+	 * @private
+	 */
+	_removeMarkers(markers) {
+		markers.forEach(marker => {
+			this.editor.session.removeMarker(marker.id);
+			marker.range.start.detach();
+			marker.range.end.detach();
+		});
+	}
+
 	showFileContent = (content) => {
 		const cursorPreChange = this.editor.selection.getCursor();
 		this.editor.setValue(content, null, true);
@@ -38,8 +38,24 @@ export class File{
 	 */
 	name;
 
-	constructor(id, name) {
+	/**
+	 * Id of folder containing file or null if file is in root of project
+	 *
+	 * @type {number | null}
+	 */
+	folderId;
+
+	/**
+	 * Path of file in project, used for displaying
+	 *
+	 * @type {string}
+	 */
+	path;
+
+	constructor(id, name, folderId = null) {
 		this.id = id;
 		this.name = name;
+		this.folderId = folderId;
+		this.path = name;
 	}
 }
\ No newline at end of file
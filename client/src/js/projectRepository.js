@@ -37,6 +37,97 @@ export default class ProjectRepository {
 
 	}
 
+	/**
+	 * Deletes project with given id. Only owner of project can delete it.
+	 *
+	 * @param id {number}
+	 * @return {Promise<void>} throws error message if project was not deleted
+	 */
+	async delete(id) {
+		const request = new Request(URI_TO_SERVER + "/projects/" + id, {
+			method: "DELETE",
+			credentials: "include"
+		});
+		const response = await fetch(request);
+		if (response.status === 401) {
+			throw NOT_LOGGED_IN_ERROR;
+		}
+		if (response.status !== 200) {
+			throw await response.json();
+		}
+	}
+
+	/**
+	 * @param projectId {number}
+	 * @return {Promise<object[]>} checkpoints of project, from the newest
+	 */
+	async getCheckpoints(projectId) {
+		const request = new Request(URI_TO_SERVER + "/projects/" + projectId + "/checkpoints", {
+			method: "GET",
+			credentials: "include"
+		});
+		const response = await fetch(request);
+		if (response.status === 401) {
+			throw NOT_LOGGED_IN_ERROR;
+		}
+		if (response.status !== 200) {
+			throw await response.json();
+		}
+		return response.json();
+	}
+
+	/**
+	 * Saves current content of all files of project as named checkpoint
+	 *
+	 * @param projectId {number}
+	 * @param name {string}
+	 * @return {Promise<object>} created checkpoint, throws error message if it was not created
+	 */
+	async createCheckpoint(projectId, name) {
+		const request = new Request(URI_TO_SERVER + "/projects/" + projectId + "/checkpoints", {
+			method: "POST",
+			body: JSON.stringify({name: name}),
+			credentials: "include",
+			headers: new Headers({
+				'content-type': 'application/json'
+			})
+		});
+		const response = await fetch(request);
+		if (response.status === 401) {
+			throw NOT_LOGGED_IN_ERROR;
+		}
+		if (response.status !== 200) {
+			throw await response.json();
+		}
+		return response.json();
+	}
+
+	/**
+	 * Restores content of files to their content in checkpoint
+	 *
+	 * @param projectId {number}
+	 * @param checkpointId {number}
+	 * @param fileId {?number} only this file is restored if set
+	 * @return {Promise<void>} throws error message if files were not restored
+	 */
+	async restoreCheckpoint(projectId, checkpointId, fileId = null) {
+		let url = URI_TO_SERVER + "/projects/" + projectId + "/checkpoints/" + checkpointId + "/restore";
+		if (fileId != null) {
+			url += "?file_id=" + fileId;
+		}
+		const request = new Request(url, {
+			method: "POST",
+			credentials: "include"
+		});
+		const response = await fetch(request);
+		if (response.status === 401) {
+			throw NOT_LOGGED_IN_ERROR;
+		}
+		if (response.status !== 200) {
+			throw await response.json();
+		}
+	}
+
 	async grantAccess(project, user) {
 
 	}
@@ -26,6 +26,16 @@ export default class ProjectsListController {
 		this.view.showSharedProjects(await sharedProjectsRequest);
 	}
 
+	async deleteProject(projectId) {
+		try {
+			await this.projectsRepository.delete(projectId);
+		} catch (e) {
+			console.error(e);
+			alert(e);
+		}
+		await this.loadProjects();
+	}
+
 	showProjectEditionSession(projectId) {
 		window.location = `editor.html?project_id=${projectId}`
 	}
@@ -69,6 +69,11 @@ export default class ProjectsListView {
 		const deleteButton = document.createElement("button");
 		deleteButton.classList.add("red-button");
 		deleteButton.innerText = "Delete";
+		deleteButton.addEventListener("click", (e) => {
+			if (confirm(`Delete project ${project.name.trim()}?`)) {
+				this.controller.deleteProject(project.id);
+			}
+		});
 		buttonsCell.appendChild(deleteButton);
 
 		return row;
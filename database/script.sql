@@ -13,18 +13,34 @@ CREATE TABLE projects
 	owner_id    INT       NOT NULL REFERENCES users (id) ON DELETE CASCADE ON UPDATE CASCADE
 );
 
+--- Folders without parent are in root of project
+CREATE TABLE folders
+(
+	id         SERIAL PRIMARY KEY,
+	name       CHAR(100) NOT NULL CHECK (length(name) > 0),
+	project_id INT       NOT NULL REFERENCES projects (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	parent_id  INT REFERENCES folders (id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE UNIQUE INDEX folders_unique_name ON folders (project_id, COALESCE(parent_id, 0), name);
+
+--- Files without folder are in root of project
 CREATE TABLE files
 (
 	id         SERIAL PRIMARY KEY,
 	name       CHAR(100) NOT NULL CHECK (length(name) > 0),
 	project_id INT       NOT NULL REFERENCES projects (id) ON DELETE CASCADE ON UPDATE CASCADE,
-	unique (name, project_id)
+	folder_id  INT REFERENCES folders (id) ON DELETE CASCADE ON UPDATE CASCADE
 );
 
+CREATE UNIQUE INDEX files_unique_name ON files (project_id, COALESCE(folder_id, 0), name);
+
+--- Users with role 'suggester' can't change files directly, they only suggest changes
 create TABLE projects_shared_for_users
 (
-	user_id    INT NOT NULL REFERENCES users (id) ON DELETE CASCADE ON UPDATE CASCADE,
-	project_id INT NOT NULL REFERENCES projects (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	user_id    INT         NOT NULL REFERENCES users (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	project_id INT         NOT NULL REFERENCES projects (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	role       VARCHAR(20) NOT NULL DEFAULT 'editor' CHECK (role IN ('editor', 'suggester')),
 	PRIMARY KEY (user_id, project_id)
 );
 
@@ -33,9 +49,117 @@ CREATE TABLE files_lines
 	file_id     INT NOT NULL REFERENCES files (id) ON DELETE CASCADE ON UPDATE CASCADE,
 	line_number INT NOT NULL CHECK ( line_number >= 0 ),
 	content     TEXT DEFAULT '',
+	--- User who modified line last, NULL if unknown or line was changed by server
+	author_id   INT         NULL REFERENCES users (id) ON DELETE SET NULL ON UPDATE CASCADE,
+	--- With time zone, so times of modification don't depend on TimeZone setting of database
+	modified_at TIMESTAMPTZ NULL,
 	PRIMARY KEY (file_id, line_number)
 );
 
+--- Used instead of files_lines when server runs with CRDT document model
+CREATE TABLE files_crdt_states
+(
+	file_id INT PRIMARY KEY REFERENCES files (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	state   TEXT NOT NULL
+);
+
+CREATE TABLE chat_messages
+(
+	id         SERIAL PRIMARY KEY,
+	project_id INT       NOT NULL REFERENCES projects (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	author_id  INT       NOT NULL REFERENCES users (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	content    TEXT      NOT NULL CHECK (length(content) > 0),
+	sent_at    TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX chat_messages_project ON chat_messages (project_id, id);
+
+--- Project members mentioned with @name in chat message
+CREATE TABLE chat_mentions
+(
+	message_id INT NOT NULL REFERENCES chat_messages (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	user_id    INT NOT NULL REFERENCES users (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	PRIMARY KEY (message_id, user_id)
+);
+
+--- Change of range of file suggested by user, waiting for review of project owner. Range is moved by server when file changes.
+CREATE TABLE suggestions
+(
+	id           SERIAL PRIMARY KEY,
+	file_id      INT  NOT NULL REFERENCES files (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	author_id    INT  NOT NULL REFERENCES users (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	start_row    INT  NOT NULL CHECK (start_row >= 0),
+	start_column INT  NOT NULL CHECK (start_column >= 0),
+	end_row      INT  NOT NULL CHECK (end_row >= 0),
+	end_column   INT  NOT NULL CHECK (end_column >= 0),
+	content      TEXT NOT NULL
+);
+
+--- Discussion about range of file. Range is moved by server when file changes.
+CREATE TABLE comment_threads
+(
+	id           SERIAL PRIMARY KEY,
+	file_id      INT     NOT NULL REFERENCES files (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	start_row    INT     NOT NULL CHECK (start_row >= 0),
+	start_column INT     NOT NULL CHECK (start_column >= 0),
+	end_row      INT     NOT NULL CHECK (end_row >= 0),
+	end_column   INT     NOT NULL CHECK (end_column >= 0),
+	resolved     BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE comments
+(
+	id        SERIAL PRIMARY KEY,
+	thread_id INT       NOT NULL REFERENCES comment_threads (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	author_id INT       NOT NULL REFERENCES users (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	content   TEXT      NOT NULL CHECK (length(content) > 0),
+	sent_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+--- Log of changes applied to files. Revisions of file are numbered from 1, revision 0 is content of file
+--- when its history started.
+CREATE TABLE files_changes
+(
+	file_id      INT       NOT NULL REFERENCES files (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	revision     INT       NOT NULL CHECK (revision > 0),
+	--- NULL for changes not made by user
+	author_id    INT REFERENCES users (id) ON DELETE SET NULL ON UPDATE CASCADE,
+	start_row    INT       NOT NULL CHECK (start_row >= 0),
+	start_column INT       NOT NULL CHECK (start_column >= 0),
+	end_row      INT       NOT NULL CHECK (end_row >= 0),
+	end_column   INT       NOT NULL CHECK (end_column >= 0),
+	content      TEXT      NOT NULL,
+	made_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
+	PRIMARY KEY (file_id, revision)
+);
+
+--- Content of file after given revision, so older content is rebuilt without replaying whole log
+CREATE TABLE files_snapshots
+(
+	file_id  INT  NOT NULL REFERENCES files (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	revision INT  NOT NULL CHECK (revision >= 0),
+	content  TEXT NOT NULL,
+	PRIMARY KEY (file_id, revision)
+);
+
+--- Named state of all files of project
+CREATE TABLE checkpoints
+(
+	id         SERIAL PRIMARY KEY,
+	project_id INT          NOT NULL REFERENCES projects (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	name       VARCHAR(200) NOT NULL CHECK (length(name) > 0),
+	author_id  INT          NOT NULL REFERENCES users (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	created_at TIMESTAMPTZ  NOT NULL DEFAULT now()
+);
+
+CREATE TABLE checkpoints_files
+(
+	checkpoint_id INT NOT NULL REFERENCES checkpoints (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	file_id       INT NOT NULL REFERENCES files (id) ON DELETE CASCADE ON UPDATE CASCADE,
+	revision      INT NOT NULL CHECK (revision >= 0),
+	PRIMARY KEY (checkpoint_id, file_id)
+);
+
 CREATE OR REPLACE FUNCTION create_user(_username varchar, _password_plain varchar) RETURNS INT
 	language plpgsql AS
 $body$
@@ -90,7 +214,29 @@ BEGIN
 END;
 $body$;
 
-CREATE OR REPLACE FUNCTION grant_access_to_project(_project_id INT, _user_id INT) RETURNS INT
+CREATE OR REPLACE FUNCTION update_project(_id INT, _name CHAR, _description CHAR) RETURNS INT
+	LANGUAGE plpgsql AS
+$body$
+DECLARE
+	_owner_id INT;
+BEGIN
+	SELECT owner_id FROM projects WHERE id = _id INTO _owner_id;
+	IF _owner_id IS NULL THEN
+		RETURN -1;
+	END IF;
+	IF _name IS NULL OR length(_name) = 0 THEN
+		RETURN -2;
+	END IF;
+	IF EXISTS(SELECT id FROM projects WHERE id <> _id AND name = _name AND owner_id = _owner_id) THEN
+		RETURN -3;
+	END IF;
+	UPDATE projects SET name = _name, description = _description WHERE id = _id;
+	RETURN 0;
+END;
+$body$;
+
+--- Shares project with user or changes role of user it is already shared with
+CREATE OR REPLACE FUNCTION grant_access_to_project(_project_id INT, _user_id INT, _role VARCHAR DEFAULT 'editor') RETURNS INT
 	LANGUAGE plpgsql AS
 $body$
 DECLARE
@@ -100,11 +246,11 @@ BEGIN
 	IF _owner_id IS NULL THEN
 		RETURN -1;
 	END IF;
-	IF _owner_id = _user_id OR
-	   EXISTS(SELECT user_id FROM projects_shared_for_users WHERE user_id = _user_id AND project_id = _project_id) THEN
+	IF _owner_id = _user_id THEN
 		RETURN 0;
 	END IF;
-	INSERT INTO projects_shared_for_users (user_id, project_id) VALUES (_user_id, _project_id);
+	INSERT INTO projects_shared_for_users (user_id, project_id, role) VALUES (_user_id, _project_id, _role)
+	ON CONFLICT (user_id, project_id) DO UPDATE SET role = EXCLUDED.role;
 	RETURN 0;
 END;
 $body$;
@@ -134,7 +280,28 @@ BEGIN
 END;
 $body$;
 
-CREATE OR REPLACE FUNCTION create_file(_project_id INT, _name CHAR) RETURNS INT
+CREATE OR REPLACE FUNCTION is_name_taken(_project_id INT, _folder_id INT, _name CHAR, _file_id INT, _own_folder_id INT) RETURNS BOOLEAN
+	LANGUAGE plpgsql AS
+$body$
+BEGIN
+	RETURN EXISTS(SELECT id
+				  FROM files
+				  WHERE project_id = _project_id
+					AND folder_id IS NOT DISTINCT FROM _folder_id
+					AND name = _name
+					AND id IS DISTINCT FROM _file_id)
+		OR EXISTS(SELECT id
+				  FROM folders
+				  WHERE project_id = _project_id
+					AND parent_id IS NOT DISTINCT FROM _folder_id
+					AND name = _name
+					AND id IS DISTINCT FROM _own_folder_id);
+END;
+$body$;
+
+COMMENT ON FUNCTION is_name_taken(INT, INT, CHAR, INT, INT) IS 'Checks if folder contains file or folder with given name, other than given file and folder';
+
+CREATE OR REPLACE FUNCTION create_file(_project_id INT, _name CHAR, _folder_id INT) RETURNS INT
 	LANGUAGE plpgsql AS
 $body$
 DECLARE
@@ -146,36 +313,92 @@ BEGIN
 	IF NOT EXISTS(SELECT id FROM projects WHERE id = _project_id) THEN
 		RETURN -2;
 	END IF;
-	IF EXISTS(SELECT id FROM files WHERE project_id = _project_id AND name = _name) THEN
+	IF _folder_id IS NOT NULL AND NOT EXISTS(SELECT id FROM folders WHERE id = _folder_id AND project_id = _project_id) THEN
+		RETURN -4;
+	END IF;
+	IF is_name_taken(_project_id, _folder_id, _name, NULL, NULL) THEN
 		RETURN -3;
 	END IF;
-	INSERT INTO files (name, project_id) VALUES (_name, _project_id) RETURNING id INTO _file_id;
+	INSERT INTO files (name, project_id, folder_id) VALUES (_name, _project_id, _folder_id) RETURNING id INTO _file_id;
 	RETURN _file_id;
 END;
 $body$;
 
-CREATE OR REPLACE FUNCTION update_file(_id INT, _new_name CHAR) RETURNS INT
+CREATE OR REPLACE FUNCTION update_file(_id INT, _new_name CHAR, _folder_id INT) RETURNS INT
 	LANGUAGE plpgsql AS
 $body$
 DECLARE
-	_old_name   CHAR;
 	_project_id INT;
 BEGIN
 	IF _new_name IS NULL OR length(_new_name) = 0 THEN
 		RETURN -1;
 	END IF;
-	SELECT name FROM files WHERE id = _id INTO _old_name;
-	IF _old_name IS NULL THEN
+	SELECT project_id FROM files WHERE id = _id INTO _project_id;
+	IF _project_id IS NULL THEN
 		RETURN -2;
 	end if;
-	If _old_name = _new_name THEN
-		RETURN 0;
+	IF _folder_id IS NOT NULL AND NOT EXISTS(SELECT id FROM folders WHERE id = _folder_id AND project_id = _project_id) THEN
+		RETURN -4;
 	END IF;
-	SELECT project_id FROM files WHERE id = _id INTO _project_id;
-	IF EXISTS(SELECT id FROM files WHERE id <> _id AND name = _new_name AND project_id = _project_id) THEN
+	IF is_name_taken(_project_id, _folder_id, _new_name, _id, NULL) THEN
+		RETURN -3;
+	END IF;
+	UPDATE files SET name = _new_name, folder_id = _folder_id WHERE id = _id;
+	RETURN 0;
+END;
+$body$;
+
+CREATE OR REPLACE FUNCTION create_folder(_project_id INT, _parent_id INT, _name CHAR) RETURNS INT
+	LANGUAGE plpgsql AS
+$body$
+DECLARE
+	_folder_id INT;
+BEGIN
+	IF _name IS NULL OR length(_name) = 0 THEN
+		RETURN -1;
+	END IF;
+	IF _parent_id IS NOT NULL AND NOT EXISTS(SELECT id FROM folders WHERE id = _parent_id AND project_id = _project_id) THEN
+		RETURN -4;
+	END IF;
+	IF is_name_taken(_project_id, _parent_id, _name, NULL, NULL) THEN
+		RETURN -3;
+	END IF;
+	INSERT INTO folders (name, project_id, parent_id) VALUES (_name, _project_id, _parent_id) RETURNING id INTO _folder_id;
+	RETURN _folder_id;
+END;
+$body$;
+
+--- Renames and moves folder. Folder can't be moved into itself or its subfolder.
+CREATE OR REPLACE FUNCTION update_folder(_id INT, _new_name CHAR, _parent_id INT) RETURNS INT
+	LANGUAGE plpgsql AS
+$body$
+DECLARE
+	_project_id INT;
+BEGIN
+	IF _new_name IS NULL OR length(_new_name) = 0 THEN
+		RETURN -1;
+	END IF;
+	SELECT project_id FROM folders WHERE id = _id INTO _project_id;
+	IF _project_id IS NULL THEN
+		RETURN -2;
+	END IF;
+	IF _parent_id IS NOT NULL THEN
+		IF NOT EXISTS(SELECT id FROM folders WHERE id = _parent_id AND project_id = _project_id) THEN
+			RETURN -4;
+		END IF;
+		IF EXISTS(WITH RECURSIVE ancestors(id, parent_id) AS (
+			SELECT id, parent_id FROM folders WHERE id = _parent_id
+			UNION ALL
+			SELECT f.id, f.parent_id FROM folders f JOIN ancestors a ON f.id = a.parent_id
+		)
+				  SELECT id FROM ancestors WHERE id = _id) THEN
+			RETURN -4;
+		END IF;
+	END IF;
+	IF is_name_taken(_project_id, _parent_id, _new_name, NULL, _id) THEN
 		RETURN -3;
 	END IF;
-	UPDATE files SET name = _new_name WHERE id = _id;
+	UPDATE folders SET name = _new_name, parent_id = _parent_id WHERE id = _id;
 	RETURN 0;
 END;
 $body$;
@@ -1,3 +1,45 @@
 pub mod projects;
 pub mod users;
-pub mod files;
\ No newline at end of file
+pub mod files;
+pub mod folders;
+pub mod history;
+
+use actix_web::body::Body;
+use actix_web::HttpResponse;
+use actix_web::dev::HttpResponseBuilder;
+use actix_http::http::StatusCode;
+use log::{error, warn};
+use crate::repositories::DatabaseError;
+use crate::services::projects_files::ServiceCreationError;
+
+pub(crate) fn user_does_not_exist(response_builder: &mut HttpResponseBuilder) -> HttpResponse<Body> {
+	warn!("Recived request from session with not existing user, possible attack");//TODO ip bla bla bla
+	response_builder
+		.status(StatusCode::INTERNAL_SERVER_ERROR)
+		.json("You don't exist")
+}
+
+pub(crate) fn database_error(response_builder: &mut HttpResponseBuilder, err: DatabaseError) -> HttpResponse<Body> {
+	error!("Request failed because of database error: {}", err);
+	response_builder
+		.status(StatusCode::SERVICE_UNAVAILABLE)
+		.json("Database is not available, try again later")
+}
+
+/// Response for user with suggester role who tried to change files
+pub(crate) fn not_allowed(response_builder: &mut HttpResponseBuilder) -> HttpResponse<Body> {
+	response_builder
+		.status(StatusCode::FORBIDDEN)
+		.json("You can only suggest changes in this project")
+}
+
+/// Response for user who can't use projects files service
+pub(crate) fn files_service_error(response_builder: &mut HttpResponseBuilder, err: ServiceCreationError) -> HttpResponse<Body> {
+	match err {
+		ServiceCreationError::UserDoesNotExists => user_does_not_exist(response_builder),
+		ServiceCreationError::DatabaseError(err) => database_error(response_builder, err),
+		_ => response_builder
+			.status(StatusCode::NOT_FOUND)
+			.json("Project does not exist or you dont have access to it")
+	}
+}
@@ -0,0 +1,354 @@
+use actix_web::body::Body;
+use actix_web::{get, post, put, delete, web, HttpResponse};
+use actix_web::dev::HttpResponseBuilder;
+use actix_session::Session;
+use actix_http::http::StatusCode;
+use actix::Addr;
+use serde::Deserialize;
+use log::{info, error};
+use crate::session_manager::get_user_id;
+use crate::services::projects_files;
+use crate::services::projects_files::{CreationError, UpdateError, DeletionError};
+use crate::repositories::{run, DatabaseError};
+use crate::controllers::{database_error, files_service_error, not_allowed};
+use crate::models::ProjectFile;
+use crate::document;
+use crate::server;
+use crate::server::DocumentModel;
+
+#[derive(Deserialize, Debug)]
+pub struct FileDto {
+	name: String,
+	/// None if file is in root of project
+	folder_id: Option<i32>,
+}
+
+#[get("/projects/{id}/files")]
+pub async fn get_files(web::Path(id): web::Path<i32>, session: Session) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user_id;
+	match get_user_id(&session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let result = run(move || {
+		let service = projects_files::new(user_id, id)?;
+		Ok(service.get_all())
+	}).await;
+	return match result {
+		Ok(Ok(files)) => response_builder.json(files),
+		Ok(Err(err)) => database_error(&mut response_builder, err),
+		Err(err) => files_service_error(&mut response_builder, err)
+	};
+}
+
+#[get("/projects/{id}/files/{file_id}")]
+pub async fn get_file(web::Path((id, file_id)): web::Path<(i32, i32)>, session: Session) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user_id;
+	match get_user_id(&session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let result = run(move || {
+		let service = projects_files::new(user_id, id)?;
+		Ok(service.get(file_id))
+	}).await;
+	return match result {
+		Ok(Ok(Some(file))) => response_builder.json(file),
+		Ok(Ok(None)) => file_does_not_exist(&mut response_builder),
+		Ok(Err(err)) => database_error(&mut response_builder, err),
+		Err(err) => files_service_error(&mut response_builder, err)
+	};
+}
+
+#[post("/projects/{id}/files")]
+pub async fn create_file(
+	web::Path(id): web::Path<i32>,
+	file_dto: web::Json<FileDto>,
+	session: Session,
+	srv: web::Data<Addr<server::EditorServer>>,
+) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user_id;
+	match get_user_id(&session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let file_dto = file_dto.into_inner();
+	let new_file = ProjectFile {
+		id: None,
+		name: file_dto.name,
+		project_id: id,
+		folder_id: file_dto.folder_id,
+	};
+	let result = run(move || {
+		let service = projects_files::new(user_id, id)?;
+		Ok(service.create(new_file))
+	}).await;
+	return match result {
+		Ok(Ok(file)) => {
+			info!("User {} created file {} in project {}", user_id, file.name, id);
+			srv.do_send(server::FileCreated {
+				project_id: id,
+				id: file.id.expect("That would mean that file was not created"),
+				name: file.name.clone(),
+				folder_id: file.folder_id,
+			});
+			response_builder.json(file)
+		}
+		Ok(Err(error)) => {
+			match error {
+				CreationError::NotAllowed => not_allowed(&mut response_builder),
+				CreationError::IllegalName => response_builder
+					.status(StatusCode::BAD_REQUEST)
+					.json("Invalid name"),
+				CreationError::DuplicateNames => response_builder
+					.status(StatusCode::BAD_REQUEST)
+					.json("File or folder with that name already exists"),
+				CreationError::FolderDoesNotExist => response_builder
+					.status(StatusCode::NOT_FOUND)
+					.json("Folder does not exist"),
+				CreationError::DatabaseError(err) => database_error(&mut response_builder, err)
+			}
+		}
+		Err(err) => files_service_error(&mut response_builder, err)
+	};
+}
+
+/// Renames file and moves it to given folder
+#[put("/projects/{id}/files/{file_id}")]
+pub async fn update_file(
+	web::Path((id, file_id)): web::Path<(i32, i32)>,
+	file_dto: web::Json<FileDto>,
+	session: Session,
+	srv: web::Data<Addr<server::EditorServer>>,
+) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user_id;
+	match get_user_id(&session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let file_dto = file_dto.into_inner();
+	let result = run(move || {
+		let service = projects_files::new(user_id, id)?;
+		// Update is not bound to project, so file has to be found in this project first
+		Ok(match service.get(file_id)? {
+			Some(old_file) => {
+				let mut file = old_file.clone();
+				file.name = file_dto.name;
+				file.folder_id = file_dto.folder_id;
+				service.update(file).map(|file| (old_file, file))
+			}
+			None => Err(UpdateError::FileDoesNotExists)
+		})
+	}).await;
+	return match result {
+		Ok(Ok((old_file, file))) => {
+			info!("User {} updated file {} in project {}", user_id, file_id, id);
+			if old_file.name.trim() != file.name.trim() {
+				srv.do_send(server::FileRenamed {
+					project_id: id,
+					id: file_id,
+					name: file.name.clone(),
+				});
+			}
+			if old_file.folder_id != file.folder_id {
+				srv.do_send(server::FileMoved {
+					project_id: id,
+					id: file_id,
+					folder_id: file.folder_id,
+				});
+			}
+			response_builder.json(file)
+		}
+		Ok(Err(error)) => {
+			match error {
+				UpdateError::NotAllowed => not_allowed(&mut response_builder),
+				UpdateError::IllegalName => response_builder
+					.status(StatusCode::BAD_REQUEST)
+					.json("Invalid name"),
+				UpdateError::DuplicateName => response_builder
+					.status(StatusCode::BAD_REQUEST)
+					.json("File or folder with that name already exists"),
+				UpdateError::FileDoesNotExists => file_does_not_exist(&mut response_builder),
+				UpdateError::FolderDoesNotExist => response_builder
+					.status(StatusCode::NOT_FOUND)
+					.json("Folder does not exist"),
+				UpdateError::DatabaseError(err) => database_error(&mut response_builder, err)
+			}
+		}
+		Err(err) => files_service_error(&mut response_builder, err)
+	};
+}
+
+#[delete("/projects/{id}/files/{file_id}")]
+pub async fn delete_file(
+	web::Path((id, file_id)): web::Path<(i32, i32)>,
+	session: Session,
+	srv: web::Data<Addr<server::EditorServer>>,
+) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user_id;
+	match get_user_id(&session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let result = run(move || {
+		let service = projects_files::new(user_id, id)?;
+		Ok(match service.get(file_id)? {
+			Some(file) => service.delete(file),
+			None => Err(DeletionError::FileDoesNotExistInProject)
+		})
+	}).await;
+	return match result {
+		Ok(Ok(_)) => {
+			info!("User {} deleted file {} from project {}", user_id, file_id, id);
+			srv.do_send(server::FileDeleted {
+				project_id: id,
+				id: file_id,
+			});
+			response_builder.json("File deleted")
+		}
+		Ok(Err(DeletionError::NotAllowed)) => not_allowed(&mut response_builder),
+		Ok(Err(DeletionError::FileDoesNotExistInProject)) => file_does_not_exist(&mut response_builder),
+		Ok(Err(DeletionError::DatabaseError(err))) => database_error(&mut response_builder, err),
+		Err(err) => files_service_error(&mut response_builder, err)
+	};
+}
+
+/// Returns whole content of file as plain text. Content of file opened in editor contains also
+/// changes which are not saved in database yet.
+#[get("/projects/{id}/files/{file_id}/content")]
+pub async fn get_file_content(
+	web::Path((id, file_id)): web::Path<(i32, i32)>,
+	session: Session,
+	srv: web::Data<Addr<server::EditorServer>>,
+) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	if let Some(response) = check_file_access(&session, id, file_id, false, &mut response_builder).await {
+		return response;
+	}
+	let opened_content = srv.send(server::ReadFileContent {
+		project_id: id,
+		file_id,
+	}).await;
+	if let Ok(Some(content)) = opened_content {
+		return response_builder
+			.content_type("text/plain; charset=utf-8")
+			.body(content);
+	}
+	let model = DocumentModel::from_env();
+	return match run(move || document::read_content(file_id, model)).await {
+		Ok(content) => response_builder
+			.content_type("text/plain; charset=utf-8")
+			.body(content),
+		Err(err) => database_error(&mut response_builder, err)
+	};
+}
+
+/// Replaces whole content of file with request body. Sessions editing file get new content
+/// as change made in editor.
+#[put("/projects/{id}/files/{file_id}/content")]
+pub async fn write_file_content(
+	web::Path((id, file_id)): web::Path<(i32, i32)>,
+	content: String,
+	session: Session,
+	srv: web::Data<Addr<server::EditorServer>>,
+) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	if let Some(response) = check_file_access(&session, id, file_id, true, &mut response_builder).await {
+		return response;
+	}
+	return match replace_content(&srv, id, file_id, content, get_user_id(&session)).await {
+		Ok(_) => response_builder.json("Content saved"),
+		Err(err) => database_error(&mut response_builder, err)
+	};
+}
+
+/// Replaces whole content of file in its document if project is opened in editor, or in database
+/// otherwise. New content is saved in history of file as change made by author.
+pub(crate) async fn replace_content(
+	srv: &Addr<server::EditorServer>,
+	project_id: i32,
+	file_id: i32,
+	content: String,
+	author_id: Option<i32>,
+) -> Result<(), DatabaseError> {
+	let written = srv.send(server::WriteFileContent {
+		project_id,
+		file_id,
+		content,
+		author_id,
+	}).await;
+	match written {
+		Ok(result) => {
+			result?.await?;
+			info!("Replaced content of file {}", file_id);
+			Ok(())
+		}
+		Err(err) => {
+			error!("Failed to replace content of file {}: {}", file_id, err);
+			Err(DatabaseError {
+				error_code: DatabaseError::CANCELED,
+				message: err.to_string(),
+			})
+		}
+	}
+}
+
+/// Checks if logged in user has access to file in project, and if user can modify it when `modify`
+/// is set.
+///
+/// # Returns
+///
+/// Error response if user can't access file, None otherwise
+async fn check_file_access(session: &Session, project_id: i32, file_id: i32, modify: bool, response_builder: &mut HttpResponseBuilder) -> Option<HttpResponse<Body>> {
+	let user_id;
+	match get_user_id(session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return Some(response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in"));
+		}
+	}
+	let result = run(move || {
+		let service = projects_files::new(user_id, project_id)?;
+		Ok(service.get(file_id).map(|file| (file, service.can_modify())))
+	}).await;
+	match result {
+		Ok(Ok((Some(_), false))) if modify => Some(not_allowed(response_builder)),
+		Ok(Ok((Some(_), _))) => None,
+		Ok(Ok((None, _))) => Some(file_does_not_exist(response_builder)),
+		Ok(Err(err)) => Some(database_error(response_builder, err)),
+		Err(err) => Some(files_service_error(response_builder, err))
+	}
+}
+
+fn file_does_not_exist(response_builder: &mut HttpResponseBuilder) -> HttpResponse<Body> {
+	response_builder
+		.status(StatusCode::NOT_FOUND)
+		.json("File does not exist")
+}
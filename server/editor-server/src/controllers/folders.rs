@@ -0,0 +1,188 @@
+use actix_web::body::Body;
+use actix_web::{get, post, put, delete, web, HttpResponse};
+use actix_web::dev::HttpResponseBuilder;
+use actix_session::Session;
+use actix_http::http::StatusCode;
+use actix::Addr;
+use serde::Deserialize;
+use log::info;
+use crate::session_manager::get_user_id;
+use crate::services::projects_files;
+use crate::services::projects_files::{FolderSaveError, FolderDeletionError};
+use crate::repositories::run;
+use crate::controllers::{database_error, files_service_error, not_allowed};
+use crate::models::Folder;
+use crate::server;
+
+#[derive(Deserialize, Debug)]
+pub struct FolderDto {
+	name: String,
+	/// None if folder is in root of project
+	parent_id: Option<i32>,
+}
+
+/// Returns folders and files of project as a tree
+#[get("/projects/{id}/tree")]
+pub async fn get_tree(web::Path(id): web::Path<i32>, session: Session) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user_id;
+	match get_user_id(&session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let result = run(move || {
+		let service = projects_files::new(user_id, id)?;
+		Ok(service.get_tree())
+	}).await;
+	return match result {
+		Ok(Ok(tree)) => response_builder.json(tree),
+		Ok(Err(err)) => database_error(&mut response_builder, err),
+		Err(err) => files_service_error(&mut response_builder, err)
+	};
+}
+
+#[post("/projects/{id}/folders")]
+pub async fn create_folder(
+	web::Path(id): web::Path<i32>,
+	folder_dto: web::Json<FolderDto>,
+	session: Session,
+	srv: web::Data<Addr<server::EditorServer>>,
+) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user_id;
+	match get_user_id(&session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let folder_dto = folder_dto.into_inner();
+	let new_folder = Folder {
+		id: None,
+		name: folder_dto.name,
+		project_id: id,
+		parent_id: folder_dto.parent_id,
+	};
+	let result = run(move || {
+		let service = projects_files::new(user_id, id)?;
+		Ok(service.create_folder(new_folder))
+	}).await;
+	return match result {
+		Ok(Ok(folder)) => {
+			info!("User {} created folder {} in project {}", user_id, folder.name, id);
+			srv.do_send(server::FolderChanged { folder: folder.clone() });
+			response_builder.json(folder)
+		}
+		Ok(Err(err)) => folder_save_error(&mut response_builder, err),
+		Err(err) => files_service_error(&mut response_builder, err)
+	};
+}
+
+/// Renames folder and moves it to given parent folder
+#[put("/projects/{id}/folders/{folder_id}")]
+pub async fn update_folder(
+	web::Path((id, folder_id)): web::Path<(i32, i32)>,
+	folder_dto: web::Json<FolderDto>,
+	session: Session,
+	srv: web::Data<Addr<server::EditorServer>>,
+) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user_id;
+	match get_user_id(&session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let folder_dto = folder_dto.into_inner();
+	let result = run(move || {
+		let service = projects_files::new(user_id, id)?;
+		Ok(match service.get_folder(folder_id)? {
+			Some(mut folder) => {
+				folder.name = folder_dto.name;
+				folder.parent_id = folder_dto.parent_id;
+				service.update_folder(folder)
+			}
+			None => Err(FolderSaveError::FolderDoesNotExist)
+		})
+	}).await;
+	return match result {
+		Ok(Ok(folder)) => {
+			info!("User {} updated folder {} in project {}", user_id, folder_id, id);
+			srv.do_send(server::FolderChanged { folder: folder.clone() });
+			response_builder.json(folder)
+		}
+		Ok(Err(err)) => folder_save_error(&mut response_builder, err),
+		Err(err) => files_service_error(&mut response_builder, err)
+	};
+}
+
+/// Deletes folder with all its subfolders and files
+#[delete("/projects/{id}/folders/{folder_id}")]
+pub async fn delete_folder(
+	web::Path((id, folder_id)): web::Path<(i32, i32)>,
+	session: Session,
+	srv: web::Data<Addr<server::EditorServer>>,
+) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user_id;
+	match get_user_id(&session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let result = run(move || {
+		let service = projects_files::new(user_id, id)?;
+		Ok(match service.get_folder(folder_id)? {
+			Some(folder) => service.delete_folder(folder),
+			None => Err(FolderDeletionError::FolderDoesNotExistInProject)
+		})
+	}).await;
+	return match result {
+		Ok(Ok(file_ids)) => {
+			info!("User {} deleted folder {} with {} files from project {}", user_id, folder_id, file_ids.len(), id);
+			srv.do_send(server::FolderDeleted {
+				project_id: id,
+				id: folder_id,
+				file_ids,
+			});
+			response_builder.json("Folder deleted")
+		}
+		Ok(Err(FolderDeletionError::NotAllowed)) => not_allowed(&mut response_builder),
+		Ok(Err(FolderDeletionError::FolderDoesNotExistInProject)) => response_builder
+			.status(StatusCode::NOT_FOUND)
+			.json("Folder does not exist"),
+		Ok(Err(FolderDeletionError::DatabaseError(err))) => database_error(&mut response_builder, err),
+		Err(err) => files_service_error(&mut response_builder, err)
+	};
+}
+
+fn folder_save_error(response_builder: &mut HttpResponseBuilder, err: FolderSaveError) -> HttpResponse<Body> {
+	match err {
+		FolderSaveError::NotAllowed => not_allowed(response_builder),
+		FolderSaveError::IllegalName => response_builder
+			.status(StatusCode::BAD_REQUEST)
+			.json("Invalid name"),
+		FolderSaveError::DuplicateName => response_builder
+			.status(StatusCode::BAD_REQUEST)
+			.json("File or folder with that name already exists"),
+		FolderSaveError::IllegalParent => response_builder
+			.status(StatusCode::BAD_REQUEST)
+			.json("Folder can't be placed there"),
+		FolderSaveError::FolderDoesNotExist => response_builder
+			.status(StatusCode::NOT_FOUND)
+			.json("Folder does not exist"),
+		FolderSaveError::DatabaseError(err) => database_error(response_builder, err)
+	}
+}
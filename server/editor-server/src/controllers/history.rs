@@ -0,0 +1,436 @@
+use actix_web::body::Body;
+use actix_web::{get, post, web, HttpResponse};
+use actix_web::dev::HttpResponseBuilder;
+use actix_http::http::StatusCode;
+use actix::Addr;
+use actix_session::Session;
+use serde::Deserialize;
+use std::collections::HashMap;
+use log::info;
+use crate::session_manager::get_user_id;
+use crate::services::history;
+use crate::services::history::{CheckpointCreationError, RestoreError, DiffVersion, DiffError, BlameError};
+use crate::repositories::run;
+use crate::controllers::{database_error, files_service_error, not_allowed};
+use crate::controllers::files::replace_content;
+use crate::server;
+use crate::server::DocumentModel;
+
+/// How many revisions are returned at once if request does not set limit
+const DEFAULT_REVISIONS_LIMIT: i64 = 100;
+const MAX_REVISIONS_LIMIT: i64 = 1000;
+
+#[derive(Deserialize, Debug)]
+pub struct RevisionsQuery {
+	/// Only revisions older than this one are returned
+	before: Option<i32>,
+	limit: Option<i64>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CheckpointDto {
+	name: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RestoreQuery {
+	/// Only this file is restored if set
+	file_id: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FileDiffQuery {
+	/// Older revision of file, `from_checkpoint` can be used instead
+	from: Option<i32>,
+	from_checkpoint: Option<i32>,
+	/// Newer revision of file, current content is used if neither this nor `to_checkpoint` is set
+	to: Option<i32>,
+	to_checkpoint: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ProjectDiffQuery {
+	from_checkpoint: i32,
+	/// Current files are used if not set
+	to_checkpoint: Option<i32>,
+}
+
+/// Version of file selected by revision or checkpoint. Error if both are set.
+fn diff_version(revision: Option<i32>, checkpoint_id: Option<i32>) -> Result<Option<DiffVersion>, ()> {
+	match (revision, checkpoint_id) {
+		(Some(_), Some(_)) => Err(()),
+		(Some(revision), None) => Ok(Some(DiffVersion::Revision(revision))),
+		(None, Some(checkpoint_id)) => Ok(Some(DiffVersion::Checkpoint(checkpoint_id))),
+		(None, None) => Ok(None)
+	}
+}
+
+fn diff_error(response_builder: &mut HttpResponseBuilder, err: DiffError) -> HttpResponse<Body> {
+	match err {
+		DiffError::FileDoesNotExist => response_builder
+			.status(StatusCode::NOT_FOUND)
+			.json("File does not exist"),
+		DiffError::CheckpointDoesNotExist => response_builder
+			.status(StatusCode::NOT_FOUND)
+			.json("Checkpoint does not exist"),
+		DiffError::FileIsNotInCheckpoint => response_builder
+			.status(StatusCode::NOT_FOUND)
+			.json("File is not in checkpoint"),
+		DiffError::RevisionNotSaved => response_builder
+			.status(StatusCode::NOT_FOUND)
+			.json("Revision is not saved in history of file"),
+		DiffError::DatabaseError(err) => database_error(response_builder, err)
+	}
+}
+
+/// Checks if user can access project and saves changes of its files opened in editor.
+///
+/// # Returns
+///
+/// Revisions of opened files, or error response if user can't access project or changes could not be saved
+async fn flush_documents(
+	srv: &Addr<server::EditorServer>,
+	user_id: i32,
+	project_id: i32,
+	response_builder: &mut HttpResponseBuilder,
+) -> Result<HashMap<i32, i32>, HttpResponse<Body>> {
+	if let Err(err) = run(move || history::new(user_id, project_id, DocumentModel::from_env()).map(|_| ())).await {
+		return Err(files_service_error(response_builder, err));
+	}
+	match srv.send(server::FlushDocuments { project_id }).await {
+		Ok(Some(revisions)) => Ok(revisions),
+		_ => Err(response_builder
+			.status(StatusCode::SERVICE_UNAVAILABLE)
+			.json("Changes of opened files could not be saved, try again later"))
+	}
+}
+
+/// Returns revisions of file from the newest
+#[get("/projects/{id}/files/{file_id}/history")]
+pub async fn get_revisions(
+	web::Path((id, file_id)): web::Path<(i32, i32)>,
+	query: web::Query<RevisionsQuery>,
+	session: Session,
+) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user_id;
+	match get_user_id(&session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let before = query.before;
+	let limit = query.limit.unwrap_or(DEFAULT_REVISIONS_LIMIT).clamp(1, MAX_REVISIONS_LIMIT);
+	let result = run(move || {
+		let service = history::new(user_id, id, DocumentModel::from_env())?;
+		Ok(service.get_revisions(file_id, before, limit))
+	}).await;
+	return match result {
+		Ok(Ok(Some(revisions))) => response_builder.json(revisions),
+		Ok(Ok(None)) => response_builder
+			.status(StatusCode::NOT_FOUND)
+			.json("File does not exist"),
+		Ok(Err(err)) => database_error(&mut response_builder, err),
+		Err(err) => files_service_error(&mut response_builder, err)
+	};
+}
+
+/// Returns content of file in given revision as plain text
+#[get("/projects/{id}/files/{file_id}/history/{revision}")]
+pub async fn get_content_in_revision(
+	web::Path((id, file_id, revision)): web::Path<(i32, i32, i32)>,
+	session: Session,
+) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user_id;
+	match get_user_id(&session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let result = run(move || {
+		let service = history::new(user_id, id, DocumentModel::from_env())?;
+		Ok(service.get_content(file_id, revision))
+	}).await;
+	return match result {
+		Ok(Ok(Some(content))) => response_builder
+			.content_type("text/plain; charset=utf-8")
+			.body(content),
+		Ok(Ok(None)) => response_builder
+			.status(StatusCode::NOT_FOUND)
+			.json("File or revision does not exist"),
+		Ok(Err(err)) => database_error(&mut response_builder, err),
+		Err(err) => files_service_error(&mut response_builder, err)
+	};
+}
+
+#[get("/projects/{id}/checkpoints")]
+pub async fn get_checkpoints(web::Path(id): web::Path<i32>, session: Session) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user_id;
+	match get_user_id(&session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let result = run(move || {
+		let service = history::new(user_id, id, DocumentModel::from_env())?;
+		Ok(service.get_checkpoints())
+	}).await;
+	return match result {
+		Ok(Ok(checkpoints)) => response_builder.json(checkpoints),
+		Ok(Err(err)) => database_error(&mut response_builder, err),
+		Err(err) => files_service_error(&mut response_builder, err)
+	};
+}
+
+#[get("/projects/{id}/checkpoints/{checkpoint_id}")]
+pub async fn get_checkpoint(web::Path((id, checkpoint_id)): web::Path<(i32, i32)>, session: Session) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user_id;
+	match get_user_id(&session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let result = run(move || {
+		let service = history::new(user_id, id, DocumentModel::from_env())?;
+		Ok(service.get_checkpoint(checkpoint_id))
+	}).await;
+	return match result {
+		Ok(Ok(Some(checkpoint))) => response_builder.json(checkpoint),
+		Ok(Ok(None)) => response_builder
+			.status(StatusCode::NOT_FOUND)
+			.json("Checkpoint does not exist"),
+		Ok(Err(err)) => database_error(&mut response_builder, err),
+		Err(err) => files_service_error(&mut response_builder, err)
+	};
+}
+
+/// Creates checkpoint with current content of all files of project, including changes made in
+/// editor which were not saved yet
+#[post("/projects/{id}/checkpoints")]
+pub async fn create_checkpoint(
+	web::Path(id): web::Path<i32>,
+	checkpoint_dto: web::Json<CheckpointDto>,
+	session: Session,
+	srv: web::Data<Addr<server::EditorServer>>,
+) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user_id;
+	match get_user_id(&session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let opened_revisions = match flush_documents(&srv, user_id, id, &mut response_builder).await {
+		Ok(revisions) => revisions,
+		Err(response) => return response
+	};
+	let result = run(move || {
+		let service = history::new(user_id, id, DocumentModel::from_env())?;
+		Ok(service.create_checkpoint(&checkpoint_dto.name, &opened_revisions))
+	}).await;
+	return match result {
+		Ok(Ok(checkpoint)) => {
+			info!("User {} created checkpoint {} in project {}", user_id, checkpoint.id, id);
+			response_builder.json(checkpoint)
+		}
+		Ok(Err(CheckpointCreationError::IllegalName)) => response_builder
+			.status(StatusCode::BAD_REQUEST)
+			.json("Name of checkpoint must have from 1 to 200 characters"),
+		Ok(Err(CheckpointCreationError::DatabaseError(err))) => database_error(&mut response_builder, err),
+		Err(err) => files_service_error(&mut response_builder, err)
+	};
+}
+
+/// Restores content of all files of project, or only one file, to their content in checkpoint.
+/// Sessions editing project get restored content as change made in editor.
+#[post("/projects/{id}/checkpoints/{checkpoint_id}/restore")]
+pub async fn restore_checkpoint(
+	web::Path((id, checkpoint_id)): web::Path<(i32, i32)>,
+	query: web::Query<RestoreQuery>,
+	session: Session,
+	srv: web::Data<Addr<server::EditorServer>>,
+) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user_id;
+	match get_user_id(&session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let file_id = query.file_id;
+	let result = run(move || {
+		let service = history::new(user_id, id, DocumentModel::from_env())?;
+		Ok(service.get_restored_contents(checkpoint_id, file_id))
+	}).await;
+	let contents = match result {
+		Ok(Ok(contents)) => contents,
+		Ok(Err(RestoreError::NotAllowed)) => return not_allowed(&mut response_builder),
+		Ok(Err(RestoreError::CheckpointDoesNotExist)) => return response_builder
+			.status(StatusCode::NOT_FOUND)
+			.json("Checkpoint does not exist"),
+		Ok(Err(RestoreError::FileIsNotInCheckpoint)) => return response_builder
+			.status(StatusCode::NOT_FOUND)
+			.json("File is not in checkpoint"),
+		Ok(Err(RestoreError::RevisionNotSaved)) => return response_builder
+			.status(StatusCode::SERVICE_UNAVAILABLE)
+			.json("Checkpoint is not saved yet, try again later"),
+		Ok(Err(RestoreError::DatabaseError(err))) => return database_error(&mut response_builder, err),
+		Err(err) => return files_service_error(&mut response_builder, err)
+	};
+	let restored_files = contents.len();
+	for (restored_file_id, content) in contents {
+		if let Err(err) = replace_content(&srv, id, restored_file_id, content, Some(user_id)).await {
+			return database_error(&mut response_builder, err);
+		}
+	}
+	info!("User {} restored {} files of project {} to checkpoint {}", user_id, restored_files, id, checkpoint_id);
+	response_builder.json(format!("Restored {} files", restored_files))
+}
+
+/// Returns differences between two versions of file as unified diff and list of hunks
+#[get("/projects/{id}/files/{file_id}/diff")]
+pub async fn get_file_diff(
+	web::Path((id, file_id)): web::Path<(i32, i32)>,
+	query: web::Query<FileDiffQuery>,
+	session: Session,
+	srv: web::Data<Addr<server::EditorServer>>,
+) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user_id;
+	match get_user_id(&session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let from;
+	let to;
+	match (diff_version(query.from, query.from_checkpoint), diff_version(query.to, query.to_checkpoint)) {
+		(Ok(Some(from_version)), Ok(to_version)) => {
+			from = from_version;
+			to = to_version.unwrap_or(DiffVersion::Current);
+		}
+		_ => {
+			return response_builder
+				.status(StatusCode::BAD_REQUEST)
+				.json("Set one of from and from_checkpoint, and at most one of to and to_checkpoint");
+		}
+	}
+	let opened_revisions = match to {
+		DiffVersion::Current => match flush_documents(&srv, user_id, id, &mut response_builder).await {
+			Ok(revisions) => revisions,
+			Err(response) => return response
+		},
+		_ => HashMap::new()
+	};
+	let result = run(move || {
+		let service = history::new(user_id, id, DocumentModel::from_env())?;
+		Ok(service.diff_file(file_id, from, to, &opened_revisions))
+	}).await;
+	return match result {
+		Ok(Ok(file_diff)) => response_builder.json(file_diff),
+		Ok(Err(err)) => diff_error(&mut response_builder, err),
+		Err(err) => files_service_error(&mut response_builder, err)
+	};
+}
+
+/// Returns differences between files of project in checkpoint and in other checkpoint or current files.
+/// Only changed files are returned.
+#[get("/projects/{id}/diff")]
+pub async fn get_project_diff(
+	web::Path(id): web::Path<i32>,
+	query: web::Query<ProjectDiffQuery>,
+	session: Session,
+	srv: web::Data<Addr<server::EditorServer>>,
+) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user_id;
+	match get_user_id(&session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let from_checkpoint = query.from_checkpoint;
+	let to_checkpoint = query.to_checkpoint;
+	let opened_revisions = match to_checkpoint {
+		None => match flush_documents(&srv, user_id, id, &mut response_builder).await {
+			Ok(revisions) => revisions,
+			Err(response) => return response
+		},
+		Some(_) => HashMap::new()
+	};
+	let result = run(move || {
+		let service = history::new(user_id, id, DocumentModel::from_env())?;
+		Ok(service.diff_project(from_checkpoint, to_checkpoint, &opened_revisions))
+	}).await;
+	return match result {
+		Ok(Ok(diffs)) => response_builder.json(diffs),
+		Ok(Err(err)) => diff_error(&mut response_builder, err),
+		Err(err) => files_service_error(&mut response_builder, err)
+	};
+}
+
+/// Returns lines of file with users who modified them last and times of modification. Lines of
+/// file opened in editor include changes which are not saved in database yet.
+#[get("/projects/{id}/files/{file_id}/blame")]
+pub async fn get_blame(
+	web::Path((id, file_id)): web::Path<(i32, i32)>,
+	session: Session,
+	srv: web::Data<Addr<server::EditorServer>>,
+) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user_id;
+	match get_user_id(&session) {
+		Some(user_id_in_session) => user_id = user_id_in_session,
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let opened = srv.send(server::ReadLineAuthors {
+		project_id: id,
+		file_id,
+	}).await.unwrap_or(None);
+	let result = run(move || {
+		let service = history::new(user_id, id, DocumentModel::from_env())?;
+		Ok(service.get_blame(file_id, opened))
+	}).await;
+	return match result {
+		Ok(Ok(lines)) => response_builder.json(lines),
+		Ok(Err(BlameError::FileDoesNotExist)) => response_builder
+			.status(StatusCode::NOT_FOUND)
+			.json("File does not exist"),
+		Ok(Err(BlameError::NotSupported)) => response_builder
+			.status(StatusCode::NOT_IMPLEMENTED)
+			.json("Authors of lines are not tracked in CRDT document model"),
+		Ok(Err(BlameError::DatabaseError(err))) => database_error(&mut response_builder, err),
+		Err(err) => files_service_error(&mut response_builder, err)
+	};
+}
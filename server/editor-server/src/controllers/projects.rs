@@ -1,17 +1,21 @@
 use actix_web::body::{Body};
-use actix_web::{get, post, delete, web, HttpResponse, Result, HttpRequest, Error};
+use actix_web::{get, post, put, delete, web, HttpResponse, Result, HttpRequest, Error};
 use actix_session::Session;
 use crate::session_manager::get_user_id;
-use actix_http::http::{StatusCode};
+use actix_http::http::{StatusCode, header};
 use serde::Deserialize;
 use crate::repositories::users::{get_user};
 use crate::services::projects;
-use log::{error, warn};
-use crate::services::projects::{SaveError, AccessGrantingError, AccessRevokingError};
+use log::{error, info, warn};
+use crate::services::projects::{SaveError, AccessGrantingError, AccessRevokingError, GetError, DeleteError};
+use crate::repositories::run;
+use crate::controllers::{user_does_not_exist, database_error};
+use crate::models::{User, ProjectRole};
 use actix::*;
 use actix_web_actors::ws;
-use crate::editor_session::EditorSession;
+use crate::editor_session::{EditorSession, ResumeRequest};
 use crate::server;
+use crate::protocol::{Protocol, SUBPROTOCOLS};
 use std::time::Instant;
 
 #[derive(Deserialize, Debug)]
@@ -20,6 +24,25 @@ pub struct ProjectCreationDto {
 	description: String,
 }
 
+/// Sent by client reconnecting to its previous editor session
+#[derive(Deserialize, Debug)]
+pub struct ResumeQuery {
+	resume_token: Option<String>,
+	last_event_id: Option<u64>,
+}
+
+/// Role of user project is shared with, editor by default
+#[derive(Deserialize, Debug)]
+pub struct AccessQuery {
+	role: Option<ProjectRole>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ProjectUpdateDto {
+	name: String,
+	description: String,
+}
+
 #[get("/projects/my")]
 pub async fn get_my_projects(session: Session) -> HttpResponse<Body> {
 	let mut response_builder = HttpResponse::build(StatusCode::OK);
@@ -34,9 +57,16 @@ pub async fn get_my_projects(session: Session) -> HttpResponse<Body> {
 				.json("Please log in");
 		}
 	}
-	let user = get_user(user_id).unwrap();
-	let service = projects::new(user);
-	return response_builder.json(service.get_owned_projects());
+	let user;
+	match run(move || get_user(user_id)).await {
+		Ok(Some(u)) => user = u,
+		Ok(None) => return user_does_not_exist(&mut response_builder),
+		Err(err) => return database_error(&mut response_builder, err)
+	}
+	return match run(move || projects::new(user).get_owned_projects()).await {
+		Ok(projects) => response_builder.json(projects),
+		Err(err) => database_error(&mut response_builder, err)
+	};
 }
 
 #[get("/projects/shared-for-me")]
@@ -52,17 +82,15 @@ pub async fn get_projects_shared_for_me(session: Session) -> HttpResponse<Body>
 		}
 	}
 	let user;
-	match get_user(user_id) {
-		None => {
-			warn!("Recived request from session with not existing user, possible attack");//TODO ip bla bla bla
-			return response_builder
-				.status(StatusCode::INTERNAL_SERVER_ERROR)
-				.json("You don't exist");
-		}
-		Some(u) => user = u
+	match run(move || get_user(user_id)).await {
+		Ok(None) => return user_does_not_exist(&mut response_builder),
+		Ok(Some(u)) => user = u,
+		Err(err) => return database_error(&mut response_builder, err)
 	}
-	let service = projects::new(user);
-	return response_builder.json(service.get_projects_shared_to_user());
+	return match run(move || projects::new(user).get_projects_shared_to_user()).await {
+		Ok(projects) => response_builder.json(projects),
+		Err(err) => database_error(&mut response_builder, err)
+	};
 }
 
 
@@ -72,14 +100,10 @@ pub async fn get_project(session: Session, web::Path(id): web::Path<i32>) -> Htt
 	let user;
 	match get_user_id(&session) {
 		Some(user_id_in_session) => {
-			match get_user(user_id_in_session) {
-				None => {
-					warn!("Recived request from session with not existing user, possible attack");//TODO ip bla bla bla
-					return response_builder
-						.status(StatusCode::INTERNAL_SERVER_ERROR)
-						.json("You don't exist");
-				}
-				Some(u) => user = u
+			match run(move || get_user(user_id_in_session)).await {
+				Ok(None) => return user_does_not_exist(&mut response_builder),
+				Ok(Some(u)) => user = u,
+				Err(err) => return database_error(&mut response_builder, err)
 			}
 		}
 		None => {
@@ -89,9 +113,9 @@ pub async fn get_project(session: Session, web::Path(id): web::Path<i32>) -> Htt
 				.json("Please log in");
 		}
 	}
-	let service = projects::new(user);
-	return match service.get(id) {
+	return match run(move || projects::new(user).get(id)).await {
 		Ok(project) => response_builder.json(project),
+		Err(GetError::DatabaseError(err)) => database_error(&mut response_builder, err),
 		Err(_) => response_builder
 			.status(StatusCode::NOT_FOUND)
 			.json("Project does not eist or you dont have access to it")
@@ -104,14 +128,10 @@ pub async fn create_project(project_dto: web::Json<ProjectCreationDto>, session:
 	let user;
 	match get_user_id(&session) {
 		Some(user_id) => {
-			match get_user(user_id) {
-				Some(u) => user = u,
-				None => {
-					warn!("Session with not existing user");
-					return response_builder
-						.status(StatusCode::INTERNAL_SERVER_ERROR)
-						.json("You dont exist");
-				}
+			match run(move || get_user(user_id)).await {
+				Ok(Some(u)) => user = u,
+				Ok(None) => return user_does_not_exist(&mut response_builder),
+				Err(err) => return database_error(&mut response_builder, err)
 			}
 		}
 		None => {
@@ -120,8 +140,8 @@ pub async fn create_project(project_dto: web::Json<ProjectCreationDto>, session:
 				.json("You have to be logged in to create projects");
 		}
 	}
-	let service = projects::new(user);
-	return match service.create(project_dto.name.clone(), project_dto.description.clone()) {
+	let project_dto = project_dto.into_inner();
+	return match run(move || projects::new(user).create(project_dto.name, project_dto.description)).await {
 		Ok(project) => {
 			response_builder.json(project)
 		}
@@ -130,6 +150,7 @@ pub async fn create_project(project_dto: web::Json<ProjectCreationDto>, session:
 			match error {
 				SaveError::InvalidName => response_builder.json("Invalid name"),
 				SaveError::ProjectWithSameNaeAlreadyExists => response_builder.json("You have project with same name"),
+				SaveError::DatabaseError(err) => database_error(&mut response_builder, err),
 				_ => {
 					error!("Unknon error occured while creating project");
 					response_builder.status(StatusCode::INTERNAL_SERVER_ERROR).finish()
@@ -139,9 +160,116 @@ pub async fn create_project(project_dto: web::Json<ProjectCreationDto>, session:
 	};
 }
 
+#[put("/projects/{id}")]
+pub async fn update_project(
+	web::Path(id): web::Path<i32>,
+	project_dto: web::Json<ProjectUpdateDto>,
+	session: Session,
+	srv: web::Data<Addr<server::EditorServer>>,
+) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user;
+	match get_user_id(&session) {
+		Some(user_id) => {
+			match run(move || get_user(user_id)).await {
+				Ok(Some(u)) => user = u,
+				Ok(None) => return user_does_not_exist(&mut response_builder),
+				Err(err) => return database_error(&mut response_builder, err)
+			}
+		}
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let project_dto = project_dto.into_inner();
+	let result = run(move || {
+		let service = projects::new(user);
+		let mut project = service.get(id)?;
+		project.name = project_dto.name;
+		project.description = project_dto.description;
+		Ok(service.update(project))
+	}).await;
+	return match result {
+		Ok(Ok(project)) => {
+			srv.do_send(server::ProjectUpdated { project: project.clone() });
+			response_builder.json(project)
+		}
+		Ok(Err(error)) => {
+			match error {
+				SaveError::InvalidName => response_builder
+					.status(StatusCode::BAD_REQUEST)
+					.json("Invalid name"),
+				SaveError::ProjectWithSameNaeAlreadyExists => response_builder
+					.status(StatusCode::BAD_REQUEST)
+					.json("You have project with same name"),
+				SaveError::AccessException => response_builder
+					.status(StatusCode::FORBIDDEN)
+					.json("You are not owner of this project"),
+				SaveError::ProjectDoesNotExists => response_builder
+					.status(StatusCode::NOT_FOUND)
+					.json("Project does not exist or you dont have access to it"),
+				SaveError::DatabaseError(err) => database_error(&mut response_builder, err)
+			}
+		}
+		Err(GetError::DatabaseError(err)) => database_error(&mut response_builder, err),
+		Err(_) => response_builder
+			.status(StatusCode::NOT_FOUND)
+			.json("Project does not exist or you dont have access to it")
+	};
+}
+
 #[delete("/projects/{id}")]
-pub async fn delete_project(web::Path(id): web::Path<u32>, session: Session) -> Result<HttpResponse<Body>> {
-	todo!()
+pub async fn delete_project(
+	web::Path(id): web::Path<i32>,
+	session: Session,
+	srv: web::Data<Addr<server::EditorServer>>,
+) -> HttpResponse<Body> {
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
+	let user;
+	match get_user_id(&session) {
+		Some(user_id) => {
+			match run(move || get_user(user_id)).await {
+				Ok(Some(u)) => user = u,
+				Ok(None) => return user_does_not_exist(&mut response_builder),
+				Err(err) => return database_error(&mut response_builder, err)
+			}
+		}
+		None => {
+			return response_builder
+				.status(StatusCode::UNAUTHORIZED)
+				.json("Please log in");
+		}
+	}
+	let user_id = user.id;
+	let result = run(move || {
+		let service = projects::new(user);
+		let project = service.get(id)?;
+		Ok(service.delete(project))
+	}).await;
+	return match result {
+		Ok(Ok(_)) => {
+			info!("User {} deleted project {}", user_id, id);
+			srv.do_send(server::ProjectDeleted { project_id: id });
+			response_builder.json("Project deleted")
+		}
+		Ok(Err(error)) => {
+			match error {
+				DeleteError::AccessDenied => response_builder
+					.status(StatusCode::FORBIDDEN)
+					.json("You are not owner of this project"),
+				DeleteError::DoesNotExist => response_builder
+					.status(StatusCode::NOT_FOUND)
+					.json("Project does not exist or you dont have access to it"),
+				DeleteError::DatabaseError(err) => database_error(&mut response_builder, err)
+			}
+		}
+		Err(GetError::DatabaseError(err)) => database_error(&mut response_builder, err),
+		Err(_) => response_builder
+			.status(StatusCode::NOT_FOUND)
+			.json("Project does not exist or you dont have access to it")
+	};
 }
 
 #[delete("/projects/{id}/access/{user_id}")]
@@ -150,7 +278,11 @@ pub async fn revoke_access(web::Path((id, user_id)): web::Path<(i32, i32)>, sess
 	let mut response_builder = HttpResponse::build(StatusCode::OK);
 	match get_user_id(&session) {
 		Some(user_id) => {
-			user = get_user(user_id).unwrap();
+			match run(move || get_user(user_id)).await {
+				Ok(Some(u)) => user = u,
+				Ok(None) => return user_does_not_exist(&mut response_builder),
+				Err(err) => return database_error(&mut response_builder, err)
+			}
 		}
 		None => {
 			return response_builder
@@ -159,25 +291,21 @@ pub async fn revoke_access(web::Path((id, user_id)): web::Path<(i32, i32)>, sess
 		}
 	}
 	let user_to_grant_access;
-	match get_user(user_id) {
-		Some(u) => user_to_grant_access = u,
-		None => return response_builder
+	match run(move || get_user(user_id)).await {
+		Ok(Some(u)) => user_to_grant_access = u,
+		Ok(None) => return response_builder
 			.status(StatusCode::NOT_FOUND)
-			.json("User does not exist")
-	}
-	let project;
-	let service = projects::new(user);
-	match service.get(id) {
-		Ok(p) => project = p,
-		Err(_) => {
-			return response_builder
-				.status(StatusCode::NOT_FOUND)
-				.body("Project does not exist or you dont have access to id");
-		}
+			.json("User does not exist"),
+		Err(err) => return database_error(&mut response_builder, err)
 	}
-	return match service.revoke_access(&project, &user_to_grant_access) {
-		Ok(_) => response_builder.body("ok"),
-		Err(error) => {
+	let result = run(move || {
+		let service = projects::new(user);
+		let project = service.get(id)?;
+		Ok(service.revoke_access(&project, &user_to_grant_access))
+	}).await;
+	return match result {
+		Ok(Ok(_)) => response_builder.body("ok"),
+		Ok(Err(error)) => {
 			match error {
 				AccessRevokingError::IsOwner => response_builder
 					.status(StatusCode::BAD_REQUEST)
@@ -187,19 +315,32 @@ pub async fn revoke_access(web::Path((id, user_id)): web::Path<(i32, i32)>, sess
 					.body("You are not owner of this project"),
 				AccessRevokingError::UserDoesNotExists => response_builder
 					.status(StatusCode::NOT_FOUND)
-					.body("This user does not exist")
+					.body("This user does not exist"),
+				AccessRevokingError::DatabaseError(err) => database_error(&mut response_builder, err)
 			}
 		}
+		Err(GetError::DatabaseError(err)) => database_error(&mut response_builder, err),
+		Err(_) => response_builder
+			.status(StatusCode::NOT_FOUND)
+			.body("Project does not exist or you dont have access to id")
 	};
 }
 
 #[post("/projects/{id}/access/{user_id}")]
-pub async fn grant_access(web::Path((id, user_id)): web::Path<(i32, i32)>, session: Session) -> HttpResponse<Body> {
+pub async fn grant_access(
+	web::Path((id, user_id)): web::Path<(i32, i32)>,
+	access_query: web::Query<AccessQuery>,
+	session: Session
+) -> HttpResponse<Body> {
 	let mut response_builder = HttpResponse::build(StatusCode::OK);
 	let user;
 	match get_user_id(&session) {
 		Some(user_id_from_session) => {
-			user = get_user(user_id_from_session).expect("User does not exist");
+			match run(move || get_user(user_id_from_session)).await {
+				Ok(Some(u)) => user = u,
+				Ok(None) => return user_does_not_exist(&mut response_builder),
+				Err(err) => return database_error(&mut response_builder, err)
+			}
 		}
 		None => {
 			warn!("Unauthorized user wanted to grant access to project {}", id);//TODO ip bla bla bla
@@ -208,32 +349,37 @@ pub async fn grant_access(web::Path((id, user_id)): web::Path<(i32, i32)>, sessi
 				.body("Please log in");
 		}
 	}
-	let service = projects::new(user);
-	let project;
-	match service.get(id) {
-		Ok(p) => project = p,
-		Err(_) => {
-			return response_builder
-				.status(StatusCode::NOT_FOUND)
-				.body("Project does not exist or you dont have access to it");
-		}
-	}
 	let user_to_grant;
-	match get_user(user_id) {
-		Some(u) => user_to_grant = u,
-		None => return response_builder
+	match run(move || get_user(user_id)).await {
+		Ok(Some(u)) => user_to_grant = u,
+		Ok(None) => return response_builder
 			.status(StatusCode::NOT_FOUND)
-			.body("User does not exists")
+			.body("User does not exists"),
+		Err(err) => return database_error(&mut response_builder, err)
 	}
-	return match service.grant_access(&project, &user_to_grant) {
-		Ok(_) => response_builder.body("Ok"),
-		Err(error) => {
+	let role = access_query.role.unwrap_or(ProjectRole::Editor);
+	let result = run(move || {
+		let service = projects::new(user);
+		let project = service.get(id)?;
+		Ok(service.grant_access(&project, &user_to_grant, role))
+	}).await;
+	return match result {
+		Ok(Ok(_)) => response_builder.body("Ok"),
+		Ok(Err(error)) => {
 			match error {
 				AccessGrantingError::NotOwner => response_builder
 					.status(StatusCode::NOT_FOUND)
-					.body("Project does not exist or you dont have access to it")
+					.body("Project does not exist or you dont have access to it"),
+				AccessGrantingError::IllegalRole => response_builder
+					.status(StatusCode::BAD_REQUEST)
+					.body("Project can't be shared with owner role"),
+				AccessGrantingError::DatabaseError(err) => database_error(&mut response_builder, err)
 			}
 		}
+		Err(GetError::DatabaseError(err)) => database_error(&mut response_builder, err),
+		Err(_) => response_builder
+			.status(StatusCode::NOT_FOUND)
+			.body("Project does not exist or you dont have access to it")
 	};
 }
 
@@ -243,32 +389,50 @@ pub async fn begin_editor_session(
 	stream: web::Payload,
 	srv: web::Data<Addr<server::EditorServer>>,
 	project_id: web::Path<i32>,
+	resume_query: web::Query<ResumeQuery>,
 	session: Session
 ) -> Result<HttpResponse, Error> {
-	let user;
+	let user: User;
+	let mut response_builder = HttpResponse::build(StatusCode::OK);
 	match get_user_id(&session){
-		Some(id) => user = get_user(id).expect("Non existing user tryied to make session"),
+		Some(id) => match run(move || get_user(id)).await {
+			Ok(Some(u)) => user = u,
+			Ok(None) => return Ok(user_does_not_exist(&mut response_builder)),
+			Err(err) => return Ok(database_error(&mut response_builder, err))
+		},
 		None => {
 			warn!("Non logged in user tried to create edition session");
 			return Ok(HttpResponse::build(StatusCode::UNAUTHORIZED).body("Log in first"));
 		}
 	}
-	let  service= projects::new(user.clone());
-	match service.get(project_id.0){
+	let service_user = user.clone();
+	let requested_project_id = project_id.0;
+	match run(move || projects::new(service_user).get(requested_project_id)).await {
+		Err(GetError::DatabaseError(err)) => return Ok(database_error(&mut response_builder, err)),
 		Err(_) => {
 			warn!("User {} tried to start editor session on project {}, which does not exists or is unavabile to user", user.id, project_id.0);
 			return Ok(HttpResponse::build(StatusCode::NOT_FOUND).body("Project does not exist or you dont have access to it"));
 		}
 		Ok(_) => {}
 	}
-	ws::start(
+	let ResumeQuery { resume_token, last_event_id } = resume_query.into_inner();
+	let requested_protocols = req.headers()
+		.get(header::SEC_WEBSOCKET_PROTOCOL)
+		.and_then(|protocols| protocols.to_str().ok());
+	ws::start_with_protocols(
 		EditorSession {
 			id: 0,
 			user,
 			hb: Instant::now(),
 			project_id: project_id.0,
 			addr: srv.get_ref().clone(),
+			protocol: Protocol::negotiate(requested_protocols),
+			resume: resume_token.map(|resume_token| ResumeRequest {
+				resume_token,
+				last_event_id,
+			}),
 		},
+		&SUBPROTOCOLS,
 		&req,
 		stream,
 	)
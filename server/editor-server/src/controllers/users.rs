@@ -1,27 +1,33 @@
 use actix_web::body::Body;
 use actix_web::{post, web, HttpResponse, Result};
-use crate::repositories::users::{create_user, login as try_login};
+use crate::repositories::users::{create_user, login as try_login, LoginError, RegistrationError};
+use crate::repositories::run;
 use actix_web::http::StatusCode;
 use serde::Deserialize;
 use actix_session::Session;
 use crate::session_manager::{get_user_id, set_user_id};
-use log::{ info};
+use log::{ info, error};
 
 
 #[post("/login")]
 pub async fn login(user_dto: web::Json<UserAuthorizationDto>, session: Session) -> Result<HttpResponse<Body>> {
 	let mut response_builder = HttpResponse::build(StatusCode::OK);
-	return match try_login(&user_dto.username, &user_dto.password) {
+	let user_dto = user_dto.into_inner();
+	return match run(move || try_login(&user_dto.username, &user_dto.password)).await {
 		Ok(user) => {
 			info!("User logged in: {}", user.name);
 			set_user_id(&session, user.id);
 			session.renew();
 			Ok(response_builder.body("hello"))
 		}
-		Err(_) => {
+		Err(LoginError::InvalidCredentials) => {
 			info!("User tried to log in with incorrect password or login");//TODO ip bla bla bla
 			Ok(response_builder.status(StatusCode::UNAUTHORIZED).body("Invalid login"))
 		}
+		Err(LoginError::DatabaseError(err)) => {
+			error!("Failed to log in user: {}", err);
+			Ok(response_builder.status(StatusCode::INTERNAL_SERVER_ERROR).body("Unknown server error"))
+		}
 	};
 }
 
@@ -43,7 +49,8 @@ pub async fn register(user_dto: web::Json<UserAuthorizationDto>) -> Result<HttpR
 	println!("{:#?}", user_dto);
 	let mut response_builder = HttpResponse::build(StatusCode::OK);
 	response_builder.header("Access-Control-Allow-Origin","*");
-	return match create_user(&user_dto.username, &user_dto.password) {
+	let user_dto = user_dto.into_inner();
+	return match run(move || create_user(&user_dto.username, &user_dto.password)).await {
 		Ok(user) => {
 			match serde_json::to_string(&user) {
 				Ok(json) => Ok(response_builder.body(json)),
@@ -53,8 +60,12 @@ pub async fn register(user_dto: web::Json<UserAuthorizationDto>) -> Result<HttpR
 				}
 			}
 		}
-		Err(error_message) => {
-			Ok(response_builder.status(StatusCode::BAD_REQUEST).body(error_message))
+		Err(RegistrationError::UserAlreadyExists) => {
+			Ok(response_builder.status(StatusCode::BAD_REQUEST).body("User with this username already exists"))
+		}
+		Err(RegistrationError::DatabaseError(err)) => {
+			error!("Failed to register user: {}", err);
+			Ok(response_builder.status(StatusCode::INTERNAL_SERVER_ERROR).body("Unknown database error"))
 		}
 	};
 }
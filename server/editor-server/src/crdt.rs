@@ -0,0 +1,437 @@
+/// CRDT representation of file content.
+///
+/// File content is kept as replicated growable array (RGA) of characters. Every character has
+/// unique id made of lamport timestamp and id of site (session) that inserted it, so concurrent
+/// insertions and deletions sent by different sites are merged in the same way no matter in
+/// which order they arrive. Deleted characters are kept as tombstones, because later operations
+/// can still refer to them.
+///
+/// Elements are kept in a vector in document order, with indexes of their ids and their positions
+/// in visible text cached for the part of the vector before the last modified element. Finding an
+/// element or its position takes O(1) when the part of document after it was not changed since
+/// the last lookup, and otherwise O(k), where k is the number of elements after the earliest
+/// change. Inserting an element into the vector takes O(k) as well, so integrating an operation
+/// costs O(k) instead of O(n) for the whole document, which makes typing near the end of file
+/// cheap. Creating operations for a change still takes O(n).
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use crate::editor_session::Position;
+
+/// Site id used for characters imported from text. Session ids are positive and changes made by
+/// server use site 0, so imported characters never get id of character inserted by any site.
+const IMPORT_SITE_ID: i32 = -1;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ElementId {
+	pub counter: u64,
+	pub site: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Operation {
+	/// Inserts character right after element `after` or at the beginning of file if it is None
+	Insert {
+		id: ElementId,
+		after: Option<ElementId>,
+		value: char,
+	},
+	Delete {
+		id: ElementId
+	},
+}
+
+/// Operation refers to character that was not yet integrated in this document.
+#[derive(Debug)]
+pub struct UnknownElement(pub ElementId);
+
+/// Change of visible text caused by integrated operations, expressed in the same way as
+/// changes sent by editors: text between `start` and `end` was replaced by `text`
+#[derive(Debug, Clone)]
+pub struct TextChange {
+	pub start: Position,
+	pub end: Position,
+	pub text: String,
+}
+
+impl TextChange {
+	fn is_insertion(&self) -> bool {
+		self.start == self.end
+	}
+
+	/// Position right after inserted text
+	fn inserted_text_end(&self) -> Position {
+		let mut end = self.start;
+		for c in self.text.chars() {
+			end = next_position(end, c);
+		}
+		end
+	}
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Element {
+	id: ElementId,
+	value: char,
+	deleted: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CrdtDocument {
+	elements: Vec<Element>,
+	/// Highest lamport timestamp seen in this document
+	clock: u64,
+	/// Indexes of elements, up to date for all elements before `indexed_until`. Entries of other
+	/// elements can be outdated.
+	#[serde(skip)]
+	indexes: HashMap<ElementId, usize>,
+	#[serde(skip)]
+	indexed_until: usize,
+	/// Positions in visible text of the first elements, the same as indexes of elements
+	#[serde(skip)]
+	positions: Vec<Position>,
+}
+
+impl CrdtDocument {
+	/// Creates document containing given text with ids of characters following given clock. Pass
+	/// clock of previous document of file, so its ids are not reused for other characters. Ids
+	/// depend only on text and clock, so every replica importing the same text will have the same document.
+	pub fn from_text(text: &str, clock: u64) -> CrdtDocument {
+		let elements: Vec<Element> = text.chars()
+			.enumerate()
+			.map(|(index, value)| Element {
+				id: ElementId { counter: clock + index as u64 + 1, site: IMPORT_SITE_ID },
+				value,
+				deleted: false,
+			})
+			.collect();
+		CrdtDocument {
+			clock: clock + elements.len() as u64,
+			elements,
+			indexes: HashMap::new(),
+			indexed_until: 0,
+			positions: Vec::new(),
+		}
+	}
+
+	/// Highest lamport timestamp seen in this document
+	pub fn clock(&self) -> u64 {
+		self.clock
+	}
+
+	/// Returns visible text of document
+	pub fn text(&self) -> String {
+		self.elements.iter()
+			.filter(|element| !element.deleted)
+			.map(|element| element.value)
+			.collect()
+	}
+
+	/// Creates operations that replace text between `start` and `end` with `lines`. Operations
+	/// are not applied, use `integrate_all` for that.
+	pub fn operations_for_change(&self, site: i32, start: Position, end: Position, lines: &[String]) -> Vec<Operation> {
+		let visible: Vec<&Element> = self.elements.iter()
+			.filter(|element| !element.deleted)
+			.collect();
+		let start_index = visible_index(&visible, start);
+		let end_index = visible_index(&visible, end).max(start_index);
+
+		let mut operations: Vec<Operation> = visible[start_index..end_index].iter()
+			.map(|element| Operation::Delete { id: element.id })
+			.collect();
+
+		let mut after = if start_index == 0 {
+			None
+		} else {
+			Some(visible[start_index - 1].id)
+		};
+		for (index, value) in lines.join("\n").chars().enumerate() {
+			let id = ElementId { counter: self.clock + index as u64 + 1, site };
+			operations.push(Operation::Insert { id, after, value });
+			after = Some(id);
+		}
+		operations
+	}
+
+	/// Integrates all operations and returns changes of visible text they caused. Consecutive
+	/// insertions are merged into one change. If any operation refers to unknown element, none
+	/// of operations is integrated.
+	pub fn integrate_all(&mut self, operations: &[Operation]) -> Result<Vec<TextChange>, UnknownElement> {
+		self.check_references(operations)?;
+		let mut changes: Vec<TextChange> = Vec::new();
+		for operation in operations {
+			let change = match self.integrate(operation)? {
+				Some(change) => change,
+				None => continue
+			};
+			if let Some(last) = changes.last_mut() {
+				if last.is_insertion() && change.is_insertion() && last.inserted_text_end() == change.start {
+					last.text.push_str(&change.text);
+					continue;
+				}
+			}
+			changes.push(change);
+		}
+		Ok(changes)
+	}
+
+	/// Integrates single operation. Integrating operation that was already integrated does nothing.
+	///
+	/// # Returns
+	///
+	/// Change of visible text or None if text did not change
+	pub fn integrate(&mut self, operation: &Operation) -> Result<Option<TextChange>, UnknownElement> {
+		match operation {
+			Operation::Insert { id, after, value } => self.integrate_insertion(*id, *after, *value),
+			Operation::Delete { id } => self.integrate_deletion(*id),
+		}
+	}
+
+	fn integrate_insertion(&mut self, id: ElementId, after: Option<ElementId>, value: char) -> Result<Option<TextChange>, UnknownElement> {
+		if self.index_of(id).is_some() {
+			return Ok(None);
+		}
+		let mut index = match after {
+			Some(after_id) => match self.index_of(after_id) {
+				Some(after_index) => after_index + 1,
+				None => return Err(UnknownElement(after_id))
+			},
+			None => 0
+		};
+		// Elements inserted concurrently after the same element are ordered by descending ids
+		while index < self.elements.len() && self.elements[index].id > id {
+			index += 1;
+		}
+		self.elements.insert(index, Element { id, value, deleted: false });
+		self.clock = self.clock.max(id.counter);
+		// Elements after inserted one moved, so their indexes and positions are outdated
+		self.indexed_until = self.indexed_until.min(index);
+		self.positions.truncate(index);
+
+		let position = self.position_of(index);
+		Ok(Some(TextChange {
+			start: position,
+			end: position,
+			text: value.to_string(),
+		}))
+	}
+
+	fn integrate_deletion(&mut self, id: ElementId) -> Result<Option<TextChange>, UnknownElement> {
+		let index = match self.index_of(id) {
+			Some(index) => index,
+			None => return Err(UnknownElement(id))
+		};
+		if self.elements[index].deleted {
+			return Ok(None);
+		}
+		let start = self.position_of(index);
+		let end = next_position(start, self.elements[index].value);
+		self.elements[index].deleted = true;
+		self.positions.truncate(index + 1);
+		Ok(Some(TextChange {
+			start,
+			end,
+			text: String::new(),
+		}))
+	}
+
+	/// Checks if every element referred by operations is in document or is inserted by earlier operation
+	fn check_references(&mut self, operations: &[Operation]) -> Result<(), UnknownElement> {
+		let mut inserted: HashSet<ElementId> = HashSet::new();
+		for operation in operations {
+			let referenced = match operation {
+				Operation::Insert { id, after, .. } => {
+					inserted.insert(*id);
+					*after
+				}
+				Operation::Delete { id } => Some(*id)
+			};
+			if let Some(id) = referenced {
+				if !inserted.contains(&id) && self.index_of(id).is_none() {
+					return Err(UnknownElement(id));
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Index of element with given id. Outdated indexes are updated until the element is found.
+	fn index_of(&mut self, id: ElementId) -> Option<usize> {
+		if let Some(&index) = self.indexes.get(&id) {
+			if self.elements.get(index).map(|element| element.id) == Some(id) {
+				return Some(index);
+			}
+		}
+		while self.indexed_until < self.elements.len() {
+			let index = self.indexed_until;
+			let element_id = self.elements[index].id;
+			self.indexes.insert(element_id, index);
+			self.indexed_until += 1;
+			if element_id == id {
+				return Some(index);
+			}
+		}
+		None
+	}
+
+	/// Position in visible text of element with given index. Outdated positions are computed
+	/// again up to the element.
+	fn position_of(&mut self, index: usize) -> Position {
+		while self.positions.len() <= index {
+			let position = match self.positions.len().checked_sub(1) {
+				Some(previous) => {
+					let element = &self.elements[previous];
+					if element.deleted {
+						self.positions[previous]
+					} else {
+						next_position(self.positions[previous], element.value)
+					}
+				}
+				None => Position { row: 0, column: 0 }
+			};
+			self.positions.push(position);
+		}
+		self.positions[index]
+	}
+}
+
+/// Converts position in text to index of visible character. Positions after end of line or
+/// after end of text are moved to the end of line or text.
+fn visible_index(visible: &[&Element], position: Position) -> usize {
+	let mut current = Position { row: 0, column: 0 };
+	for (index, element) in visible.iter().enumerate() {
+		if current >= position || (current.row == position.row && element.value == '\n') {
+			return index;
+		}
+		current = next_position(current, element.value);
+	}
+	visible.len()
+}
+
+fn next_position(position: Position, value: char) -> Position {
+	if value == '\n' {
+		Position { row: position.row + 1, column: 0 }
+	} else {
+		Position { row: position.row, column: position.column + 1 }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn id(counter: u64, site: i32) -> ElementId {
+		ElementId { counter, site }
+	}
+
+	fn insert(id: ElementId, after: Option<ElementId>, value: char) -> Operation {
+		Operation::Insert { id, after, value }
+	}
+
+	fn position(row: u32, column: u32) -> Position {
+		Position { row, column }
+	}
+
+	#[test]
+	fn imported_text_continues_from_given_clock() {
+		let mut document = CrdtDocument::from_text("ab", 5);
+		assert_eq!(document.index_of(id(6, IMPORT_SITE_ID)), Some(0));
+		assert_eq!(document.index_of(id(7, IMPORT_SITE_ID)), Some(1));
+		assert_eq!(document.index_of(id(1, IMPORT_SITE_ID)), None);
+		assert_eq!(document.clock(), 7);
+	}
+
+	#[test]
+	fn insertion_is_placed_after_referred_element() {
+		let mut document = CrdtDocument::from_text("ac", 0);
+		let change = document.integrate(&insert(id(3, 1), Some(id(1, IMPORT_SITE_ID)), 'b')).unwrap().unwrap();
+		assert_eq!(document.text(), "abc");
+		assert_eq!(document.index_of(id(3, 1)), Some(1));
+		assert_eq!(change.start, position(0, 1));
+		assert_eq!(change.text, "b");
+		assert_eq!(document.clock(), 3);
+	}
+
+	#[test]
+	fn concurrent_insertions_are_ordered_the_same_in_every_replica() {
+		let first = insert(id(2, 1), Some(id(1, IMPORT_SITE_ID)), 'x');
+		let second = insert(id(2, 2), Some(id(1, IMPORT_SITE_ID)), 'y');
+		let mut document = CrdtDocument::from_text("a", 0);
+		document.integrate(&first).unwrap();
+		document.integrate(&second).unwrap();
+		let mut other_document = CrdtDocument::from_text("a", 0);
+		other_document.integrate(&second).unwrap();
+		other_document.integrate(&first).unwrap();
+		assert_eq!(document.text(), "ayx");
+		assert_eq!(other_document.text(), "ayx");
+	}
+
+	#[test]
+	fn integrating_insertion_again_does_nothing() {
+		let mut document = CrdtDocument::from_text("a", 0);
+		let operation = insert(id(2, 1), None, 'b');
+		assert!(document.integrate(&operation).unwrap().is_some());
+		assert!(document.integrate(&operation).unwrap().is_none());
+		assert_eq!(document.text(), "ba");
+	}
+
+	#[test]
+	fn deleted_element_stays_as_tombstone() {
+		let mut document = CrdtDocument::from_text("ab\nc", 0);
+		let change = document.integrate(&Operation::Delete { id: id(3, IMPORT_SITE_ID) }).unwrap().unwrap();
+		assert_eq!(document.text(), "abc");
+		assert_eq!((change.start, change.end), (position(0, 2), position(1, 0)));
+		assert_eq!(document.index_of(id(3, IMPORT_SITE_ID)), Some(2));
+		assert!(document.integrate(&Operation::Delete { id: id(3, IMPORT_SITE_ID) }).unwrap().is_none());
+		document.integrate(&insert(id(5, 1), Some(id(3, IMPORT_SITE_ID)), 'd')).unwrap();
+		assert_eq!(document.text(), "abdc");
+	}
+
+	#[test]
+	fn operations_referring_to_unknown_element_are_rejected() {
+		let mut document = CrdtDocument::from_text("a", 0);
+		assert!(document.integrate(&insert(id(3, 1), Some(id(2, 2)), 'b')).is_err());
+		assert!(document.integrate(&Operation::Delete { id: id(2, 2) }).is_err());
+		let operations = [insert(id(3, 1), None, 'b'), Operation::Delete { id: id(2, 2) }];
+		assert!(document.integrate_all(&operations).is_err());
+		assert_eq!(document.text(), "a");
+		assert_eq!(document.index_of(id(3, 1)), None);
+	}
+
+	#[test]
+	fn operations_for_change_replace_text() {
+		let mut document = CrdtDocument::from_text("abc\ndef", 0);
+		let lines = vec!["x".to_owned(), "y".to_owned()];
+		let operations = document.operations_for_change(1, position(0, 1), position(1, 1), &lines);
+		let changes = document.integrate_all(&operations).unwrap();
+		assert_eq!(document.text(), "ax\nyef");
+		assert_eq!(changes.last().unwrap().text, "x\ny");
+	}
+
+	#[test]
+	fn cached_indexes_and_positions_follow_changes() {
+		let mut document = CrdtDocument::from_text("ab\ncd", 0);
+		assert_eq!(document.index_of(id(5, IMPORT_SITE_ID)), Some(4));
+		document.integrate(&insert(id(6, 1), Some(id(1, IMPORT_SITE_ID)), 'x')).unwrap();
+		document.integrate(&Operation::Delete { id: id(3, IMPORT_SITE_ID) }).unwrap();
+		let change = document.integrate(&insert(id(7, 1), Some(id(5, IMPORT_SITE_ID)), 'y')).unwrap().unwrap();
+		assert_eq!(document.text(), "axbcdy");
+		assert_eq!(change.start, position(0, 5));
+		for (index, element) in document.elements.clone().iter().enumerate() {
+			assert_eq!(document.index_of(element.id), Some(index));
+		}
+		let change = document.integrate(&Operation::Delete { id: id(4, IMPORT_SITE_ID) }).unwrap().unwrap();
+		assert_eq!(change.start, position(0, 3));
+	}
+
+	#[test]
+	fn deserialized_document_rebuilds_indexes() {
+		let mut document = CrdtDocument::from_text("ab", 0);
+		document.integrate(&insert(id(3, 1), Some(id(2, IMPORT_SITE_ID)), 'c')).unwrap();
+		let mut copy: CrdtDocument = serde_json::from_str(&serde_json::to_string(&document).unwrap()).unwrap();
+		assert_eq!(copy.index_of(id(3, 1)), Some(2));
+		let change = copy.integrate(&insert(id(4, 2), Some(id(3, 1)), 'd')).unwrap().unwrap();
+		assert_eq!(copy.text(), "abcd");
+		assert_eq!(change.start, position(0, 3));
+	}
+}
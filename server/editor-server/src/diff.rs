@@ -0,0 +1,151 @@
+use similar::{ChangeTag, TextDiff};
+use crate::models::{FileDiff, DiffHunk, DiffLine, DiffLineKind};
+
+/// Number of unchanged lines shown around changed lines
+const CONTEXT_LINES: usize = 3;
+
+/// Compares two versions of file line by line
+///
+/// # Arguments
+///
+/// * `from` - revision and content of the older version, None if file was not in it
+/// * `to` - revision and content of the newer version, None if file was not in it
+pub fn diff_file(file_id: i32, name: &str, from: Option<(i32, &str)>, to: Option<(i32, &str)>) -> FileDiff {
+	// Names of files are padded with spaces in database
+	let name = name.trim_end();
+	let old_content = from.map(|(_, content)| content).unwrap_or("");
+	let new_content = to.map(|(_, content)| content).unwrap_or("");
+	let diff = TextDiff::from_lines(old_content, new_content);
+	let old_header = match from {
+		Some((revision, _)) => format!("a/{}@{}", name, revision),
+		None => "/dev/null".to_owned()
+	};
+	let new_header = match to {
+		Some((revision, _)) => format!("b/{}@{}", name, revision),
+		None => "/dev/null".to_owned()
+	};
+	let unified = diff.unified_diff()
+		.context_radius(CONTEXT_LINES)
+		.header(&old_header, &new_header)
+		.to_string();
+	let hunks = diff.grouped_ops(CONTEXT_LINES).iter()
+		.filter(|group| !group.is_empty())
+		.map(|group| {
+			let old_range = group[0].old_range().start..group[group.len() - 1].old_range().end;
+			let new_range = group[0].new_range().start..group[group.len() - 1].new_range().end;
+			let lines = group.iter()
+				.flat_map(|op| diff.iter_changes(op))
+				.map(|change| DiffLine {
+					kind: match change.tag() {
+						ChangeTag::Equal => DiffLineKind::Context,
+						ChangeTag::Insert => DiffLineKind::Added,
+						ChangeTag::Delete => DiffLineKind::Removed,
+					},
+					content: change.value().trim_end_matches(&['\r', '\n'][..]).to_owned(),
+				})
+				.collect();
+			DiffHunk {
+				old_start: hunk_start(old_range.start, old_range.len()),
+				old_lines: old_range.len(),
+				new_start: hunk_start(new_range.start, new_range.len()),
+				new_lines: new_range.len(),
+				lines,
+			}
+		})
+		.collect();
+	FileDiff {
+		file_id,
+		name: name.to_owned(),
+		from_revision: from.map(|(revision, _)| revision),
+		to_revision: to.map(|(revision, _)| revision),
+		unified,
+		hunks,
+	}
+}
+
+/// Number of the first line of hunk, like in unified diff empty hunk starts at the line before it
+fn hunk_start(start: usize, length: usize) -> usize {
+	if length == 0 {
+		start
+	} else {
+		start + 1
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn kinds(hunk: &DiffHunk) -> Vec<DiffLineKind> {
+		hunk.lines.iter().map(|line| line.kind).collect()
+	}
+
+	fn contents(hunk: &DiffHunk) -> Vec<&str> {
+		hunk.lines.iter().map(|line| line.content.as_str()).collect()
+	}
+
+	#[test]
+	fn modified_file_has_hunk_with_context() {
+		let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+		let new = "1\n2\n3\n4\n5\nsix\n7\n8\n9\n10\n";
+		let diff = diff_file(1, "main.rs   ", Some((3, old)), Some((5, new)));
+		assert_eq!(diff.name, "main.rs");
+		assert_eq!((diff.from_revision, diff.to_revision), (Some(3), Some(5)));
+		assert!(diff.unified.starts_with("--- a/main.rs@3\n+++ b/main.rs@5\n@@ -3,7 +3,7 @@\n"));
+		assert_eq!(diff.hunks.len(), 1);
+		let hunk = &diff.hunks[0];
+		assert_eq!((hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines), (3, 7, 3, 7));
+		assert_eq!(contents(hunk), vec!["3", "4", "5", "6", "six", "7", "8", "9"]);
+		assert_eq!(kinds(hunk)[3..5], [DiffLineKind::Removed, DiffLineKind::Added]);
+	}
+
+	#[test]
+	fn added_file_is_compared_with_dev_null() {
+		let diff = diff_file(1, "new.rs", None, Some((1, "a\nb\n")));
+		assert!(diff.unified.starts_with("--- /dev/null\n+++ b/new.rs@1\n@@ -0,0 +1,2 @@\n"));
+		assert_eq!(diff.from_revision, None);
+		let hunk = &diff.hunks[0];
+		assert_eq!((hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines), (0, 0, 1, 2));
+		assert_eq!(kinds(hunk), vec![DiffLineKind::Added, DiffLineKind::Added]);
+	}
+
+	#[test]
+	fn removed_file_is_compared_with_dev_null() {
+		let diff = diff_file(1, "old.rs", Some((4, "a\nb\n")), None);
+		assert!(diff.unified.starts_with("--- a/old.rs@4\n+++ /dev/null\n@@ -1,2 +0,0 @@\n"));
+		assert_eq!(diff.to_revision, None);
+		let hunk = &diff.hunks[0];
+		assert_eq!((hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines), (1, 2, 0, 0));
+		assert_eq!(kinds(hunk), vec![DiffLineKind::Removed, DiffLineKind::Removed]);
+	}
+
+	#[test]
+	fn insertion_into_empty_file_starts_at_line_before_it() {
+		let diff = diff_file(1, "main.rs", Some((1, "")), Some((2, "a\n")));
+		assert!(diff.unified.contains("@@ -0,0 +1 @@\n"));
+		let hunk = &diff.hunks[0];
+		assert_eq!((hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines), (0, 0, 1, 1));
+		assert_eq!(hunk_start(5, 0), 5);
+		assert_eq!(hunk_start(5, 2), 6);
+	}
+
+	#[test]
+	fn insertion_between_lines_keeps_numbers_of_old_lines() {
+		let diff = diff_file(1, "main.rs", Some((1, "a\nb\n")), Some((2, "a\nx\nb\n")));
+		let hunk = &diff.hunks[0];
+		assert_eq!((hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines), (1, 2, 1, 3));
+		assert_eq!(kinds(hunk), vec![DiffLineKind::Context, DiffLineKind::Added, DiffLineKind::Context]);
+	}
+
+	#[test]
+	fn line_endings_are_stripped_from_lines() {
+		let diff = diff_file(1, "main.rs", Some((1, "a\r\nb\r\n")), Some((2, "a\r\nc\r\n")));
+		assert_eq!(contents(&diff.hunks[0]), vec!["a", "b", "c"]);
+	}
+
+	#[test]
+	fn same_content_has_no_hunks() {
+		let diff = diff_file(1, "main.rs", Some((1, "a\n")), Some((2, "a\n")));
+		assert!(diff.hunks.is_empty());
+	}
+}
@@ -0,0 +1,870 @@
+/// Actor holding content of one edited file in memory.
+///
+/// Changes are applied on in-memory copy of file and saved to database in batches: periodically,
+/// after too many unsaved changes and when document is closed (when last session editing file
+/// disconnects). Saves of one file are written one after another, also when they are started by
+/// different documents. Document closes document of the same file opened before, for example in
+/// room which is closing, and waits for its saves before it loads content. Every applied change
+/// is also saved in history of file as its next revision, with snapshot of content every
+/// SNAPSHOT_INTERVAL revisions. Documents using lines model also
+/// remember who modified each line last.
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicI32, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use actix::prelude::*;
+use actix::WeakAddr;
+use futures::channel::oneshot;
+use futures::future::{join_all, FutureExt, Shared};
+use once_cell::sync::Lazy;
+use ropey::Rope;
+use sha2::{Digest, Sha256};
+use similar::{capture_diff_slices, Algorithm, DiffTag};
+use log::{info, error};
+use crate::editor_session::{FileChange, Position};
+use crate::crdt::{CrdtDocument, Operation, TextChange, UnknownElement};
+use crate::server::DocumentModel;
+use crate::project_room::SERVER_SESSION_ID;
+use crate::repositories::{in_transaction, DatabaseError, Transaction};
+use crate::repositories::file_history::HistoryChange;
+use crate::repositories::file_content_repository::LineAuthor;
+
+/// How often unsaved changes are saved to database
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+/// Number of unsaved changes after which document is saved without waiting for FLUSH_INTERVAL
+const MAX_UNSAVED_CHANGES: usize = 500;
+/// Snapshot of content is saved in history of file when number of its revision is divisible by this
+const SNAPSHOT_INTERVAL: i32 = 100;
+
+/// Save of file, resolves when it finished whether it succeeded or not
+type Saving = Shared<Pin<Box<dyn Future<Output = ()> + Send>>>;
+
+/// Saves content of document in transaction
+type SaveContent = Box<dyn FnOnce(&mut Transaction) -> Result<(), DatabaseError> + Send>;
+
+/// Resolves when content written to file is saved in database
+pub type ContentSaved = Pin<Box<dyn Future<Output = Result<(), DatabaseError>> + Send>>;
+
+/// The last started save of every file which is being saved, with its number
+static SAVES: Lazy<Mutex<HashMap<i32, (u64, Saving)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static NEXT_SAVE: AtomicU64 = AtomicU64::new(0);
+/// The last opened document of every file
+static OPENED: Lazy<Mutex<HashMap<i32, WeakAddr<FileDocument>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+enum DocumentContent {
+	Text(Rope),
+	Crdt(CrdtDocument),
+}
+
+pub struct FileDocument {
+	file_id: i32,
+	model: DocumentModel,
+	content: DocumentContent,
+	unsaved_changes: usize,
+	/// Revision of file in its history after the last applied change
+	revision: i32,
+	/// The last revision of file saved in database, updated by saves
+	saved_revision: Arc<AtomicI32>,
+	/// Changes not saved in history yet
+	unsaved_history: Vec<HistoryChange>,
+	/// Authors of lines of content, empty if document uses CRDT model
+	line_authors: Vec<LineAuthor>,
+	/// Document of file opened before this one, closed before content is loaded
+	previous: Option<Addr<FileDocument>>,
+}
+
+/// Applies change made by editor.
+///
+/// # Returns
+///
+/// CRDT operations that change was converted to, empty if document does not use CRDT model
+#[derive(Message)]
+#[rtype(result = "Vec<Operation>")]
+pub struct ApplyChange {
+	pub change: FileChange,
+	/// User who made the change, saved in history of file
+	pub author_id: Option<i32>,
+}
+
+/// Replaces whole content of file with one change made by author
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ReplaceContent {
+	pub content: String,
+	pub author_id: Option<i32>,
+}
+
+/// Integrates CRDT operations sent by client. Fails if document does not use CRDT model.
+#[derive(Message)]
+#[rtype(result = "Result<Vec<TextChange>, UnknownElement>")]
+pub struct IntegrateOperations {
+	pub operations: Vec<Operation>,
+	pub author_id: Option<i32>,
+}
+
+/// Saves unsaved changes and returns revision of file in its history after they are saved, so it
+/// can be referred to. Returns None if saving failed.
+#[derive(Message)]
+#[rtype(result = "Option<i32>")]
+pub struct Flush;
+
+#[derive(Message)]
+#[rtype(result = "String")]
+pub struct GetContent;
+
+/// Returns CRDT state of content, None if document does not use CRDT model
+#[derive(Message)]
+#[rtype(result = "Option<CrdtDocument>")]
+pub struct GetCrdtState;
+
+/// Returns content and authors of its lines, authors are empty if document uses CRDT model
+#[derive(Message)]
+#[rtype(result = "(String, Vec<LineAuthor>)")]
+pub struct GetLineAuthors;
+
+/// Returns SHA-256 checksum of content encoded as UTF-8, as lowercase hex string
+#[derive(Message)]
+#[rtype(result = "String")]
+pub struct GetChecksum;
+
+/// Saves unsaved changes and stops document
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Close;
+
+/// Stops document without saving changes, used when file was deleted
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Discard;
+
+/// Sent by document to itself when saving changes failed, so they are saved again later
+#[derive(Message)]
+#[rtype(result = "()")]
+struct SaveFailed {
+	changes: usize,
+	history: Vec<HistoryChange>,
+}
+
+impl FileDocument {
+	/// Creates empty document, content of file is loaded from database when document is started.
+	/// Document does not handle messages until content is loaded and stops if loading fails.
+	fn new(file_id: i32, model: DocumentModel, previous: Option<Addr<FileDocument>>) -> FileDocument {
+		FileDocument {
+			file_id,
+			model,
+			content: DocumentContent::Text(Rope::new()),
+			unsaved_changes: 0,
+			revision: 0,
+			saved_revision: Arc::new(AtomicI32::new(0)),
+			unsaved_history: Vec::new(),
+			line_authors: Vec::new(),
+			previous,
+		}
+	}
+
+	/// Applies change and remembers it in history of file
+	///
+	/// # Returns
+	///
+	/// CRDT operations that change was converted to, empty if document does not use CRDT model
+	fn apply(&mut self, change: FileChange, author_id: Option<i32>, ctx: &mut Context<Self>) -> Vec<Operation> {
+		let operations = match &mut self.content {
+			DocumentContent::Text(rope) => {
+				let author = LineAuthor {
+					author_id,
+					modified_at: now_millis(),
+				};
+				replace_in_rope_with_authors(rope, &mut self.line_authors, change.start, change.end, &change.lines, author);
+				Vec::new()
+			}
+			DocumentContent::Crdt(document) => {
+				let operations = document.operations_for_change(change.session_id, change.start, change.end, &change.lines);
+				if let Err(UnknownElement(id)) = document.integrate_all(&operations) {
+					error!("Operations created for file {} refer to unknown element {:?}", self.file_id, id);
+					return Vec::new();
+				}
+				operations
+			}
+		};
+		self.change_applied(author_id, change.start, change.end, change.lines, ctx);
+		operations
+	}
+
+	/// Saves unsaved changes in background after previous saves of file. Saving does not depend on
+	/// document actor, so changes are saved also when document is stopping.
+	fn flush(&mut self, addr: Option<Addr<FileDocument>>) {
+		if self.unsaved_changes == 0 {
+			return;
+		}
+		let file_id = self.file_id;
+		let changes = self.unsaved_changes;
+		let history = std::mem::take(&mut self.unsaved_history);
+		let revision = self.revision;
+		let snapshot = if history.iter().any(|change| change.revision % SNAPSHOT_INTERVAL == 0) {
+			Some((revision, content_text(&self.content)))
+		} else {
+			None
+		};
+		let save: SaveContent = match &self.content {
+			DocumentContent::Text(rope) => {
+				let lines: Vec<String> = rope.to_string()
+					.split('\n')
+					.map(|line| line.to_owned())
+					.collect();
+				let authors = self.line_authors.clone();
+				Box::new(move |transaction| crate::repositories::file_content_repository::new(file_id)
+					.replace_content(transaction, &lines, &authors))
+			}
+			DocumentContent::Crdt(document) => {
+				let document = document.clone();
+				Box::new(move |transaction| crate::repositories::crdt_documents::new(file_id).save(transaction, &document))
+			}
+		};
+		let saved_revision = self.saved_revision.clone();
+		self.unsaved_changes = 0;
+		start_save(file_id, async move {
+			let saved_history = history.clone();
+			// History and content are saved together, so content is never ahead of history
+			let result: Result<(), DatabaseError> = crate::repositories::run(move || in_transaction(|transaction| {
+				crate::repositories::file_history::new(file_id)
+					.append(transaction, &saved_history, snapshot.as_ref().map(|(revision, content)| (*revision, content.as_str())))?;
+				save(transaction)
+			})).await;
+			match result {
+				Ok(_) => {
+					saved_revision.fetch_max(revision, Ordering::SeqCst);
+					info!("Saved {} changes in file {}", changes, file_id);
+				}
+				Err(err) => {
+					error!("Failed to save {} changes in file {}: {}", changes, file_id, err);
+					if let Some(addr) = addr {
+						addr.do_send(SaveFailed { changes, history });
+					}
+				}
+			}
+		});
+	}
+
+	/// Remembers change applied to content as next revision of file
+	fn change_applied(&mut self, author_id: Option<i32>, start: Position, end: Position, lines: Vec<String>, ctx: &mut Context<Self>) {
+		self.revision += 1;
+		self.unsaved_history.push(HistoryChange {
+			revision: self.revision,
+			author_id,
+			start,
+			end,
+			lines,
+		});
+		self.unsaved_changes += 1;
+		if self.unsaved_changes >= MAX_UNSAVED_CHANGES {
+			self.flush(Some(ctx.address()));
+		}
+	}
+}
+
+/// Starts save of file after all previously started saves of the file finished
+fn start_save<F>(file_id: i32, save: F)
+	where F: Future<Output = ()> + Send + 'static {
+	let number = NEXT_SAVE.fetch_add(1, Ordering::SeqCst);
+	let mut saves = SAVES.lock().unwrap_or_else(|err| err.into_inner());
+	let previous = saves.get(&file_id).map(|(_, saving)| saving.clone());
+	let saving: Pin<Box<dyn Future<Output = ()> + Send>> = Box::pin(async move {
+		if let Some(previous) = previous {
+			previous.await;
+		}
+		save.await;
+	});
+	let saving = saving.shared();
+	saves.insert(file_id, (number, saving.clone()));
+	// Save is run even if nobody waits for it and forgotten when it finishes
+	actix::spawn(async move {
+		saving.await;
+		let mut saves = SAVES.lock().unwrap_or_else(|err| err.into_inner());
+		if saves.get(&file_id).is_some_and(|(last, _)| *last == number) {
+			saves.remove(&file_id);
+		}
+	});
+}
+
+/// Waits until all saves of file finished, including saves started while waiting
+pub async fn saves_finished(file_id: i32) {
+	let mut finished = None;
+	loop {
+		let last = SAVES.lock().unwrap_or_else(|err| err.into_inner())
+			.get(&file_id)
+			.map(|(number, saving)| (*number, saving.clone()));
+		match last {
+			Some((number, saving)) if finished != Some(number) => {
+				saving.await;
+				finished = Some(number);
+			}
+			_ => return
+		}
+	}
+}
+
+/// Waits until saves of all files started before finished, so documents closed before are saved
+pub async fn started_saves_finished() {
+	let started: Vec<Saving> = SAVES.lock().unwrap_or_else(|err| err.into_inner())
+		.values()
+		.map(|(_, saving)| saving.clone())
+		.collect();
+	join_all(started).await;
+}
+
+/// Opens document of file. Document of the same file opened before is closed and new document
+/// loads content after its changes are saved.
+pub fn open(file_id: i32, model: DocumentModel) -> Addr<FileDocument> {
+	let mut opened = OPENED.lock().unwrap_or_else(|err| err.into_inner());
+	let previous = opened.get(&file_id).and_then(|document| document.upgrade());
+	let document = FileDocument::new(file_id, model, previous).start();
+	opened.insert(file_id, document.downgrade());
+	document
+}
+
+/// Loads content of file from database
+fn load_content(transaction: &mut Transaction, file_id: i32, model: DocumentModel) -> Result<DocumentContent, DatabaseError> {
+	let content = match model {
+		DocumentModel::Lines => {
+			let lines = crate::repositories::file_content_repository::new(file_id).get_content(transaction)?;
+			DocumentContent::Text(Rope::from_str(&lines.join("\n")))
+		}
+		DocumentModel::Crdt => {
+			let document = match crate::repositories::crdt_documents::new(file_id).get(transaction)? {
+				Some(document) => document,
+				None => {
+					info!("Importing file {} to CRDT document", file_id);
+					let lines = crate::repositories::file_content_repository::new(file_id).get_content(transaction)?;
+					CrdtDocument::from_text(&lines.join("\n"), 0)
+				}
+			};
+			DocumentContent::Crdt(document)
+		}
+	};
+	Ok(content)
+}
+
+fn content_text(content: &DocumentContent) -> String {
+	match content {
+		DocumentContent::Text(rope) => rope.to_string(),
+		DocumentContent::Crdt(document) => document.text(),
+	}
+}
+
+/// Reads content of file which is not opened in any document and authors of its lines. Authors are
+/// saved only by documents using lines model.
+pub fn read_line_authors(file_id: i32) -> Result<(String, Vec<LineAuthor>), DatabaseError> {
+	in_transaction(|transaction| {
+		let repository = crate::repositories::file_content_repository::new(file_id);
+		let lines = repository.get_content(transaction)?;
+		let authors = repository.get_authors(transaction)?;
+		Ok((lines.join("\n"), authors))
+	})
+}
+
+/// Reads content of file which is not opened in any document
+pub fn read_content(file_id: i32, model: DocumentModel) -> Result<String, DatabaseError> {
+	in_transaction(|transaction| Ok(content_text(&load_content(transaction, file_id, model)?)))
+}
+
+/// Returns last revision saved in history of file. History of file which has none yet is started
+/// with snapshot of content returned by `read_content`. File has to be locked by transaction.
+fn saved_revision<C>(transaction: &mut Transaction, file_id: i32, read_content: C) -> Result<i32, DatabaseError>
+	where C: FnOnce(&mut Transaction) -> Result<String, DatabaseError> {
+	let history = crate::repositories::file_history::new(file_id);
+	if let Some(revision) = history.last_revision(transaction)? {
+		return Ok(revision);
+	}
+	info!("Starting history of file {}", file_id);
+	let content = read_content(transaction)?;
+	history.append(transaction, &[], Some((0, &content)))?;
+	Ok(0)
+}
+
+/// Returns last revision saved in history of file which is not opened in any document
+pub fn history_revision(file_id: i32, model: DocumentModel) -> Result<i32, DatabaseError> {
+	in_transaction(|transaction| {
+		crate::repositories::file_history::new(file_id).lock(transaction)?;
+		saved_revision(transaction, file_id, |transaction| Ok(content_text(&load_content(transaction, file_id, model)?)))
+	})
+}
+
+/// Rebuilds content of file in given revision from the newest snapshot before it and changes made
+/// after that snapshot.
+///
+/// # Returns
+///
+/// None if revision is not saved in history of file
+pub fn content_in_revision(file_id: i32, revision: i32) -> Result<Option<String>, DatabaseError> {
+	let history = crate::repositories::file_history::new(file_id);
+	let (snapshot_revision, snapshot) = match history.get_snapshot(revision)? {
+		Some(snapshot) => snapshot,
+		None => return Ok(None)
+	};
+	let changes = history.get_changes(snapshot_revision, revision)?;
+	Ok(apply_history(&snapshot, snapshot_revision, &changes, revision))
+}
+
+/// Applies changes made after snapshot, ordered by revision, to its content.
+///
+/// # Returns
+///
+/// None if any change between snapshot and given revision is missing
+fn apply_history(snapshot: &str, snapshot_revision: i32, changes: &[HistoryChange], revision: i32) -> Option<String> {
+	if changes.len() as i32 != revision - snapshot_revision {
+		return None;
+	}
+	let mut rope = Rope::from_str(snapshot);
+	changes.iter().for_each(|change| replace_in_rope(&mut rope, change.start, change.end, &change.lines));
+	Some(rope.to_string())
+}
+
+/// Position of the end of text
+pub fn end_position(content: &str) -> Position {
+	let last_line = content.rsplit('\n').next().unwrap_or("");
+	Position {
+		row: content.matches('\n').count() as u32,
+		column: last_line.chars().count() as u32,
+	}
+}
+
+/// Saves whole content of file which is not edited in any room. Content is replaced in document
+/// of file if it is still opened, otherwise it is saved in database after previous saves of file.
+/// Save is started right away, so documents of file opened later wait for it. Content is saved in
+/// history of file as one change made by author. Resolves when content is saved.
+pub fn write_content(file_id: i32, model: DocumentModel, content: String, author_id: Option<i32>) -> ContentSaved {
+	let opened = OPENED.lock().unwrap_or_else(|err| err.into_inner())
+		.get(&file_id)
+		.and_then(|document| document.upgrade());
+	if let Some(document) = opened {
+		// Sent before awaiting, so content is saved by flush right after it is replaced
+		let replaced = document.send(ReplaceContent { content, author_id });
+		let saved = saved_content(file_id, &document);
+		return Box::pin(async move {
+			replaced.await.map_err(|err| DatabaseError {
+				error_code: DatabaseError::CANCELED,
+				message: err.to_string(),
+			})?;
+			saved.await
+		});
+	}
+	let (sender, receiver) = oneshot::channel();
+	start_save(file_id, async move {
+		let result = crate::repositories::run(move || write_content_now(file_id, model, &content, author_id)).await;
+		let _ = sender.send(result);
+	});
+	Box::pin(async move {
+		receiver.await.unwrap_or_else(|_| Err(DatabaseError {
+			error_code: DatabaseError::CANCELED,
+			message: "Content was not saved".to_owned(),
+		}))
+	})
+}
+
+/// Saves changes applied to document so far, resolves when they are saved in database
+pub fn saved_content(file_id: i32, document: &Addr<FileDocument>) -> ContentSaved {
+	let flushed = document.send(Flush);
+	Box::pin(async move {
+		match flushed.await {
+			Ok(Some(_)) => Ok(()),
+			Ok(None) => Err(DatabaseError {
+				error_code: DatabaseError::QUERY_ERROR,
+				message: format!("Changes of file {} could not be saved", file_id),
+			}),
+			Err(err) => Err(DatabaseError {
+				error_code: DatabaseError::CANCELED,
+				message: err.to_string(),
+			}),
+		}
+	})
+}
+
+/// Reads old content, saves new content in history and replaces content in one transaction
+fn write_content_now(file_id: i32, model: DocumentModel, content: &str, author_id: Option<i32>) -> Result<(), DatabaseError> {
+	in_transaction(|transaction| {
+		let history = crate::repositories::file_history::new(file_id);
+		history.lock(transaction)?;
+		let old_document = load_content(transaction, file_id, model)?;
+		let old_content = content_text(&old_document);
+		if old_content == content {
+			return Ok(());
+		}
+		let revision = saved_revision(transaction, file_id, |_| Ok(old_content.clone()))? + 1;
+		let author = LineAuthor {
+			author_id,
+			modified_at: now_millis(),
+		};
+		let lines: Vec<String> = content.split('\n')
+			.map(|line| line.to_owned())
+			.collect();
+		let change = HistoryChange {
+			revision,
+			author_id,
+			start: Position { row: 0, column: 0 },
+			end: end_position(&old_content),
+			lines: lines.clone(),
+		};
+		// Whole content is in the change anyway, so snapshot lets content be rebuilt without older changes
+		history.append(transaction, &[change], Some((revision, content)))?;
+		match model {
+			DocumentModel::Lines => {
+				let repository = crate::repositories::file_content_repository::new(file_id);
+				let old_lines: Vec<&str> = old_content.split('\n').collect();
+				let new_lines: Vec<&str> = content.split('\n').collect();
+				let authors = replaced_line_authors(&old_lines, &repository.get_authors(transaction)?, &new_lines, author);
+				repository.replace_content(transaction, &lines, &authors)
+			}
+			DocumentModel::Crdt => {
+				// Clients may still refer to characters of old document, so their ids are not reused
+				let clock = match &old_document {
+					DocumentContent::Crdt(document) => document.clock(),
+					DocumentContent::Text(_) => 0
+				};
+				crate::repositories::crdt_documents::new(file_id).save(transaction, &CrdtDocument::from_text(content, clock))
+			}
+		}
+	})
+}
+
+/// Replaces text between `start` and `end` with `lines`
+fn replace_in_rope(rope: &mut Rope, start: Position, end: Position, lines: &[String]) {
+	let start = char_index(rope, start);
+	let end = char_index(rope, end).max(start);
+	rope.remove(start..end);
+	rope.insert(start, &lines.join("\n"));
+}
+
+/// Replaces text between `start` and `end` with `lines` like `replace_in_rope` and updates authors
+/// of lines. Only lines which are different after replacement get new author.
+fn replace_in_rope_with_authors(
+	rope: &mut Rope,
+	authors: &mut Vec<LineAuthor>,
+	start: Position,
+	end: Position,
+	lines: &[String],
+	author: LineAuthor,
+) {
+	let lines_count = rope.len_lines();
+	authors.resize(lines_count, LineAuthor::default());
+	let first_row = start.row as usize;
+	// Missing lines before start of change are added by it
+	if authors.len() < first_row {
+		authors.resize(first_row, author);
+	}
+	let old_rows = first_row..((end.row.max(start.row) as usize + 1).min(lines_count)).max(first_row);
+	let old_lines: Vec<String> = old_rows.clone().map(|row| rope_line(rope, row)).collect();
+	replace_in_rope(rope, start, end, lines);
+	let new_rows = first_row..(first_row + lines.len().max(1)).min(rope.len_lines());
+	let new_lines: Vec<String> = new_rows.map(|row| rope_line(rope, row)).collect();
+	let old_lines: Vec<&str> = old_lines.iter().map(|line| line.as_str()).collect();
+	let new_lines: Vec<&str> = new_lines.iter().map(|line| line.as_str()).collect();
+	let new_authors = replaced_line_authors(&old_lines, &authors[old_rows.clone()], &new_lines, author);
+	authors.splice(old_rows, new_authors);
+	authors.resize(rope.len_lines(), LineAuthor::default());
+}
+
+/// Authors of lines replacing old lines. Lines which are unchanged keep their authors, other lines
+/// get `author`.
+fn replaced_line_authors(old_lines: &[&str], old_authors: &[LineAuthor], new_lines: &[&str], author: LineAuthor) -> Vec<LineAuthor> {
+	let mut authors = Vec::with_capacity(new_lines.len());
+	for operation in capture_diff_slices(Algorithm::Myers, old_lines, new_lines) {
+		let (tag, old_range, new_range) = operation.as_tag_tuple();
+		match tag {
+			DiffTag::Equal => authors.extend(old_range.map(|index| old_authors.get(index).copied().unwrap_or_default())),
+			DiffTag::Insert | DiffTag::Replace => authors.extend(new_range.map(|_| author)),
+			DiffTag::Delete => {}
+		}
+	}
+	authors
+}
+
+/// Line of rope without line ending
+fn rope_line(rope: &Rope, row: usize) -> String {
+	let line = rope.line(row).to_string();
+	match line.strip_suffix('\n') {
+		Some(line) => line.to_owned(),
+		None => line
+	}
+}
+
+/// Current time in milliseconds since Unix epoch
+fn now_millis() -> Option<i64> {
+	SystemTime::now().duration_since(UNIX_EPOCH)
+		.map(|duration| duration.as_millis() as i64)
+		.ok()
+}
+
+/// Converts position to index of character in rope. Columns after end of line are moved to the end of
+/// line and missing lines are added as empty lines.
+fn char_index(rope: &mut Rope, position: Position) -> usize {
+	let row = position.row as usize;
+	while rope.len_lines() <= row {
+		rope.insert_char(rope.len_chars(), '\n');
+	}
+	let line = rope.line(row);
+	let mut line_length = line.len_chars();
+	if line_length > 0 && line.char(line_length - 1) == '\n' {
+		line_length -= 1;
+	}
+	rope.line_to_char(row) + line_length.min(position.column as usize)
+}
+
+impl Actor for FileDocument {
+	type Context = Context<Self>;
+
+	fn started(&mut self, ctx: &mut Self::Context) {
+		let file_id = self.file_id;
+		let model = self.model;
+		let previous = self.previous.take();
+		ctx.wait(async move {
+			if let Some(previous) = previous {
+				// Previous document starts its last save before it responds, or it is stopped already
+				let _ = previous.send(Close).await;
+			}
+			saves_finished(file_id).await;
+			crate::repositories::run(move || in_transaction(|transaction| {
+				crate::repositories::file_history::new(file_id).lock(transaction)?;
+				let content = load_content(transaction, file_id, model)?;
+				let revision = saved_revision(transaction, file_id, |_| Ok(content_text(&content)))?;
+				let line_authors = match content {
+					DocumentContent::Text(_) => crate::repositories::file_content_repository::new(file_id).get_authors(transaction)?,
+					DocumentContent::Crdt(_) => Vec::new()
+				};
+				Ok((content, revision, line_authors))
+			})).await
+		}
+			.into_actor(self)
+			.then(move |res: Result<(DocumentContent, i32, Vec<LineAuthor>), DatabaseError>, act, ctx| {
+				match res {
+					Ok((content, revision, line_authors)) => {
+						act.content = content;
+						act.revision = revision;
+						act.saved_revision.store(revision, Ordering::SeqCst);
+						act.line_authors = line_authors;
+					}
+					Err(err) => {
+						error!("Failed to load file {}: {}", file_id, err);
+						ctx.stop();
+					}
+				}
+				fut::ready(())
+			}));
+		ctx.run_interval(FLUSH_INTERVAL, |act, ctx| act.flush(Some(ctx.address())));
+	}
+
+	fn stopping(&mut self, _: &mut Self::Context) -> Running {
+		self.flush(None);
+		Running::Stop
+	}
+}
+
+impl Handler<ApplyChange> for FileDocument {
+	type Result = MessageResult<ApplyChange>;
+
+	fn handle(&mut self, msg: ApplyChange, ctx: &mut Context<Self>) -> Self::Result {
+		MessageResult(self.apply(msg.change, msg.author_id, ctx))
+	}
+}
+
+impl Handler<ReplaceContent> for FileDocument {
+	type Result = ();
+
+	fn handle(&mut self, msg: ReplaceContent, ctx: &mut Context<Self>) {
+		let old_content = content_text(&self.content);
+		if old_content == msg.content {
+			return;
+		}
+		let change = FileChange {
+			session_id: SERVER_SESSION_ID,
+			start: Position { row: 0, column: 0 },
+			end: end_position(&old_content),
+			file_id: self.file_id,
+			lines: msg.content.split('\n').map(|line| line.to_owned()).collect(),
+			revision: None,
+			client_change_id: None,
+		};
+		info!("Replacing content of file {} which is closing", self.file_id);
+		self.apply(change, msg.author_id, ctx);
+	}
+}
+
+impl Handler<IntegrateOperations> for FileDocument {
+	type Result = Result<Vec<TextChange>, UnknownElement>;
+
+	fn handle(&mut self, msg: IntegrateOperations, ctx: &mut Context<Self>) -> Self::Result {
+		let changes = match &mut self.content {
+			DocumentContent::Crdt(document) => document.integrate_all(&msg.operations)?,
+			DocumentContent::Text(_) => {
+				error!("Tried to integrate CRDT operations in file {} which does not use CRDT model", self.file_id);
+				return Ok(Vec::new());
+			}
+		};
+		for change in changes.iter() {
+			let lines = change.text.split('\n').map(|line| line.to_owned()).collect();
+			self.change_applied(msg.author_id, change.start, change.end, lines, ctx);
+		}
+		Ok(changes)
+	}
+}
+
+impl Handler<GetContent> for FileDocument {
+	type Result = String;
+
+	fn handle(&mut self, _: GetContent, _: &mut Context<Self>) -> Self::Result {
+		content_text(&self.content)
+	}
+}
+
+impl Handler<GetCrdtState> for FileDocument {
+	type Result = Option<CrdtDocument>;
+
+	fn handle(&mut self, _: GetCrdtState, _: &mut Context<Self>) -> Self::Result {
+		match &self.content {
+			DocumentContent::Crdt(document) => Some(document.clone()),
+			DocumentContent::Text(_) => None
+		}
+	}
+}
+
+impl Handler<GetLineAuthors> for FileDocument {
+	type Result = MessageResult<GetLineAuthors>;
+
+	fn handle(&mut self, _: GetLineAuthors, _: &mut Context<Self>) -> Self::Result {
+		MessageResult((content_text(&self.content), self.line_authors.clone()))
+	}
+}
+
+impl Handler<GetChecksum> for FileDocument {
+	type Result = String;
+
+	fn handle(&mut self, _: GetChecksum, _: &mut Context<Self>) -> Self::Result {
+		let mut hasher = Sha256::new();
+		match &self.content {
+			DocumentContent::Text(rope) => rope.chunks().for_each(|chunk| hasher.update(chunk.as_bytes())),
+			DocumentContent::Crdt(document) => hasher.update(document.text().as_bytes()),
+		}
+		hasher.finalize()
+			.iter()
+			.map(|byte| format!("{:02x}", byte))
+			.collect()
+	}
+}
+
+impl Handler<Close> for FileDocument {
+	type Result = ();
+
+	fn handle(&mut self, _: Close, ctx: &mut Context<Self>) {
+		// Saved right away, so document opened after this one waits for the save
+		self.flush(None);
+		ctx.stop();
+	}
+}
+
+impl Handler<Discard> for FileDocument {
+	type Result = ();
+
+	fn handle(&mut self, _: Discard, ctx: &mut Context<Self>) {
+		self.unsaved_changes = 0;
+		self.unsaved_history.clear();
+		ctx.stop();
+	}
+}
+
+impl Handler<SaveFailed> for FileDocument {
+	type Result = ();
+
+	fn handle(&mut self, msg: SaveFailed, _: &mut Context<Self>) {
+		self.unsaved_changes += msg.changes;
+		// Newer changes may be waiting for saving already
+		self.unsaved_history.extend(msg.history);
+		self.unsaved_history.sort_by_key(|change| change.revision);
+	}
+}
+
+impl Handler<Flush> for FileDocument {
+	type Result = ResponseFuture<Option<i32>>;
+
+	fn handle(&mut self, _: Flush, ctx: &mut Context<Self>) -> Self::Result {
+		let file_id = self.file_id;
+		let revision = self.revision;
+		let saved_revision = self.saved_revision.clone();
+		self.flush(Some(ctx.address()));
+		Box::pin(async move {
+			saves_finished(file_id).await;
+			if saved_revision.load(Ordering::SeqCst) >= revision {
+				Some(revision)
+			} else {
+				None
+			}
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn author(author_id: i32) -> LineAuthor {
+		LineAuthor {
+			author_id: Some(author_id),
+			modified_at: Some(author_id as i64 * 1000),
+		}
+	}
+
+	#[test]
+	fn unchanged_lines_keep_their_authors() {
+		let authors = replaced_line_authors(&["a", "b"], &[author(1), author(2)], &["a", "b"], author(3));
+		assert_eq!(authors, vec![author(1), author(2)]);
+	}
+
+	#[test]
+	fn replaced_and_inserted_lines_get_new_author() {
+		let authors = replaced_line_authors(&["a", "b", "c"], &[author(1), author(2), author(1)], &["a", "x", "c", "d"], author(3));
+		assert_eq!(authors, vec![author(1), author(3), author(1), author(3)]);
+	}
+
+	#[test]
+	fn deleted_lines_drop_out() {
+		let authors = replaced_line_authors(&["a", "b", "c"], &[author(1), author(2), author(3)], &["a", "c"], author(4));
+		assert_eq!(authors, vec![author(1), author(3)]);
+	}
+
+	#[test]
+	fn lines_without_known_author_get_default_author() {
+		let authors = replaced_line_authors(&["a", "b", "c"], &[author(1)], &["a", "b", "c", "d"], author(2));
+		assert_eq!(authors, vec![author(1), LineAuthor::default(), LineAuthor::default(), author(2)]);
+	}
+
+	fn history_change(revision: i32, start: (u32, u32), end: (u32, u32), lines: &[&str]) -> HistoryChange {
+		HistoryChange {
+			revision,
+			author_id: None,
+			start: Position { row: start.0, column: start.1 },
+			end: Position { row: end.0, column: end.1 },
+			lines: lines.iter().map(|line| line.to_string()).collect(),
+		}
+	}
+
+	#[test]
+	fn history_is_applied_to_snapshot_in_order() {
+		let changes = [
+			history_change(101, (0, 1), (0, 1), &["x", "y"]),
+			history_change(102, (1, 0), (2, 1), &[""]),
+		];
+		assert_eq!(apply_history("ab\ncd\nef", 100, &changes, 102), Some("ax\nd\nef".to_owned()));
+	}
+
+	#[test]
+	fn snapshot_in_requested_revision_is_returned_as_it_is() {
+		assert_eq!(apply_history("ab", 100, &[], 100), Some("ab".to_owned()));
+	}
+
+	#[test]
+	fn missing_changes_in_history_give_no_content() {
+		let changes = [history_change(101, (0, 0), (0, 0), &["x"])];
+		assert_eq!(apply_history("ab", 100, &changes, 102), None);
+		assert_eq!(apply_history("ab", 100, &[], 101), None);
+	}
+}
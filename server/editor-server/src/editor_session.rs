@@ -4,17 +4,13 @@ use actix::*;
 use actix_web_actors::ws;
 use crate::server;
 use crate::models::User;
-use crate::server::{ProjectInfoDto, ErrorMessage, FileCreated, FileDeleted, ChangeInFile};
+use crate::server::{ProjectInfoDto, ErrorMessage, CloseSession, FileChecksum, Event, ProjectEvent};
+use crate::crdt::Operation;
+use crate::protocol::{Protocol, Frame, IncomingMessage, OutgoingMessage, DecodeError, ChatMessageDto, PROTOCOL_VERSION};
 use log::{error, info, warn};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 
-const INCOMING_CODE_NEW_FILE: &str = "1";
-const INCOMING_CODE_DELETE_FILE: &str = "2";
-const INCOMING_CODE_RENAME_FILE: &str = "3";
-const INCOMING_CODE_GET_FILE_CONTENT: &str = "4";
-const INCOMING_CODE_CHANGE_IN_FILE: &str = "5";
-
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
 
@@ -28,20 +24,44 @@ pub struct EditorSession {
 	pub user: User,
 	/// Editor server
 	pub addr: Addr<server::EditorServer>,
+	/// Format of messages negotiated in websocket handshake
+	pub protocol: Protocol,
+	/// Session which client wants to resume with this connection
+	pub resume: Option<ResumeRequest>,
+}
+
+/// Resume token and id of last event received by client, sent when it reconnects
+pub struct ResumeRequest {
+	pub resume_token: String,
+	/// None if client did not receive any event
+	pub last_event_id: Option<u64>,
 }
 
 #[derive(Message)]
-#[rtype(i32)]
+#[rtype(result = "Connected")]
 pub struct Connect {
 	pub addr: Addr<EditorSession>,
 	pub project_id: i32,
 	pub user: User,
+	pub resume: Option<ResumeRequest>,
+}
+
+/// Session assigned to connection
+pub struct Connected {
+	pub session_id: i32,
+	/// Token allowing to resume session after connection is lost
+	pub resume_token: String,
+	/// Whether connection took over existing session
+	pub resumed: bool,
 }
 
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct Disconnect {
-	pub session_id: i32
+	pub session_id: i32,
+	pub addr: Addr<EditorSession>,
+	/// False if connection was closed on purpose and session should end immediately
+	pub resumable: bool,
 }
 
 //TODO
@@ -57,6 +77,8 @@ pub struct IncomingChange {
 pub struct FileCreationRequest {
 	pub session_id: i32,
 	pub filename: String,
+	/// None if file is created in root of project
+	pub folder_id: Option<i32>,
 }
 
 #[derive(Message)]
@@ -74,6 +96,49 @@ pub struct FileNameChangeRequest {
 	pub new_filename: String,
 }
 
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct FileMoveRequest {
+	pub session_id: i32,
+	pub file_id: i32,
+	/// None moves file to root of project
+	pub folder_id: Option<i32>,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct FolderCreationRequest {
+	pub session_id: i32,
+	pub name: String,
+	/// None if folder is created in root of project
+	pub parent_id: Option<i32>,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct FolderRenameRequest {
+	pub session_id: i32,
+	pub folder_id: i32,
+	pub new_name: String,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct FolderMoveRequest {
+	pub session_id: i32,
+	pub folder_id: i32,
+	/// None moves folder to root of project
+	pub parent_id: Option<i32>,
+}
+
+/// Deletes folder with all its subfolders and files
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct FolderDeletionRequest {
+	pub session_id: i32,
+	pub folder_id: i32,
+}
+
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct FileContentRequest {
@@ -81,6 +146,61 @@ pub struct FileContentRequest {
 	pub file_id: i32,
 }
 
+/// Checksum of file content computed by client. If it differs from content on server, session gets
+/// content of file again.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct FileChecksumReport {
+	pub session_id: i32,
+	pub file_id: i32,
+	pub revision: usize,
+	pub checksum: String,
+}
+
+/// Changes made by client while it was offline, each made on the result of previous ones
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct OfflineChangesRequest {
+	pub session_id: i32,
+	pub file_id: i32,
+	/// Last revision of file seen by client before it went offline
+	pub revision: Option<usize>,
+	/// Checksum of file content in that revision, used when server does not remember the revision
+	pub base_checksum: Option<String>,
+	pub changes: Vec<FileChange>,
+}
+
+/// Where in project is user of session. Sent to other sessions of project.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct PresenceUpdate {
+	pub session_id: i32,
+	/// File opened by user, None if no file is opened
+	pub file_id: Option<i32>,
+	pub cursor: Option<Position>,
+	pub selections: Vec<Selection>,
+	/// Visible part of file, forwarded only to sessions following this one
+	pub viewport: Option<Viewport>,
+}
+
+/// Session starts or stops following other session
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct FollowRequest {
+	pub session_id: i32,
+	/// Session to follow, None stops following
+	pub leader_id: Option<i32>,
+}
+
+/// CRDT operations made by client using CRDT document model
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CrdtOperationsRequest {
+	pub session_id: i32,
+	pub file_id: i32,
+	pub operations: Vec<Operation>,
+}
+
 impl Actor for EditorSession {
 	type Context = ws::WebsocketContext<Self>;
 
@@ -94,11 +214,20 @@ impl Actor for EditorSession {
 				addr,
 				project_id: self.project_id,
 				user: self.user.clone(),
+				resume: self.resume.take(),
 			})
 			.into_actor(self)
 			.then(|res, act, ctx| {
 				match res {
-					Ok(res) => act.id = res,
+					Ok(connected) => {
+						act.id = connected.session_id;
+						act.send(OutgoingMessage::Welcome {
+							version: PROTOCOL_VERSION,
+							session_id: connected.session_id,
+							resume_token: connected.resume_token,
+							resumed: connected.resumed,
+						}, ctx);
+					}
 					// something is wrong with chat server
 					_ => ctx.stop(),
 				}
@@ -107,49 +236,153 @@ impl Actor for EditorSession {
 			.wait(ctx);
 	}
 
-	fn stopping(&mut self, _: &mut Self::Context) -> Running {
-		// notify chat server
-		self.addr.do_send(Disconnect { session_id: self.id });
+	fn stopping(&mut self, ctx: &mut Self::Context) -> Running {
+		// notify chat server, connection was lost if it wasn't closed on purpose before
+		self.addr.do_send(Disconnect {
+			session_id: self.id,
+			addr: ctx.address(),
+			resumable: true,
+		});
 		Running::Stop
 	}
 }
 
-/// Handle messages from chat server, we simply send it to peer websocket
-impl Handler<server::Message> for EditorSession {
+impl Handler<server::Event> for EditorSession {
 	type Result = ();
 
-	fn handle(&mut self, msg: server::Message, ctx: &mut Self::Context) {
-		ctx.text(msg.0);
+	fn handle(&mut self, msg: Event, ctx: &mut Self::Context) -> Self::Result {
+		let message = match msg.event {
+			ProjectEvent::SessionJoined(joined) => OutgoingMessage::SessionJoined {
+				session_id: joined.session_id,
+				name: joined.name,
+			},
+			ProjectEvent::SessionLeft(left) => OutgoingMessage::SessionLeft { session_id: left.session_id },
+			ProjectEvent::ActiveFileChanged(changed) => OutgoingMessage::ActiveFileChanged {
+				session_id: changed.session_id,
+				file_id: changed.file_id,
+			},
+			ProjectEvent::FollowChanged(changed) => OutgoingMessage::FollowChanged {
+				session_id: changed.session_id,
+				leader_id: changed.leader_id,
+			},
+			ProjectEvent::ProjectUpdated(updated) => OutgoingMessage::ProjectUpdated { project: updated.project },
+			ProjectEvent::FileCreated(created) => OutgoingMessage::FileCreated {
+				file_id: created.id,
+				name: created.name,
+				folder_id: created.folder_id,
+			},
+			ProjectEvent::FileDeleted(deleted) => OutgoingMessage::FileDeleted { file_id: deleted.id },
+			ProjectEvent::FileRenamed(renamed) => OutgoingMessage::FileRenamed {
+				file_id: renamed.id,
+				name: renamed.name,
+			},
+			ProjectEvent::FileMoved(moved) => OutgoingMessage::FileMoved {
+				file_id: moved.id,
+				folder_id: moved.folder_id,
+			},
+			ProjectEvent::FolderChanged(changed) => OutgoingMessage::FolderChanged { folder: changed.folder },
+			ProjectEvent::FolderDeleted(deleted) => OutgoingMessage::FolderDeleted { folder_id: deleted.id },
+			ProjectEvent::ChangeInFile(change) if change.session_id == self.id => OutgoingMessage::ChangeAcknowledged {
+				file_id: change.file_id,
+				revision: change.revision,
+				client_change_id: change.client_change_id,
+			},
+			ProjectEvent::ChangeInFile(change) => OutgoingMessage::ChangeInFile {
+				file_id: change.file_id,
+				start: Position { row: change.start_row, column: change.start_column },
+				end: Position { row: change.end_row, column: change.end_column },
+				revision: change.revision,
+				lines: change.change.split('\n').map(|line| line.to_owned()).collect(),
+			},
+			ProjectEvent::CrdtOperations(operations) => OutgoingMessage::CrdtOperations(operations),
+			ProjectEvent::CommentThreadChanged(thread) => OutgoingMessage::CommentThreadChanged(thread),
+			ProjectEvent::SuggestionCreated(suggestion) => OutgoingMessage::SuggestionCreated(suggestion),
+			ProjectEvent::SuggestionReviewed(reviewed) => OutgoingMessage::SuggestionReviewed {
+				suggestion_id: reviewed.suggestion_id,
+				accepted: reviewed.accepted,
+			},
+			ProjectEvent::ChatMessage(message) => OutgoingMessage::ChatMessage(ChatMessageDto::new(message, self.user.id)),
+		};
+		match self.protocol.encode_event(msg.id, &message) {
+			Ok(frames) => self.write_frames(frames, ctx),
+			Err(err) => error!("Error while serializing event {} to session {}: {}", msg.id, self.id, err)
+		}
 	}
 }
 
-impl Handler<server::ChangeInFile> for EditorSession{
+impl Handler<server::FileChecksum> for EditorSession {
 	type Result = ();
 
-	fn handle(&mut self, msg: ChangeInFile, ctx: &mut Self::Context) -> Self::Result {
-		let message =format!("6{} {} {} {} {} {} {}", msg.file_id, msg.start_row, msg.start_column, msg.end_row, msg.end_column, msg.change_id, msg.change);
-		ctx.text(message);
+	fn handle(&mut self, msg: FileChecksum, ctx: &mut Self::Context) -> Self::Result {
+		self.send(OutgoingMessage::FileChecksum {
+			file_id: msg.file_id,
+			revision: msg.revision,
+			checksum: msg.checksum,
+		}, ctx);
 	}
 }
 
-impl Handler<server::ProjectInfoDto> for EditorSession {
+impl Handler<server::OfflineChangesResult> for EditorSession {
 	type Result = ();
 
-	fn handle(&mut self, msg: ProjectInfoDto, ctx: &mut Self::Context) -> Self::Result {
-		match serde_json::to_string(&msg) {
-			Ok(json) => ctx.text(format!("9{}", json)),
-			Err(err) => {
-				error!("Error while serializing ProjectInfoDto: {}", err);
-			}
-		}
+	fn handle(&mut self, msg: server::OfflineChangesResult, ctx: &mut Self::Context) -> Self::Result {
+		self.send(OutgoingMessage::OfflineChangesResult(msg), ctx);
 	}
 }
 
-impl Handler<server::FileCreated> for EditorSession {
+impl Handler<server::SessionPresence> for EditorSession {
 	type Result = ();
 
-	fn handle(&mut self, msg: FileCreated, ctx: &mut Self::Context) -> Self::Result {
-		ctx.text(format!("3{} {}", msg.id, msg.name));
+	fn handle(&mut self, msg: server::SessionPresence, ctx: &mut Self::Context) -> Self::Result {
+		self.send(OutgoingMessage::Presence(msg), ctx);
+	}
+}
+
+impl Handler<server::LeaderFileChanged> for EditorSession {
+	type Result = ();
+
+	fn handle(&mut self, msg: server::LeaderFileChanged, ctx: &mut Self::Context) -> Self::Result {
+		self.send(OutgoingMessage::LeaderFileChanged {
+			leader_id: msg.leader_id,
+			file_id: msg.file_id,
+		}, ctx);
+	}
+}
+
+impl Handler<server::CommentThreads> for EditorSession {
+	type Result = ();
+
+	fn handle(&mut self, msg: server::CommentThreads, ctx: &mut Self::Context) -> Self::Result {
+		self.send(OutgoingMessage::CommentThreads { threads: msg.threads }, ctx);
+	}
+}
+
+impl Handler<server::Suggestions> for EditorSession {
+	type Result = ();
+
+	fn handle(&mut self, msg: server::Suggestions, ctx: &mut Self::Context) -> Self::Result {
+		self.send(OutgoingMessage::Suggestions { suggestions: msg.suggestions }, ctx);
+	}
+}
+
+impl Handler<server::ChatHistory> for EditorSession {
+	type Result = ();
+
+	fn handle(&mut self, msg: server::ChatHistory, ctx: &mut Self::Context) -> Self::Result {
+		let user_id = self.user.id;
+		self.send(OutgoingMessage::ChatHistory {
+			messages: msg.messages.into_iter()
+				.map(|message| ChatMessageDto::new(message, user_id))
+				.collect()
+		}, ctx);
+	}
+}
+
+impl Handler<server::ProjectInfoDto> for EditorSession {
+	type Result = ();
+
+	fn handle(&mut self, msg: ProjectInfoDto, ctx: &mut Self::Context) -> Self::Result {
+		self.send(OutgoingMessage::ProjectInfo(msg), ctx);
 	}
 }
 
@@ -157,15 +390,21 @@ impl Handler<server::ErrorMessage> for EditorSession {
 	type Result = ();
 
 	fn handle(&mut self, msg: ErrorMessage, ctx: &mut Self::Context) -> Self::Result {
-		ctx.text(format!("a{}", msg.msg));
+		self.send(OutgoingMessage::Error { message: msg.msg }, ctx);
 	}
 }
 
-impl Handler<server::FileDeleted> for EditorSession {
+impl Handler<server::CloseSession> for EditorSession {
 	type Result = ();
 
-	fn handle(&mut self, msg: FileDeleted, ctx: &mut Self::Context) -> Self::Result {
-		ctx.text(format!("4{}", msg.id));
+	fn handle(&mut self, msg: CloseSession, ctx: &mut Self::Context) -> Self::Result {
+		info!("Closing session {}: {}", self.id, msg.reason);
+		self.leave(ctx);
+		ctx.close(Some(ws::CloseReason {
+			code: ws::CloseCode::Normal,
+			description: Some(msg.reason),
+		}));
+		ctx.stop();
 	}
 }
 
@@ -173,11 +412,27 @@ impl Handler<server::FileContent> for EditorSession {
 	type Result = ();
 
 	fn handle(&mut self, msg: server::FileContent, ctx: &mut Self::Context) -> Self::Result {
-		ctx.text(format!("5{} {}", msg.file_id, msg.content));
+		self.send(OutgoingMessage::FileContent {
+			file_id: msg.file_id,
+			content: msg.content,
+			revision: msg.revision,
+		}, ctx);
+	}
+}
+
+impl Handler<server::CrdtState> for EditorSession {
+	type Result = ();
+
+	fn handle(&mut self, msg: server::CrdtState, ctx: &mut Self::Context) -> Self::Result {
+		self.send(OutgoingMessage::CrdtState {
+			file_id: msg.file_id,
+			document: msg.document,
+			revision: msg.revision,
+		}, ctx);
 	}
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct FileChange {
@@ -186,21 +441,104 @@ pub struct FileChange {
 	pub end: Position,
 	pub file_id: i32,
 	pub lines: Vec<String>,
-	pub last_change_applied_id: Option<i32>,
+	/// Revision of file the change was made on
+	pub revision: Option<usize>,
+	/// Id given to change by client, sent back in acknowledgement
+	pub client_change_id: Option<u32>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 pub struct Position {
 	pub row: u32,
 	pub column: u32,
 }
 
+/// Selected text between two positions in file
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Selection {
+	pub start: Position,
+	pub end: Position,
+}
+
+/// Message sent by session to project chat
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ChatMessageRequest {
+	pub session_id: i32,
+	pub content: String,
+}
+
+/// Session requests chat messages older than given one, or the last ones
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ChatHistoryRequest {
+	pub session_id: i32,
+	pub before_id: Option<i32>,
+}
+
+/// Starts comment thread about range of file
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CommentThreadCreationRequest {
+	pub session_id: i32,
+	pub file_id: i32,
+	pub start: Position,
+	pub end: Position,
+	pub content: String,
+}
+
+/// Adds comment to existing thread
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CommentRequest {
+	pub session_id: i32,
+	pub thread_id: i32,
+	pub content: String,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CommentThreadResolutionRequest {
+	pub session_id: i32,
+	pub thread_id: i32,
+	pub resolved: bool,
+}
+
+/// Session suggests change in file instead of applying it, project owner accepts or rejects it later
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SuggestionRequest {
+	pub change: FileChange,
+}
+
+/// Project owner accepts or rejects suggested change
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SuggestionReviewRequest {
+	pub session_id: i32,
+	pub suggestion_id: i32,
+	pub accept: bool,
+}
+
+/// Rows of file visible in editor
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Viewport {
+	pub first_row: u32,
+	pub last_row: u32,
+}
+
 impl  std::cmp::PartialEq for Position{
 	fn eq(&self, other: &Self) -> bool {
 		self.row == other.row && self.column == other.column
 	}
 }
 
+impl std::cmp::PartialOrd for Position {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some((self.row, self.column).cmp(&(other.row, other.column)))
+	}
+}
+
 /// WebSocket message handler
 impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for EditorSession {
 	fn handle(
@@ -227,12 +565,13 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for EditorSession {
 				self.hb = Instant::now();
 			}
 			ws::Message::Text(text) => {
-				self.parse_message_and_send_to_server(text, ctx);
+				self.parse_message_and_send_to_server(self.protocol.decode_text(&text));
 			}
 			ws::Message::Binary(bytes) => {
-				warn!("Unexpected binary: {:?} {}", bytes, bytes.len());
+				self.parse_message_and_send_to_server(self.protocol.decode_binary(&bytes));
 			},
 			ws::Message::Close(reason) => {
+				self.leave(ctx);
 				ctx.close(reason);
 				ctx.stop();
 			}
@@ -245,153 +584,234 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for EditorSession {
 }
 
 impl  EditorSession {
-	fn parse_message_and_send_to_server(&self, message: String, _: &mut ws::WebsocketContext<Self>) {
-		if message.len() <= 1 {
-			warn!("Incoming empty message or message with only code and no content form editor session {}: {}", self.id, message);
-			return;
+	/// Encodes message in protocol of session and sends it to client
+	fn send(&self, message: OutgoingMessage, ctx: &mut ws::WebsocketContext<Self>) {
+		match self.protocol.encode(&message) {
+			Ok(frames) => self.write_frames(frames, ctx),
+			Err(err) => error!("Error while serializing message to session {}: {}", self.id, err)
 		}
-		let (incoming_code, incoming_message) = message.split_at(1);
-		match incoming_code {
-			INCOMING_CODE_NEW_FILE => {
-				info!("New file req, file name {}", incoming_message);
+	}
+
+	fn write_frames(&self, frames: Vec<Frame>, ctx: &mut ws::WebsocketContext<Self>) {
+		frames.into_iter().for_each(|frame| match frame {
+			Frame::Text(text) => ctx.text(text),
+			Frame::Binary(bytes) => ctx.binary(bytes),
+		});
+	}
+
+	/// Ends session immediately, without waiting for client to resume it
+	fn leave(&self, ctx: &mut ws::WebsocketContext<Self>) {
+		self.addr.do_send(Disconnect {
+			session_id: self.id,
+			addr: ctx.address(),
+			resumable: false,
+		});
+	}
+
+	fn parse_message_and_send_to_server(&self, message: Result<IncomingMessage, DecodeError>) {
+		match message {
+			Ok(incoming_message) => self.send_to_server(incoming_message),
+			Err(err) => warn!("Session {} sent message which could not be decoded: {}", self.id, err)
+		}
+	}
+
+	fn send_to_server(&self, message: IncomingMessage) {
+		match message {
+			IncomingMessage::CreateFile { name, folder_id } => {
+				info!("New file req, file name {}", name);
 				self.addr.do_send(FileCreationRequest {
 					session_id: self.id,
-					filename: incoming_message.to_owned(),
-				})
+					filename: name,
+					folder_id,
+				});
 			}
-			INCOMING_CODE_DELETE_FILE => {
-				let file_id;
-				match incoming_message.parse::<i32>() {
-					Ok(id) => file_id = id,
-					Err(_) => {
-						warn!("Unparsable file id");
-						return;
-					}
-				}
+			IncomingMessage::DeleteFile { file_id } => {
 				info!("Session {} editing project {} requested deletion of file {}", self.id, self.project_id, file_id);
 				self.addr.do_send(FileDeletionRequest {
 					session_id: self.id,
 					file_id,
 				});
 			}
-			INCOMING_CODE_RENAME_FILE => {
-				info!("Rename file request");
+			IncomingMessage::RenameFile { file_id, name } => {
+				info!("Session {} editing project {} requested renaming file {} to {}", self.id, self.project_id, file_id, name);
+				self.addr.do_send(FileNameChangeRequest {
+					session_id: self.id,
+					file_id,
+					new_filename: name,
+				});
 			}
-			INCOMING_CODE_CHANGE_IN_FILE => {
-				self.handle_incoming_change_in_file_message(incoming_message);
-				info!("Incoming change in file")
+			IncomingMessage::MoveFile { file_id, folder_id } => {
+				info!("Session {} editing project {} requested moving file {} to folder {:?}", self.id, self.project_id, file_id, folder_id);
+				self.addr.do_send(FileMoveRequest {
+					session_id: self.id,
+					file_id,
+					folder_id,
+				});
 			}
-			INCOMING_CODE_GET_FILE_CONTENT => {
+			IncomingMessage::GetFileContent { file_id } => {
 				info!("Incoming file content request");
-				let file_id;
-				match incoming_message.parse::<i32>() {
-					Ok(id) => file_id = id,
-					Err(_) => {
-						warn!("Session {} sent file content request with unparsable file id {}", self.id, incoming_message);
-						return;
-					}
-				}
 				self.addr.do_send(FileContentRequest {
 					session_id: self.id,
 					file_id,
-				})
+				});
 			}
-			_ => {
-				error!("Unknown message code: {} of message in session {}", incoming_code, self.id);
+			IncomingMessage::ChangeInFile { file_id, start, end, lines, revision, client_change_id } => {
+				info!("Incoming change in file");
+				self.addr.do_send(FileChange {
+					session_id: self.id,
+					start,
+					end,
+					file_id,
+					lines,
+					revision,
+					client_change_id,
+				});
 			}
-		}
-	}
-
-	fn handle_incoming_change_in_file_message(&self, message: &str) {
-		let splitted_message: Vec<&str> = message.splitn(7, " ").collect();
-		if splitted_message.len() != 7 {
-			error!("Illegal change in file package sent: could not split in 7 required parts");
-			return;
-		}
-		let file_id;
-		match splitted_message[0].parse::<i32>() {
-			Ok(id) => file_id = id,
-			Err(_) => {
-				error!("Could not parse file id in incoming file change package: {}", splitted_message[0]);
-				return;
+			IncomingMessage::OfflineChanges(offline_changes) => {
+				info!("Session {} sent {} offline changes in file {}", self.id, offline_changes.changes.len(), offline_changes.file_id);
+				let session_id = self.id;
+				let file_id = offline_changes.file_id;
+				let revision = offline_changes.revision;
+				self.addr.do_send(OfflineChangesRequest {
+					session_id,
+					file_id,
+					revision,
+					base_checksum: offline_changes.base_checksum,
+					changes: offline_changes.changes.into_iter()
+						.map(|change| FileChange {
+							session_id,
+							start: change.start,
+							end: change.end,
+							file_id,
+							lines: change.lines,
+							revision,
+							client_change_id: change.client_change_id,
+						})
+						.collect(),
+				});
 			}
-		}
-		let start;
-		match self.convert_to_position([splitted_message[1], splitted_message[2]]) {
-			Ok(position) => start = position,
-			Err(err) => {
-				match err {
-					ConvertingToPositionError::UnparsableRow => {
-						error!("Unparasble starting row: {}", splitted_message[1]);
-					}
-					ConvertingToPositionError::UnparsableColumn => {
-						error!("Unparsable starting column: {}", splitted_message[2]);
-					}
-				}
-				return;
+			IncomingMessage::FileChecksum { file_id, revision, checksum } => {
+				self.addr.do_send(FileChecksumReport {
+					session_id: self.id,
+					file_id,
+					revision,
+					checksum,
+				});
 			}
-		}
-		let end;
-		match self.convert_to_position([splitted_message[3], splitted_message[4]]) {
-			Ok(position) => end = position,
-			Err(err) => {
-				match err {
-					ConvertingToPositionError::UnparsableRow => {
-						error!("Unparasble ending row: {}", splitted_message[1]);
-					}
-					ConvertingToPositionError::UnparsableColumn => {
-						error!("Unparsable ending column: {}", splitted_message[2]);
+			IncomingMessage::Presence { file_id, cursor, selections, viewport } => {
+				self.addr.do_send(PresenceUpdate {
+					session_id: self.id,
+					file_id,
+					cursor,
+					selections,
+					viewport,
+				});
+			}
+			IncomingMessage::Follow { session_id } => {
+				info!("Session {} editing project {} follows session {:?}", self.id, self.project_id, session_id);
+				self.addr.do_send(FollowRequest {
+					session_id: self.id,
+					leader_id: session_id,
+				});
+			}
+			IncomingMessage::ChatMessage { content } => {
+				self.addr.do_send(ChatMessageRequest {
+					session_id: self.id,
+					content,
+				});
+			}
+			IncomingMessage::GetChatHistory { before_id } => {
+				self.addr.do_send(ChatHistoryRequest {
+					session_id: self.id,
+					before_id,
+				});
+			}
+			IncomingMessage::CreateCommentThread { file_id, start, end, content } => {
+				self.addr.do_send(CommentThreadCreationRequest {
+					session_id: self.id,
+					file_id,
+					start,
+					end,
+					content,
+				});
+			}
+			IncomingMessage::AddComment { thread_id, content } => {
+				self.addr.do_send(CommentRequest {
+					session_id: self.id,
+					thread_id,
+					content,
+				});
+			}
+			IncomingMessage::ResolveCommentThread { thread_id, resolved } => {
+				self.addr.do_send(CommentThreadResolutionRequest {
+					session_id: self.id,
+					thread_id,
+					resolved,
+				});
+			}
+			IncomingMessage::SuggestChange { file_id, start, end, lines, revision } => {
+				info!("Session {} editing project {} suggested change in file {}", self.id, self.project_id, file_id);
+				self.addr.do_send(SuggestionRequest {
+					change: FileChange {
+						session_id: self.id,
+						start,
+						end,
+						file_id,
+						lines,
+						revision,
+						client_change_id: None,
 					}
-				}
-				return;
+				});
 			}
-		}
-
-		let last_change_applied_id = match splitted_message[5].parse::<i32>() {
-			Ok(id) => Some(id),
-			Err(_) => None
-		};
-
-		self.addr.do_send(FileChange {
-			session_id: self.id,
-			start,
-			end,
-			file_id,
-			lines: splitted_message[6].split("\n").map(|str_ref| { return str_ref.to_owned(); }).collect(),
-			last_change_applied_id,
-		})
-	}
-
-	/// Tries to convert array of 2 strings into Position.
-	/// # Params
-	///
-	///  * `positions_as_strings` - Position represented as array of strings. First string is row and second is column
-	///
-	/// # Returns
-	///
-	/// Position if both of strings were parsable to u32, Err otherwise
-	fn convert_to_position(&self, positions_as_strings: [&str; 2]) -> Result<Position, ConvertingToPositionError> {
-		let row;
-		match positions_as_strings[0].parse::<u32>() {
-			Ok(n) => row = n,
-			Err(_) => {
-				return Err(ConvertingToPositionError::UnparsableRow);
+			IncomingMessage::ReviewSuggestion { suggestion_id, accept } => {
+				info!("Session {} editing project {} reviewed suggestion {}, accepted: {}", self.id, self.project_id, suggestion_id, accept);
+				self.addr.do_send(SuggestionReviewRequest {
+					session_id: self.id,
+					suggestion_id,
+					accept,
+				});
 			}
-		}
-		return match positions_as_strings[1].parse::<u32>() {
-			Ok(column) => {
-				Ok(Position {
-					row,
-					column,
-				})
+			IncomingMessage::CrdtOperations { file_id, operations } => {
+				self.addr.do_send(CrdtOperationsRequest {
+					session_id: self.id,
+					file_id,
+					operations,
+				});
 			}
-			Err(_) => {
-				return Err(ConvertingToPositionError::UnparsableColumn);
+			IncomingMessage::CreateFolder { name, parent_id } => {
+				info!("Session {} requested new folder {} in folder {:?}", self.id, name, parent_id);
+				self.addr.do_send(FolderCreationRequest {
+					session_id: self.id,
+					name,
+					parent_id,
+				});
 			}
-		};
+			IncomingMessage::RenameFolder { folder_id, name } => {
+				info!("Session {} editing project {} requested renaming folder {} to {}", self.id, self.project_id, folder_id, name);
+				self.addr.do_send(FolderRenameRequest {
+					session_id: self.id,
+					folder_id,
+					new_name: name,
+				});
+			}
+			IncomingMessage::MoveFolder { folder_id, parent_id } => {
+				info!("Session {} editing project {} requested moving folder {} to folder {:?}", self.id, self.project_id, folder_id, parent_id);
+				self.addr.do_send(FolderMoveRequest {
+					session_id: self.id,
+					folder_id,
+					parent_id,
+				});
+			}
+			IncomingMessage::DeleteFolder { folder_id } => {
+				info!("Session {} editing project {} requested deletion of folder {}", self.id, self.project_id, folder_id);
+				self.addr.do_send(FolderDeletionRequest {
+					session_id: self.id,
+					folder_id,
+				});
+			}
+		}
 	}
 
-
 	/// helper method that sends ping to client every second.
 	///
 	/// also this method checks heartbeats from client
@@ -401,9 +821,7 @@ impl  EditorSession {
 			if Instant::now().duration_since(act.hb) > CLIENT_TIMEOUT {
 				// heartbeat timed out
 				info!("Websocket Client heartbeat failed, disconnecting!");
-				// notify chat server
-				act.addr.do_send(Disconnect { session_id: act.id });
-				// stop actor
+				// stop actor, session can be resumed after reconnection
 				ctx.stop();
 				// don't try to send a ping
 				return;
@@ -412,8 +830,3 @@ impl  EditorSession {
 		});
 	}
 }
-
-enum ConvertingToPositionError {
-	UnparsableRow,
-	UnparsableColumn,
-}
@@ -4,4 +4,10 @@ pub mod session_manager;
 pub mod services;
 pub mod models;
 pub mod editor_session;
+pub mod protocol;
 pub mod server;
+pub mod operational_transformation;
+pub mod crdt;
+pub mod document;
+pub mod diff;
+pub mod project_room;
\ No newline at end of file
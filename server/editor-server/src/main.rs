@@ -1,6 +1,9 @@
 use actix_web::{middleware, App, HttpServer};
 use editor_server::controllers::projects;
 use editor_server::controllers::users;
+use editor_server::controllers::folders;
+use editor_server::controllers::files;
+use editor_server::controllers::history;
 use env_logger::Env;
 use actix_cors::Cors;
 use actix_http::cookie::SameSite;
@@ -37,10 +40,31 @@ async fn main() -> std::io::Result<()> {
 			.service(projects::get_my_projects)
 			.service(projects::get_projects_shared_for_me)
 			.service(projects::delete_project)
+			.service(projects::update_project)
 			.service(projects::create_project)
 			.service(projects::grant_access)
 			.service(projects::revoke_access)
 			.service(projects::begin_editor_session)
+			.service(files::get_files)
+			.service(files::get_file)
+			.service(files::create_file)
+			.service(files::update_file)
+			.service(files::delete_file)
+			.service(files::get_file_content)
+			.service(files::write_file_content)
+			.service(history::get_revisions)
+			.service(history::get_content_in_revision)
+			.service(history::get_checkpoints)
+			.service(history::get_checkpoint)
+			.service(history::create_checkpoint)
+			.service(history::restore_checkpoint)
+			.service(history::get_file_diff)
+			.service(history::get_project_diff)
+			.service(history::get_blame)
+			.service(folders::get_tree)
+			.service(folders::create_folder)
+			.service(folders::update_folder)
+			.service(folders::delete_folder)
 			.data(server.clone())
 
 			.service(users::register)
@@ -1,4 +1,5 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use crate::editor_session::Position;
 
 #[derive(Serialize)]
 #[derive(Clone)]
@@ -33,6 +34,37 @@ impl Project {
 	}
 }
 
+/// What user can do in project
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectRole {
+	Owner,
+	Editor,
+	/// Can only suggest changes in files, owner of project accepts or rejects them
+	Suggester,
+}
+
+impl ProjectRole {
+	/// Name of role of user project is shared with, as stored in database
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			ProjectRole::Owner => "owner",
+			ProjectRole::Editor => "editor",
+			ProjectRole::Suggester => "suggester",
+		}
+	}
+
+	pub fn parse(role: &str) -> Option<ProjectRole> {
+		match role {
+			"owner" => Some(ProjectRole::Owner),
+			"editor" => Some(ProjectRole::Editor),
+			"suggester" => Some(ProjectRole::Suggester),
+			_ => None
+		}
+	}
+}
+
 impl PartialEq for Project {
 	fn eq(&self, other: &Self) -> bool {
 		self.id == other.id
@@ -44,6 +76,8 @@ pub struct ProjectFile {
 	pub id: Option<i32>,
 	pub name: String,
 	pub project_id: i32,
+	/// None if file is in root of project
+	pub folder_id: Option<i32>,
 }
 
 impl PartialEq for ProjectFile {
@@ -58,7 +92,213 @@ impl ProjectFile {
 			id: None,
 			name,
 			project_id: project.id.expect("Can't create file for non existing project"),
+			folder_id: None,
 		}
 	}
 }
 
+#[derive(Clone)]
+#[derive(Serialize)]
+pub struct Folder {
+	pub id: Option<i32>,
+	pub name: String,
+	pub project_id: i32,
+	/// None if folder is in root of project
+	pub parent_id: Option<i32>,
+}
+
+impl PartialEq for Folder {
+	fn eq(&self, other: &Self) -> bool {
+		self.id == other.id
+	}
+}
+
+/// Folder or file in tree of project files
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FileTreeNode {
+	Folder {
+		id: i32,
+		name: String,
+		children: Vec<FileTreeNode>,
+	},
+	File {
+		id: i32,
+		name: String,
+	},
+}
+
+impl FileTreeNode {
+	/// Builds tree from folders and files of project. Folders are placed before files and both are
+	/// sorted by name.
+	pub fn build_tree(folders: &[Folder], files: &[ProjectFile]) -> Vec<FileTreeNode> {
+		FileTreeNode::children_of(None, folders, files)
+	}
+
+	fn children_of(folder_id: Option<i32>, folders: &[Folder], files: &[ProjectFile]) -> Vec<FileTreeNode> {
+		let mut child_folders: Vec<&Folder> = folders.iter()
+			.filter(|folder| folder.parent_id == folder_id && folder.id.is_some())
+			.collect();
+		child_folders.sort_by(|a, b| a.name.cmp(&b.name));
+		let mut child_files: Vec<&ProjectFile> = files.iter()
+			.filter(|file| file.folder_id == folder_id && file.id.is_some())
+			.collect();
+		child_files.sort_by(|a, b| a.name.cmp(&b.name));
+
+		let mut nodes: Vec<FileTreeNode> = child_folders.into_iter()
+			.map(|folder| FileTreeNode::Folder {
+				id: folder.id.unwrap(),
+				name: folder.name.clone(),
+				children: FileTreeNode::children_of(folder.id, folders, files),
+			})
+			.collect();
+		nodes.extend(child_files.into_iter()
+			.map(|file| FileTreeNode::File {
+				id: file.id.unwrap(),
+				name: file.name.clone(),
+			}));
+		nodes
+	}
+}
+
+
+/// Message sent to project chat
+#[derive(Clone)]
+#[derive(Serialize)]
+pub struct ChatMessage {
+	pub id: i32,
+	pub author: User,
+	pub content: String,
+	/// Milliseconds since Unix epoch
+	pub sent_at: i64,
+	/// Ids of project members mentioned in message
+	pub mentions: Vec<i32>,
+}
+
+impl ChatMessage {
+	/// Names of users mentioned in content with `@name`
+	pub fn mentioned_names(content: &str) -> Vec<String> {
+		let mut names: Vec<String> = content.split_whitespace()
+			.filter_map(|word| word.strip_prefix('@'))
+			.map(|name| name.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '_' && c != '-'))
+			.filter(|name| !name.is_empty())
+			.map(|name| name.to_owned())
+			.collect();
+		names.sort();
+		names.dedup();
+		names
+	}
+}
+
+/// Comments about range of file
+#[derive(Clone)]
+#[derive(Serialize)]
+pub struct CommentThread {
+	pub id: i32,
+	pub file_id: i32,
+	pub start: Position,
+	pub end: Position,
+	pub resolved: bool,
+	/// Comments from the oldest one, the first one started thread
+	pub comments: Vec<Comment>,
+}
+
+#[derive(Clone)]
+#[derive(Serialize)]
+pub struct Comment {
+	pub id: i32,
+	pub author: User,
+	pub content: String,
+	/// Milliseconds since Unix epoch
+	pub sent_at: i64,
+}
+
+/// Change of range of file suggested by user with suggester role
+#[derive(Clone)]
+#[derive(Serialize)]
+pub struct Suggestion {
+	pub id: i32,
+	pub file_id: i32,
+	pub author: User,
+	pub start: Position,
+	pub end: Position,
+	/// Lines replacing text between start and end
+	pub lines: Vec<String>,
+}
+
+/// Change saved in history of file
+#[derive(Serialize)]
+pub struct FileRevision {
+	pub revision: i32,
+	/// None if change was not made by user
+	pub author: Option<User>,
+	/// Milliseconds since Unix epoch
+	pub made_at: i64,
+}
+
+/// Named state of all files of project
+#[derive(Serialize)]
+pub struct Checkpoint {
+	pub id: i32,
+	pub name: String,
+	pub author: User,
+	/// Milliseconds since Unix epoch
+	pub created_at: i64,
+	pub files: Vec<CheckpointFile>,
+}
+
+/// Revision of file saved in checkpoint
+#[derive(Serialize)]
+pub struct CheckpointFile {
+	pub file_id: i32,
+	pub revision: i32,
+}
+
+/// Differences between two versions of file
+#[derive(Serialize)]
+pub struct FileDiff {
+	pub file_id: i32,
+	pub name: String,
+	/// None if file was not in the older version of project
+	pub from_revision: Option<i32>,
+	/// None if file was not in the newer version of project
+	pub to_revision: Option<i32>,
+	/// Differences in unified diff format
+	pub unified: String,
+	pub hunks: Vec<DiffHunk>,
+}
+
+/// Changed fragment of file with surrounding lines. Numbers of lines start from 1.
+#[derive(Serialize)]
+pub struct DiffHunk {
+	pub old_start: usize,
+	pub old_lines: usize,
+	pub new_start: usize,
+	pub new_lines: usize,
+	pub lines: Vec<DiffLine>,
+}
+
+#[derive(Serialize)]
+pub struct DiffLine {
+	pub kind: DiffLineKind,
+	/// Line without line ending
+	pub content: String,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffLineKind {
+	Context,
+	Added,
+	Removed,
+}
+
+/// Line of file with user who modified it last
+#[derive(Serialize)]
+pub struct BlameLine {
+	pub content: String,
+	/// None if author is unknown or line was changed by server
+	pub author: Option<User>,
+	/// Milliseconds since Unix epoch, None if unknown
+	pub modified_at: Option<i64>,
+}
@@ -0,0 +1,293 @@
+/// Server side operational transformation of changes made in files.
+///
+/// Every change is a replacement of text between `start` and `end` with `lines`. Insertions have
+/// `start == end`, deletions have no text to insert. Changes sent by sessions are made on the
+/// version of file the session has seen, so before applying them they have to be transformed
+/// against every change applied by server since then.
+///
+/// Changes are numbered with revisions of file. Revision of file is number of changes applied to it
+/// since server started tracking it, so first change makes revision 1.
+use std::collections::VecDeque;
+use crate::editor_session::{FileChange, Position};
+
+/// How many applied changes are remembered per file. Sessions that are further behind
+/// have to reload file content.
+const MAX_HISTORY_LENGTH: usize = 1000;
+
+#[derive(Clone)]
+pub struct AppliedChange {
+	/// Revision of file made by this change
+	pub revision: usize,
+	pub session_id: i32,
+	pub start: Position,
+	pub end: Position,
+	pub lines: Vec<String>,
+}
+
+impl AppliedChange {
+	pub fn new(revision: usize, change: &FileChange) -> AppliedChange {
+		AppliedChange {
+			revision,
+			session_id: change.session_id,
+			start: change.start,
+			end: change.end,
+			lines: change.lines.clone(),
+		}
+	}
+
+	/// Position right after text inserted by this change
+	fn inserted_text_end(&self) -> Position {
+		text_end(&self.start, &self.lines)
+	}
+}
+
+/// Position right after text inserted by change
+pub fn inserted_text_end(change: &FileChange) -> Position {
+	text_end(&change.start, &change.lines)
+}
+
+/// Position right after given lines inserted at `start`
+fn text_end(start: &Position, lines: &[String]) -> Position {
+	let last_line_length = lines.last().map(|line| line.chars().count() as u32).unwrap_or(0);
+	if lines.len() <= 1 {
+		Position {
+			row: start.row,
+			column: start.column + last_line_length,
+		}
+	} else {
+		Position {
+			row: start.row + lines.len() as u32 - 1,
+			column: last_line_length,
+		}
+	}
+}
+
+/// Log of changes applied to one file.
+pub struct FileHistory {
+	changes: VecDeque<AppliedChange>,
+	/// Number of changes removed from the beginning of log
+	forgotten_changes: usize,
+}
+
+impl Default for FileHistory {
+	fn default() -> Self {
+		FileHistory::new()
+	}
+}
+
+impl FileHistory {
+	pub fn new() -> FileHistory {
+		FileHistory {
+			changes: VecDeque::new(),
+			forgotten_changes: 0,
+		}
+	}
+
+	/// Number of changes applied to file since server started tracking it
+	pub fn revision(&self) -> usize {
+		self.forgotten_changes + self.changes.len()
+	}
+
+	/// Returns all changes applied after given revision or None if some of them were already forgotten
+	pub fn changes_since(&self, revision: usize) -> Option<Vec<AppliedChange>> {
+		if revision < self.forgotten_changes || revision > self.revision() {
+			return None;
+		}
+		Some(self.changes.iter()
+			.skip(revision - self.forgotten_changes)
+			.cloned()
+			.collect())
+	}
+
+	/// Remembers change as next revision of file and returns this revision
+	pub fn push(&mut self, change: &FileChange) -> usize {
+		let revision = self.revision() + 1;
+		self.changes.push_back(AppliedChange::new(revision, change));
+		while self.changes.len() > MAX_HISTORY_LENGTH {
+			self.changes.pop_front();
+			self.forgotten_changes += 1;
+		}
+		revision
+	}
+}
+
+/// Transforms change so it can be applied after `applied` change, although it was made without
+/// knowledge about it.
+///
+/// When both changes insert text in the same place, text from `applied` ends up first.
+pub fn transform(change: &mut FileChange, applied: &AppliedChange) {
+	let is_insertion = change.start == change.end;
+	change.start = transform_position(&change.start, applied, true);
+	change.end = if is_insertion {
+		change.start
+	} else {
+		transform_position(&change.end, applied, false)
+	};
+}
+
+/// Checks if change modifies the same part of text as `applied` change, although it was made without
+/// knowledge about it. Changes only touching each other don't conflict, unless both insert text in
+/// the same place.
+pub fn conflicts(change: &FileChange, applied: &AppliedChange) -> bool {
+	if change.start == change.end && applied.start == applied.end {
+		return change.start == applied.start;
+	}
+	change.start < applied.end && applied.start < change.end
+}
+
+/// Moves cursor of session so it stays in the same place in text after `applied` change. Cursor
+/// in place where text was inserted stays before it, unless the text was inserted by the session.
+pub fn transform_cursor(cursor: &Position, applied: &AppliedChange, session_id: i32) -> Position {
+	transform_position(cursor, applied, applied.session_id == session_id)
+}
+
+/// Moves range so it covers the same text after `applied` change. Text inserted at the edges of
+/// range becomes part of it.
+pub fn transform_range(start: &Position, end: &Position, applied: &AppliedChange) -> (Position, Position) {
+	(transform_position(start, applied, false), transform_position(end, applied, true))
+}
+
+/// Moves range of text which will be replaced later, so it covers the same text after `applied`
+/// change. Text inserted at the edges of range stays outside of it, so replacing the range does not
+/// remove it. Empty range (insertion) stays empty.
+pub fn transform_replaced_range(start: &Position, end: &Position, applied: &AppliedChange) -> (Position, Position) {
+	let transformed_start = transform_position(start, applied, true);
+	if start == end {
+		return (transformed_start, transformed_start);
+	}
+	(transformed_start, transform_position(end, applied, false))
+}
+
+/// Moves position so it points to the same place in text after `applied` change.
+///
+/// # Params
+///
+/// * `position` - position in text before `applied` change
+/// * `applied` - change that was made
+/// * `move_after_insertion` - whether position should be placed after text inserted exactly in this position
+fn transform_position(position: &Position, applied: &AppliedChange, move_after_insertion: bool) -> Position {
+	let inserted_text_end = applied.inserted_text_end();
+	if *position < applied.start {
+		return *position;
+	}
+	if *position == applied.start && (applied.start != applied.end || !move_after_insertion) {
+		return *position;
+	}
+	if *position < applied.end {
+		// text at this position was deleted
+		return applied.start;
+	}
+	if position.row == applied.end.row {
+		Position {
+			row: inserted_text_end.row,
+			column: inserted_text_end.column + position.column - applied.end.column,
+		}
+	} else {
+		Position {
+			row: position.row - applied.end.row + inserted_text_end.row,
+			column: position.column,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn position(row: u32, column: u32) -> Position {
+		Position { row, column }
+	}
+
+	fn applied(start: Position, end: Position, lines: &[&str]) -> AppliedChange {
+		AppliedChange {
+			revision: 1,
+			session_id: 1,
+			start,
+			end,
+			lines: lines.iter().map(|line| line.to_string()).collect(),
+		}
+	}
+
+	#[test]
+	fn position_before_change_does_not_move() {
+		let change = applied(position(1, 2), position(1, 4), &["abc"]);
+		assert_eq!(transform_position(&position(1, 1), &change, false), position(1, 1));
+		assert_eq!(transform_position(&position(0, 10), &change, true), position(0, 10));
+	}
+
+	#[test]
+	fn position_after_insertion_in_the_same_line_moves_by_inserted_text() {
+		let change = applied(position(0, 2), position(0, 2), &["abc"]);
+		assert_eq!(transform_position(&position(0, 5), &change, false), position(0, 8));
+	}
+
+	#[test]
+	fn position_after_inserted_lines_moves_to_next_rows() {
+		let change = applied(position(0, 2), position(0, 2), &["ab", "cd", "e"]);
+		assert_eq!(transform_position(&position(0, 4), &change, false), position(2, 3));
+		assert_eq!(transform_position(&position(1, 4), &change, false), position(3, 4));
+	}
+
+	#[test]
+	fn position_in_place_of_insertion_moves_only_if_requested() {
+		let change = applied(position(0, 2), position(0, 2), &["abc"]);
+		assert_eq!(transform_position(&position(0, 2), &change, false), position(0, 2));
+		assert_eq!(transform_position(&position(0, 2), &change, true), position(0, 5));
+	}
+
+	#[test]
+	fn position_in_deleted_text_moves_to_start_of_deletion() {
+		let change = applied(position(0, 2), position(2, 1), &[]);
+		assert_eq!(transform_position(&position(1, 5), &change, false), position(0, 2));
+		assert_eq!(transform_position(&position(2, 0), &change, true), position(0, 2));
+	}
+
+	#[test]
+	fn position_after_deleted_lines_moves_up() {
+		let change = applied(position(0, 2), position(2, 1), &[]);
+		assert_eq!(transform_position(&position(2, 4), &change, false), position(0, 5));
+		assert_eq!(transform_position(&position(3, 4), &change, false), position(1, 4));
+	}
+
+	#[test]
+	fn position_after_replacement_moves_by_difference() {
+		let change = applied(position(0, 1), position(0, 4), &["x"]);
+		assert_eq!(transform_position(&position(0, 6), &change, false), position(0, 4));
+	}
+
+	#[test]
+	fn range_grows_with_text_inserted_at_its_edges() {
+		let at_start = applied(position(0, 2), position(0, 2), &["ab"]);
+		assert_eq!(transform_range(&position(0, 2), &position(0, 5), &at_start), (position(0, 2), position(0, 7)));
+		let at_end = applied(position(0, 5), position(0, 5), &["ab"]);
+		assert_eq!(transform_range(&position(0, 2), &position(0, 5), &at_end), (position(0, 2), position(0, 7)));
+	}
+
+	#[test]
+	fn replaced_range_does_not_grow_with_text_inserted_at_its_edges() {
+		let at_start = applied(position(0, 2), position(0, 2), &["ab"]);
+		assert_eq!(transform_replaced_range(&position(0, 2), &position(0, 5), &at_start), (position(0, 4), position(0, 7)));
+		let at_end = applied(position(0, 5), position(0, 5), &["ab"]);
+		assert_eq!(transform_replaced_range(&position(0, 2), &position(0, 5), &at_end), (position(0, 2), position(0, 5)));
+	}
+
+	#[test]
+	fn empty_replaced_range_stays_empty() {
+		let change = applied(position(0, 2), position(0, 2), &["x"]);
+		assert_eq!(transform_replaced_range(&position(0, 2), &position(0, 2), &change), (position(0, 3), position(0, 3)));
+		let before = applied(position(0, 0), position(0, 0), &["a", "b"]);
+		assert_eq!(transform_replaced_range(&position(0, 2), &position(0, 2), &before), (position(1, 3), position(1, 3)));
+	}
+
+	#[test]
+	fn range_containing_deleted_text_shrinks() {
+		let change = applied(position(0, 3), position(0, 5), &[]);
+		assert_eq!(transform_range(&position(0, 1), &position(0, 8), &change), (position(0, 1), position(0, 6)));
+	}
+
+	#[test]
+	fn deleted_range_collapses() {
+		let change = applied(position(0, 1), position(1, 0), &[]);
+		assert_eq!(transform_range(&position(0, 2), &position(0, 4), &change), (position(0, 1), position(0, 1)));
+	}
+}
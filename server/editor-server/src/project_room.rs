@@ -0,0 +1,2035 @@
+/// Actor managing all editor sessions of one project. Rooms are created and stopped by EditorServer.
+use actix::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use crate::editor_session;
+use crate::editor_session::{FileCreationRequest, EditorSession, FileDeletionRequest, FileNameChangeRequest, FileChange, Position, FileMoveRequest,
+							FolderCreationRequest, FolderRenameRequest, FolderMoveRequest, FolderDeletionRequest};
+use crate::models::{User, Project, ProjectFile, Folder, FileTreeNode, CommentThread, Suggestion, ProjectRole};
+use log::{info, error, warn};
+use crate::repositories::{run, DatabaseError};
+use crate::repositories::comments::ICommentsRepository;
+use crate::services::projects::GetError;
+use crate::services::projects_files::{IProjectsFilesService, CreationError, DeletionError, UpdateError, ServiceCreationError, FolderSaveError,
+									   FolderDeletionError};
+use crate::operational_transformation::{FileHistory, AppliedChange, conflicts, inserted_text_end, transform_cursor, transform_range, transform_replaced_range};
+use crate::crdt::{CrdtDocument, Operation, UnknownElement};
+use crate::repositories::file_content_repository::LineAuthor;
+use crate::document::{self, FileDocument, ApplyChange, IntegrateOperations, GetContent, GetCrdtState, GetLineAuthors, GetChecksum, Close, Discard, Flush, end_position};
+use crate::server::{SessionJoined, SessionLeft, ErrorMessage, CloseSession, ProjectUpdated, ProjectDeleted, DocumentModel, FileCreated, FileDeleted, FileRenamed, FileMoved,
+					FileContent, CrdtState, ReadFileContent, ReadLineAuthors, WriteFileContent, FlushDocuments, FolderChanged, FolderDeleted, CrdtOperationsApplied, ProjectInfoDto, SessionDataDto, ChangeInFile,
+					FileChecksum, Event, ProjectEvent, OfflineChangesResult, ChangeConflict, SessionPresence, ActiveFileChanged,
+					FollowChanged, LeaderFileChanged, ChatHistory, CommentThreads, Suggestions, SuggestionReviewed};
+
+/// Session id of changes which were not made by any editor session (e.g. made through REST API)
+pub(crate) const SERVER_SESSION_ID: i32 = 0;
+/// Error sent to sessions of users with suggester role who try to change files
+const NOT_ALLOWED_MESSAGE: &str = "You can only suggest changes in this project";
+/// How often sessions get checksums of files changed since last checksum
+const CHECKSUM_INTERVAL: Duration = Duration::from_secs(10);
+/// How many events are remembered for sessions which resume after losing connection
+const MAX_REMEMBERED_EVENTS: usize = 1000;
+/// How many chat messages are sent at once
+const CHAT_HISTORY_LENGTH: i64 = 50;
+/// Maximal number of characters in chat message
+const MAX_CHAT_MESSAGE_LENGTH: usize = 2000;
+/// How many chat messages session can send in CHAT_RATE_LIMIT_INTERVAL
+const CHAT_RATE_LIMIT: usize = 5;
+const CHAT_RATE_LIMIT_INTERVAL: Duration = Duration::from_secs(10);
+/// Maximal number of characters in comment
+const MAX_COMMENT_LENGTH: usize = 5000;
+/// How often ranges of comment threads and suggestions moved by changes in files are saved
+const RANGES_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Adds session to room
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Join {
+	pub session_id: i32,
+	pub addr: Addr<EditorSession>,
+	pub user: User,
+}
+
+/// Attaches new connection to session which lost its previous connection and sends it events it missed.
+///
+/// # Returns
+///
+/// false if session is not in room or some of missed events were already forgotten
+#[derive(Message)]
+#[rtype(result = "bool")]
+pub struct Resume {
+	pub session_id: i32,
+	pub addr: Addr<EditorSession>,
+	/// Id of last event received by session, None if it did not receive any
+	pub last_event_id: Option<u64>,
+}
+
+/// Result of merging change made by session into file
+enum MergeResult {
+	/// Change was transformed against changes unseen by session and applied as given revision
+	Applied {
+		revision: usize,
+		/// Change after transformation
+		start: Position,
+		end: Position,
+		/// Sessions which made unseen changes in the same part of file
+		conflicting_sessions: Vec<i32>,
+	},
+	/// Change with the same client id was already applied
+	AlreadyApplied,
+	/// Change was made on revision which is already forgotten
+	Outdated,
+	UnknownSession,
+}
+
+/// Removes session from room
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Leave {
+	pub session_id: i32
+}
+
+/// Closes all documents of project and stops room
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CloseRoom;
+
+struct SessionData {
+	pub id: i32,
+	pub user: User,
+	pub recipient: Addr<EditorSession>,
+	/// Revisions of files at the moment their content was sent to this session. Keys are file ids.
+	pub content_revisions: HashMap<i32, usize>,
+	/// Id of last event sent before session joined
+	pub joined_after_event: u64,
+	/// Id given by client to last change applied from this session. Client numbers changes in
+	/// increasing order, so changes sent again after resuming session are applied only once.
+	pub last_client_change_id: Option<u32>,
+	/// Last presence sent by session
+	pub presence: Option<SessionPresence>,
+	/// File whose content was last requested by session
+	pub active_file_id: Option<i32>,
+	/// Session followed by this one
+	pub leader_id: Option<i32>,
+	/// When session sent its last chat messages, at most CHAT_RATE_LIMIT of them
+	pub chat_messages_sent_at: VecDeque<Instant>,
+	pub role: ProjectRole,
+}
+
+impl SessionData {
+	/// Presence of session as seen by other session. Viewport is sent only to followers.
+	fn presence_for(&self, session: &SessionData) -> Option<SessionPresence> {
+		let mut presence = self.presence.clone()?;
+		if session.leader_id != Some(self.id) {
+			presence.viewport = None;
+		}
+		Some(presence)
+	}
+}
+
+pub struct ProjectRoom {
+	project_id: i32,
+	sessions: HashMap<i32, SessionData>,
+	/// Ids of files of project, documents are opened only for them
+	files: HashSet<i32>,
+	/// Changes applied to edited files. Keys are file ids.
+	files_histories: HashMap<i32, FileHistory>,
+	document_model: DocumentModel,
+	/// Opened documents. Keys are file ids.
+	documents: HashMap<i32, Addr<FileDocument>>,
+	/// Last checksums of opened files with revisions they were computed in. Keys are file ids.
+	checksums: HashMap<i32, (usize, String)>,
+	/// Recently sent events
+	events: VecDeque<Event>,
+	last_event_id: u64,
+	/// Comment threads in files of project. Keys are thread ids.
+	comment_threads: HashMap<i32, CommentThread>,
+	/// Threads whose ranges were moved by changes since they were saved
+	moved_comment_threads: HashSet<i32>,
+	/// Suggested changes waiting for review of project owner. Keys are suggestion ids.
+	suggestions: HashMap<i32, Suggestion>,
+	/// Suggestions whose ranges were moved by changes since they were saved
+	moved_suggestions: HashSet<i32>,
+}
+
+impl ProjectRoom {
+	pub fn new(project_id: i32, document_model: DocumentModel) -> ProjectRoom {
+		ProjectRoom {
+			project_id,
+			sessions: HashMap::new(),
+			files: HashSet::new(),
+			files_histories: HashMap::new(),
+			document_model,
+			documents: HashMap::new(),
+			checksums: HashMap::new(),
+			events: VecDeque::new(),
+			last_event_id: 0,
+			comment_threads: HashMap::new(),
+			moved_comment_threads: HashSet::new(),
+			suggestions: HashMap::new(),
+			moved_suggestions: HashSet::new(),
+		}
+	}
+
+	/// Numbers event, sends it to all sessions in room and remembers it for sessions which lost connection
+	fn broadcast(&mut self, event: ProjectEvent) {
+		self.last_event_id += 1;
+		let event = Event {
+			id: self.last_event_id,
+			event,
+		};
+		self.sessions
+			.values()
+			.for_each(|session| { session.recipient.do_send(event.clone()) });
+		self.events.push_back(event);
+		while self.events.len() > MAX_REMEMBERED_EVENTS {
+			self.events.pop_front();
+		}
+	}
+
+	/// Sends ProjectInfoDto with given project and tree of its files to actor
+	fn send_project_info(&self, addr: &Addr<EditorSession>, session_data: &SessionData, project: Project, files: Vec<FileTreeNode>) {
+		let mut sessions: Vec<SessionDataDto> = self.sessions.values()
+			.map(|full_session_data| {
+				return SessionDataDto {
+					id: full_session_data.id,
+					name: full_session_data.user.name.clone(),
+					active_file_id: full_session_data.active_file_id,
+					leader_id: full_session_data.leader_id,
+					role: full_session_data.role,
+					presence: full_session_data.presence_for(session_data),
+				};
+			}).collect();
+		sessions.push(SessionDataDto {
+			id: session_data.id,
+			name: session_data.user.name.clone(),
+			active_file_id: None,
+			leader_id: None,
+			role: session_data.role,
+			presence: None,
+		});
+		addr.do_send(ProjectInfoDto {
+			project,
+			files,
+			sessions,
+		});
+	}
+
+	/// Sends error message to given actor
+	///
+	fn send_error(&self, addr: &Addr<EditorSession>, msg: String) {
+		addr.do_send(ErrorMessage { msg });
+	}
+
+	fn send_folder_save_error(&self, addr: &Addr<EditorSession>, err: FolderSaveError) {
+		match err {
+			FolderSaveError::NotAllowed => self.send_error(addr, NOT_ALLOWED_MESSAGE.to_owned()),
+			FolderSaveError::IllegalName => self.send_error(addr, "Illegal folder name".to_owned()),
+			FolderSaveError::DuplicateName => self.send_error(addr, "File or folder with that name already exists".to_owned()),
+			FolderSaveError::FolderDoesNotExist => self.send_error(addr, "Folder does not exist".to_owned()),
+			FolderSaveError::IllegalParent => self.send_error(addr, "Folder can't be placed there".to_owned()),
+			FolderSaveError::DatabaseError(err) => {
+				error!("Unknown error occured while saving folder: {}", err);
+				self.send_error(addr, "Unknown error occured while saving folder".to_owned());
+			}
+		}
+	}
+
+	/// Runs operation with projects files service of session user in database threads and handles
+	/// its result in room. Session is notified if it can't access project.
+	fn run_with_files_service<T, F, H>(&self, session_id: i32, ctx: &mut Context<Self>, operation: F, handle_result: H)
+		where F: FnOnce(&dyn IProjectsFilesService) -> T + Send + 'static,
+			  T: Send + 'static,
+			  H: FnOnce(&mut ProjectRoom, T, &Addr<EditorSession>, &mut Context<Self>) + 'static {
+		let (user_id, recipient);
+		match self.sessions.get(&session_id) {
+			Some(data) => {
+				user_id = data.user.id;
+				recipient = data.recipient.clone();
+			}
+			None => {
+				error!("Not registered session {} sent request", session_id);
+				return;
+			}
+		}
+		let project_id = self.project_id;
+		// Waiting, so changes of project structure are applied in order they were requested
+		run(move || {
+			let projects_files_service = crate::services::projects_files::new(user_id, project_id)?;
+			Ok(operation(projects_files_service.as_ref()))
+		})
+			.into_actor(self)
+			.then(move |res: Result<T, ServiceCreationError>, act, ctx| {
+				match res {
+					Ok(result) => handle_result(act, result, &recipient, ctx),
+					Err(ServiceCreationError::DatabaseError(err)) => {
+						error!("Failed to check access of user {} to project {}: {}", user_id, project_id, err);
+						act.send_error(&recipient, "Database is not available, try again later".to_owned());
+					}
+					Err(_) => act.send_error(&recipient, "You don't have access to this project".to_owned())
+				}
+				fut::ready(())
+			})
+			.wait(ctx);
+	}
+
+	/// Loads folder, applies update to it and saves it
+	fn update_folder<U>(&self, session_id: i32, folder_id: i32, ctx: &mut Context<Self>, update: U)
+		where U: FnOnce(&mut Folder) + Send + 'static {
+		self.run_with_files_service(session_id, ctx, move |service| {
+			match service.get_folder(folder_id)? {
+				Some(mut folder) => {
+					update(&mut folder);
+					service.update_folder(folder)
+				}
+				None => Err(FolderSaveError::FolderDoesNotExist)
+			}
+		}, move |act, res, recipient, ctx| {
+			match res {
+				Ok(folder) => {
+					info!("Session {} updated folder {} in project {}", session_id, folder_id, act.project_id);
+					act.handle(FolderChanged { folder }, ctx);
+				}
+				Err(err) => act.send_folder_save_error(recipient, err)
+			}
+		});
+	}
+
+	/// Sends current content of file to session and remembers which revision of file this session has.
+	fn send_file_content(&mut self, session_id: i32, file_id: i32, ctx: &mut Context<Self>) {
+		if !self.check_file(session_id, file_id) {
+			return;
+		}
+		let revision = self.revision(file_id);
+		let recipient;
+		match self.sessions.get_mut(&session_id) {
+			Some(session_data) => {
+				session_data.content_revisions.insert(file_id, revision);
+				recipient = session_data.recipient.clone();
+			}
+			None => {
+				error!("Inactive session (or at least not registered in sessions registry) send \"get content\" package");
+				return;
+			}
+		}
+		let document;
+		match self.document(file_id) {
+			Some(addr) => document = addr,
+			None => return
+		}
+		info!("Sending file {} contents to session {}", file_id, session_id);
+		// Document handles messages in order, so content and CRDT state will contain all changes up to remembered revision
+		let content = document.send(GetContent);
+		let crdt_state = match self.document_model {
+			DocumentModel::Crdt => Some(document.send(GetCrdtState)),
+			DocumentModel::Lines => None
+		};
+		async move {
+			let content = content.await?;
+			let crdt_state = match crdt_state {
+				Some(request) => request.await?,
+				None => None
+			};
+			Ok((content, crdt_state))
+		}
+			.into_actor(self)
+			.then(move |res: Result<(String, Option<CrdtDocument>), MailboxError>, _, _| {
+				match res {
+					Ok((content, crdt_state)) => {
+						recipient.do_send(FileContent {
+							file_id,
+							content,
+							revision,
+						});
+						if let Some(document) = crdt_state {
+							recipient.do_send(CrdtState {
+								file_id,
+								document,
+								revision,
+							});
+						}
+					}
+					Err(err) => {
+						error!("Failed to get content of file {}: {}", file_id, err);
+						recipient.do_send(ErrorMessage { msg: "Failed to load file, try again later".to_owned() });
+					}
+				}
+				fut::ready(())
+			})
+			.spawn(ctx);
+	}
+
+	/// Remembers file opened by session and notifies sessions if it is other than before
+	fn set_active_file(&mut self, session_id: i32, file_id: Option<i32>) {
+		match self.sessions.get_mut(&session_id) {
+			Some(session_data) if session_data.active_file_id != file_id => session_data.active_file_id = file_id,
+			_ => return
+		}
+		self.broadcast(ProjectEvent::ActiveFileChanged(ActiveFileChanged {
+			session_id,
+			file_id,
+		}));
+		self.sessions.values()
+			.filter(|session| session.leader_id == Some(session_id))
+			.for_each(|follower| follower.recipient.do_send(LeaderFileChanged {
+				leader_id: session_id,
+				file_id,
+			}));
+	}
+
+	/// Remembers session followed by other session and notifies sessions about it
+	fn set_leader(&mut self, session_id: i32, leader_id: Option<i32>) {
+		match self.sessions.get_mut(&session_id) {
+			Some(session_data) => session_data.leader_id = leader_id,
+			None => return
+		}
+		self.broadcast(ProjectEvent::FollowChanged(FollowChanged {
+			session_id,
+			leader_id,
+		}));
+	}
+
+	/// Sends chat messages older than given one, or the last ones, to session
+	fn send_chat_history(&mut self, session_id: i32, before_id: Option<i32>, ctx: &mut Context<Self>) {
+		let recipient;
+		match self.sessions.get(&session_id) {
+			Some(session_data) => recipient = session_data.recipient.clone(),
+			None => {
+				error!("Not registered session requested chat history");
+				return;
+			}
+		}
+		let project_id = self.project_id;
+		run(move || crate::repositories::chat_messages::new(project_id).get_last(before_id, CHAT_HISTORY_LENGTH))
+			.into_actor(self)
+			.then(move |res, act, _| {
+				match res {
+					Ok(messages) => recipient.do_send(ChatHistory { messages }),
+					Err(err) => {
+						error!("Failed to get chat history of project {}: {}", project_id, err);
+						act.send_error(&recipient, "Failed to load chat messages, try again later".to_owned());
+					}
+				}
+				fut::ready(())
+			})
+			.spawn(ctx);
+	}
+
+	/// Current revision of file
+	fn revision(&self, file_id: i32) -> usize {
+		self.files_histories.get(&file_id)
+			.map(|history| history.revision())
+			.unwrap_or(0)
+	}
+
+	/// Computes checksum of current content of file and remembers it
+	fn compute_checksum<H>(&mut self, file_id: i32, ctx: &mut Context<Self>, handle_checksum: H)
+		where H: FnOnce(&mut Self, usize, &str, &mut Context<Self>) + 'static
+	{
+		let revision = self.revision(file_id);
+		let document;
+		match self.document(file_id) {
+			Some(addr) => document = addr,
+			None => return
+		}
+		// Document handles messages in order, so checksum is computed for content in remembered revision
+		document
+			.send(GetChecksum)
+			.into_actor(self)
+			.then(move |res, act, ctx| {
+				match res {
+					Ok(checksum) => {
+						handle_checksum(act, revision, &checksum, ctx);
+						act.checksums.insert(file_id, (revision, checksum));
+					}
+					Err(err) => error!("Failed to compute checksum of file {}: {}", file_id, err)
+				}
+				fut::ready(())
+			})
+			.spawn(ctx);
+	}
+
+	/// Sends checksums of opened files changed since their last checksum to sessions editing them
+	fn send_checksums(&mut self, ctx: &mut Context<Self>) {
+		let changed_files: Vec<i32> = self.documents.iter()
+			.filter(|(_, document)| document.connected())
+			.map(|(file_id, _)| *file_id)
+			.filter(|file_id| self.checksums.get(file_id).map(|(revision, _)| *revision) != Some(self.revision(*file_id)))
+			.collect();
+		for file_id in changed_files {
+			self.compute_checksum(file_id, ctx, move |act, revision, checksum, _| {
+				let message = FileChecksum {
+					file_id,
+					revision,
+					checksum: checksum.to_owned(),
+				};
+				act.sessions.values()
+					.filter(|session| session.content_revisions.contains_key(&file_id))
+					.for_each(|session| session.recipient.do_send(message.clone()));
+			});
+		}
+	}
+
+	/// Sends content of file again to session if its content differs from content on server
+	fn verify_checksum(&mut self, session_id: i32, file_id: i32, checksum: &str, session_checksum: &str, ctx: &mut Context<Self>) {
+		if checksum != session_checksum {
+			warn!("Content of file {} diverged in session {}, sending it again", file_id, session_id);
+			self.send_file_content(session_id, file_id, ctx);
+		}
+	}
+
+	/// Returns document of file, loading it if it is not opened yet or its loading failed. Returns
+	/// None if file does not belong to project.
+	fn document(&mut self, file_id: i32) -> Option<Addr<FileDocument>> {
+		if !self.files.contains(&file_id) {
+			warn!("File {} does not exist in project {}, its document is not opened", file_id, self.project_id);
+			return None;
+		}
+		if let Some(document) = self.documents.get(&file_id) {
+			if document.connected() {
+				return Some(document.clone());
+			}
+		}
+		let document = document::open(file_id, self.document_model);
+		self.documents.insert(file_id, document.clone());
+		Some(document)
+	}
+
+	/// Checks if file belongs to project and sends error to session if it does not
+	fn check_file(&self, session_id: i32, file_id: i32) -> bool {
+		if self.files.contains(&file_id) {
+			return true;
+		}
+		warn!("Session {} editing project {} referred to file {} from outside of project", session_id, self.project_id, file_id);
+		if let Some(session_data) = self.sessions.get(&session_id) {
+			self.send_error(&session_data.recipient, "File does not exist".to_owned());
+		}
+		false
+	}
+
+	/// Closes documents of files which are not opened by any session
+	fn close_unused_documents(&mut self) {
+		let sessions = &self.sessions;
+		let unused_files: Vec<i32> = self.documents.keys()
+			.filter(|file_id| !sessions.values().any(|session| session.content_revisions.contains_key(file_id)))
+			.cloned()
+			.collect();
+		for file_id in unused_files {
+			self.checksums.remove(&file_id);
+			if let Some(document) = self.documents.remove(&file_id) {
+				info!("Closing file {}, nobody edits it", file_id);
+				document.do_send(Close);
+			}
+		}
+	}
+
+	/// Remembers change in file history as next revision of file and sends it to all sessions editing
+	/// project. Session which made the change gets only acknowledgement with its revision.
+	fn broadcast_change(&mut self, change: &FileChange) -> usize {
+		let revision = self.files_histories.entry(change.file_id)
+			.or_default()
+			.push(change);
+		let message = ChangeInFile {
+			file_id: change.file_id,
+			start_row: change.start.row,
+			start_column: change.start.column,
+			end_row: change.end.row,
+			end_column: change.end.column,
+			revision,
+			change: change.lines.join("\n"),
+			session_id: change.session_id,
+			client_change_id: change.client_change_id,
+		};
+		self.broadcast(ProjectEvent::ChangeInFile(message));
+		let applied = AppliedChange::new(revision, change);
+		self.transform_presences(&applied, change.file_id);
+		self.transform_comment_threads(&applied, change.file_id);
+		self.transform_suggestions(&applied, change.file_id);
+		revision
+	}
+
+	/// Moves ranges of comment threads in file, so they cover the same text after change
+	fn transform_comment_threads(&mut self, applied: &AppliedChange, file_id: i32) {
+		for thread in self.comment_threads.values_mut().filter(|thread| thread.file_id == file_id) {
+			let (start, end) = transform_range(&thread.start, &thread.end, applied);
+			if start != thread.start || end != thread.end {
+				thread.start = start;
+				thread.end = end;
+				self.moved_comment_threads.insert(thread.id);
+			}
+		}
+	}
+
+	/// Moves ranges of suggestions in file, so they replace the same text after change
+	fn transform_suggestions(&mut self, applied: &AppliedChange, file_id: i32) {
+		for suggestion in self.suggestions.values_mut().filter(|suggestion| suggestion.file_id == file_id) {
+			// Text typed at edges of suggestion is not replaced when suggestion is accepted
+			let (start, end) = transform_replaced_range(&suggestion.start, &suggestion.end, applied);
+			if start != suggestion.start || end != suggestion.end {
+				suggestion.start = start;
+				suggestion.end = end;
+				self.moved_suggestions.insert(suggestion.id);
+			}
+		}
+	}
+
+	/// Saves ranges of comment threads and suggestions moved since last save
+	fn save_moved_ranges(&mut self) {
+		let project_id = self.project_id;
+		let comment_threads = &self.comment_threads;
+		let thread_ranges: Vec<(i32, Position, Position)> = self.moved_comment_threads.drain()
+			.filter_map(|thread_id| comment_threads.get(&thread_id))
+			.map(|thread| (thread.id, thread.start, thread.end))
+			.collect();
+		if !thread_ranges.is_empty() {
+			actix::spawn(async move {
+				let result: Result<(), DatabaseError> = run(move || crate::repositories::comments::new(project_id).update_ranges(&thread_ranges)).await;
+				if let Err(err) = result {
+					error!("Failed to save ranges of comment threads in project {}: {}", project_id, err);
+				}
+			});
+		}
+		let suggestions = &self.suggestions;
+		let suggestion_ranges: Vec<(i32, Position, Position)> = self.moved_suggestions.drain()
+			.filter_map(|suggestion_id| suggestions.get(&suggestion_id))
+			.map(|suggestion| (suggestion.id, suggestion.start, suggestion.end))
+			.collect();
+		if !suggestion_ranges.is_empty() {
+			actix::spawn(async move {
+				let result: Result<(), DatabaseError> = run(move || crate::repositories::suggestions::new(project_id).update_ranges(&suggestion_ranges)).await;
+				if let Err(err) = result {
+					error!("Failed to save ranges of suggestions in project {}: {}", project_id, err);
+				}
+			});
+		}
+	}
+
+	/// Checks if session can change files directly. Sessions of users with suggester role get error.
+	fn can_edit(&self, session_id: i32) -> bool {
+		match self.sessions.get(&session_id) {
+			Some(session_data) if session_data.role == ProjectRole::Suggester => {
+				self.send_error(&session_data.recipient, NOT_ALLOWED_MESSAGE.to_owned());
+				false
+			}
+			_ => true
+		}
+	}
+
+	/// Applies accepted suggestion as change made outside of editor sessions
+	fn apply_suggestion(&mut self, suggestion: Suggestion, ctx: &mut Context<Self>) {
+		let change = FileChange {
+			session_id: SERVER_SESSION_ID,
+			start: suggestion.start,
+			end: suggestion.end,
+			file_id: suggestion.file_id,
+			lines: suggestion.lines,
+			revision: None,
+			client_change_id: None,
+		};
+		self.broadcast_change(&change);
+		self.apply_change(change, Some(suggestion.author.id), ctx);
+		// Suggestions can change files which nobody edits
+		self.close_unused_documents();
+	}
+
+	/// Runs operation with comments repository of project in database threads and handles its
+	/// result in room. Operation gets user of session.
+	fn run_with_comments_repository<T, F, H>(&self, session_id: i32, ctx: &mut Context<Self>, operation: F, handle_result: H)
+		where F: FnOnce(&dyn ICommentsRepository, &User) -> Result<T, DatabaseError> + Send + 'static,
+			  T: Send + 'static,
+			  H: FnOnce(&mut ProjectRoom, T, &Addr<EditorSession>) + 'static {
+		let (user, recipient);
+		match self.sessions.get(&session_id) {
+			Some(data) => {
+				user = data.user.clone();
+				recipient = data.recipient.clone();
+			}
+			None => {
+				error!("Not registered session {} sent request", session_id);
+				return;
+			}
+		}
+		let project_id = self.project_id;
+		// Waiting, so changes applied later move ranges of created threads
+		run(move || operation(crate::repositories::comments::new(project_id).as_ref(), &user))
+			.into_actor(self)
+			.then(move |res, act, _| {
+				match res {
+					Ok(result) => handle_result(act, result, &recipient),
+					Err(err) => {
+						error!("Failed to save comment in project {}: {}", project_id, err);
+						act.send_error(&recipient, "Failed to save comment, try again later".to_owned());
+					}
+				}
+				fut::ready(())
+			})
+			.wait(ctx);
+	}
+
+	/// Returns trimmed content of comment or sends error to session if it is empty or too long
+	fn validate_comment(&self, recipient: &Addr<EditorSession>, content: &str) -> Option<String> {
+		let content = content.trim();
+		if content.is_empty() || content.chars().count() > MAX_COMMENT_LENGTH {
+			self.send_error(recipient, format!("Comment must have from 1 to {} characters", MAX_COMMENT_LENGTH));
+			return None;
+		}
+		Some(content.to_owned())
+	}
+
+	/// Remembers changed comment thread and sends it to all sessions
+	fn broadcast_comment_thread(&mut self, thread: CommentThread) {
+		self.comment_threads.insert(thread.id, thread.clone());
+		self.broadcast(ProjectEvent::CommentThreadChanged(thread));
+	}
+
+	/// Moves cursors and selections of sessions in file, so they point to the same text after change
+	fn transform_presences(&mut self, applied: &AppliedChange, file_id: i32) {
+		let presences = self.sessions.values_mut()
+			.filter_map(|session| session.presence.as_mut())
+			.filter(|presence| presence.file_id == Some(file_id));
+		for presence in presences {
+			let session_id = presence.session_id;
+			presence.cursor = presence.cursor.map(|cursor| transform_cursor(&cursor, applied, session_id));
+			for selection in presence.selections.iter_mut() {
+				selection.start = transform_cursor(&selection.start, applied, session_id);
+				selection.end = transform_cursor(&selection.end, applied, session_id);
+			}
+		}
+	}
+
+	/// Sends presences of all other sessions to session
+	fn send_presences(&self, session_id: i32) {
+		let session_data;
+		match self.sessions.get(&session_id) {
+			Some(data) => session_data = data,
+			None => return
+		}
+		self.sessions.values()
+			.filter(|session| session.id != session_id)
+			.filter_map(|session| session.presence_for(session_data))
+			.for_each(|presence| session_data.recipient.do_send(presence));
+	}
+
+	/// Transforms change made by session against changes in file it has not seen yet.
+	///
+	/// Changes made by the same session are skipped, because session made this change after them.
+	/// That is correct only if those changes were applied before changes of other sessions unseen
+	/// by this session, so they were not transformed themselves. Sessions keep it by sending next
+	/// change after the previous one was acknowledged, changes breaking it are rejected.
+	///
+	/// # Returns
+	///
+	/// Sessions which made unseen changes in the same part of file, None if change was made on
+	/// already forgotten revision or after own change which was transformed
+	fn transform_unseen_changes(&mut self, change: &mut FileChange) -> Option<Vec<i32>> {
+		let history = self.files_histories.entry(change.file_id).or_default();
+		// Revision sent by client could be from other file if client switched files recently,
+		// but session has seen at least revision of file it got with file content.
+		let content_revision = self.sessions.get(&change.session_id)
+			.and_then(|session_data| session_data.content_revisions.get(&change.file_id).cloned());
+		let seen_revision = match change.revision.filter(|revision| *revision <= history.revision()) {
+			Some(revision) => revision.max(content_revision.unwrap_or(0)),
+			None => content_revision.unwrap_or_else(|| history.revision())
+		};
+		let mut conflicting_sessions = Vec::new();
+		match history.changes_since(seen_revision) {
+			Some(unseen_changes) => {
+				let sender_id = change.session_id;
+				let mut other_sessions_changed = false;
+				for applied in unseen_changes.iter() {
+					if applied.session_id == sender_id {
+						if other_sessions_changed {
+							warn!("Session {} sent change before its change {} transformed by server was acknowledged", sender_id, applied.revision);
+							return None;
+						}
+						continue;
+					}
+					other_sessions_changed = true;
+					if conflicts(change, applied) && !conflicting_sessions.contains(&applied.session_id) {
+						conflicting_sessions.push(applied.session_id);
+					}
+					crate::operational_transformation::transform(change, applied);
+				}
+				Some(conflicting_sessions)
+			}
+			None => {
+				warn!("Session {} sent change based on forgotten revision {} of file {}", change.session_id, seen_revision, change.file_id);
+				None
+			}
+		}
+	}
+
+	/// Transforms change made by session against changes it has not seen yet, then broadcasts and
+	/// applies it.
+	fn merge_change(&mut self, mut change: FileChange, ctx: &mut Context<Self>) -> MergeResult {
+		match self.sessions.get(&change.session_id) {
+			Some(session_data) => {
+				if let (Some(client_change_id), Some(last_client_change_id)) = (change.client_change_id, session_data.last_client_change_id) {
+					if client_change_id <= last_client_change_id {
+						info!("Session {} sent change {} again, it was already applied", change.session_id, client_change_id);
+						return MergeResult::AlreadyApplied;
+					}
+				}
+			}
+			None => return MergeResult::UnknownSession
+		}
+		let conflicting_sessions;
+		match self.transform_unseen_changes(&mut change) {
+			Some(sessions) => conflicting_sessions = sessions,
+			None => return MergeResult::Outdated
+		}
+		if change.client_change_id.is_some() {
+			if let Some(session_data) = self.sessions.get_mut(&change.session_id) {
+				session_data.last_client_change_id = change.client_change_id;
+			}
+		}
+		let revision = self.broadcast_change(&change);
+		let start = change.start;
+		let end = inserted_text_end(&change);
+		let author_id = self.sessions.get(&change.session_id).map(|session_data| session_data.user.id);
+		self.apply_change(change, author_id, ctx);
+		MergeResult::Applied {
+			revision,
+			start,
+			end,
+			conflicting_sessions,
+		}
+	}
+
+	/// Checks if changes made since given revision of file are still remembered
+	fn remembers_revision(&self, file_id: i32, revision: usize) -> bool {
+		match self.files_histories.get(&file_id) {
+			Some(history) => history.changes_since(revision).is_some(),
+			None => revision == 0
+		}
+	}
+
+	/// Merges changes made by session while it was offline one after another. Every change is made
+	/// on the previous one, so all of them are based on the same revision of changes of other sessions.
+	/// Batch of more changes is rejected if other sessions changed file since that revision, clients
+	/// send changes made offline as one change.
+	fn merge_offline_changes(&mut self, session_id: i32, file_id: i32, base_revision: usize, changes: Vec<FileChange>, ctx: &mut Context<Self>) {
+		let mut conflicts = Vec::new();
+		for mut change in changes {
+			change.revision = Some(base_revision);
+			let client_change_id = change.client_change_id;
+			match self.merge_change(change, ctx) {
+				MergeResult::Applied { revision, start, end, conflicting_sessions } => {
+					if !conflicting_sessions.is_empty() {
+						conflicts.push(ChangeConflict {
+							client_change_id,
+							revision,
+							start,
+							end,
+							session_ids: conflicting_sessions,
+						});
+					}
+				}
+				MergeResult::AlreadyApplied => {}
+				MergeResult::Outdated | MergeResult::UnknownSession => {
+					self.reject_offline_changes(session_id, file_id, ctx);
+					return;
+				}
+			}
+		}
+		info!("Merged offline changes of session {} in file {}, {} of them conflicted", session_id, file_id, conflicts.len());
+		if let Some(session_data) = self.sessions.get(&session_id) {
+			session_data.recipient.do_send(OfflineChangesResult {
+				file_id,
+				merged: true,
+				conflicts,
+			});
+		}
+	}
+
+	/// Informs session that its offline changes could not be merged and sends it current content of file
+	fn reject_offline_changes(&mut self, session_id: i32, file_id: i32, ctx: &mut Context<Self>) {
+		warn!("Offline changes of session {} in file {} could not be merged", session_id, file_id);
+		if let Some(session_data) = self.sessions.get(&session_id) {
+			session_data.recipient.do_send(OfflineChangesResult {
+				file_id,
+				merged: false,
+				conflicts: Vec::new(),
+			});
+		}
+		self.send_file_content(session_id, file_id, ctx);
+	}
+
+	fn broadcast_crdt_operations(&mut self, file_id: i32, operations: Vec<Operation>) {
+		self.broadcast(ProjectEvent::CrdtOperations(CrdtOperationsApplied {
+			file_id,
+			operations,
+		}));
+	}
+
+	/// Applies change made by author to document of file. With CRDT model, resulting operations are
+	/// sent to all sessions editing project.
+	fn apply_change(&mut self, change: FileChange, author_id: Option<i32>, ctx: &mut Context<Self>) {
+		let file_id = change.file_id;
+		let document;
+		match self.document(file_id) {
+			Some(addr) => document = addr,
+			None => return
+		}
+		match self.document_model {
+			DocumentModel::Lines => document.do_send(ApplyChange { change, author_id }),
+			DocumentModel::Crdt => {
+				document.send(ApplyChange { change, author_id })
+					.into_actor(self)
+					.then(move |res, act, _| {
+						match res {
+							Ok(operations) => act.broadcast_crdt_operations(file_id, operations),
+							Err(err) => error!("Failed to apply change in file {}: {}", file_id, err)
+						}
+						fut::ready(())
+					})
+					.wait(ctx);
+			}
+		}
+	}
+}
+
+impl Actor for ProjectRoom {
+	type Context = Context<Self>;
+
+	fn started(&mut self, ctx: &mut Self::Context) {
+		info!("Opened room of project {}", self.project_id);
+		ctx.run_interval(CHECKSUM_INTERVAL, |act, ctx| act.send_checksums(ctx));
+		ctx.run_interval(RANGES_SAVE_INTERVAL, |act, _| act.save_moved_ranges());
+		let project_id = self.project_id;
+		// Waiting, so files are known and ranges of all threads and suggestions are moved by changes applied in room
+		run(move || -> Result<_, DatabaseError> {
+			let files = match crate::repositories::projects::new().get(project_id)? {
+				Some(project) => crate::repositories::projects_files::new(project).get_all()?,
+				None => Vec::new()
+			};
+			let threads = crate::repositories::comments::new(project_id).get_threads()?;
+			let suggestions = crate::repositories::suggestions::new(project_id).get_all()?;
+			Ok((files, threads, suggestions))
+		})
+			.into_actor(self)
+			.then(move |res, act, _| {
+				match res {
+					Ok((files, threads, suggestions)) => {
+						act.files = files.into_iter()
+							.filter_map(|file| file.id)
+							.collect();
+						act.comment_threads = threads.into_iter()
+							.map(|thread| (thread.id, thread))
+							.collect();
+						act.suggestions = suggestions.into_iter()
+							.map(|suggestion| (suggestion.id, suggestion))
+							.collect();
+					}
+					Err(err) => error!("Failed to load files, comment threads and suggestions of project {}: {}", project_id, err)
+				}
+				fut::ready(())
+			})
+			.wait(ctx);
+	}
+
+	fn stopping(&mut self, _: &mut Self::Context) -> Running {
+		info!("Closing room of project {}", self.project_id);
+		self.save_moved_ranges();
+		self.documents.drain().for_each(|(_, document)| document.do_send(Close));
+		Running::Stop
+	}
+}
+
+impl Handler<CloseRoom> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, _: CloseRoom, ctx: &mut Context<Self>) {
+		ctx.stop();
+	}
+}
+
+impl Handler<editor_session::FileDeletionRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: FileDeletionRequest, ctx: &mut Context<Self>) -> Self::Result {
+		let session_id = msg.session_id;
+		let file_id = msg.file_id;
+		// Waiting, so changes sent after deletion don't reopen document of deleted file
+		self.run_with_files_service(session_id, ctx, move |service| {
+			match service.get(file_id)? {
+				Some(file) => service.delete(file),
+				None => Err(DeletionError::FileDoesNotExistInProject)
+			}
+		}, move |act, res, recipient, ctx| {
+			match res {
+				Ok(_) => {
+					info!("Session {} deleted file {} from project {}", session_id, file_id, act.project_id);
+					let project_id = act.project_id;
+					act.handle(FileDeleted {
+						project_id,
+						id: file_id,
+					}, ctx);
+				}
+				Err(DeletionError::NotAllowed) => act.send_error(recipient, NOT_ALLOWED_MESSAGE.to_owned()),
+				Err(DeletionError::FileDoesNotExistInProject) => {
+					warn!("Session {} editing project {} tried to delete non existing file {}", session_id, act.project_id, file_id);
+					act.send_error(recipient, "File does not exist".to_owned());
+				}
+				Err(DeletionError::DatabaseError(err)) => {
+					error!("Unknown error occured while deleting file: {}", err);
+					act.send_error(recipient, "Unknown error occured while deleting file".to_owned());
+				}
+			}
+		});
+	}
+}
+
+impl Handler<ProjectUpdated> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: ProjectUpdated, _: &mut Context<Self>) {
+		self.broadcast(ProjectEvent::ProjectUpdated(msg));
+	}
+}
+
+/// Closes all sessions of deleted project. Documents are discarded, because files no longer exist.
+impl Handler<ProjectDeleted> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, _: ProjectDeleted, _: &mut Context<Self>) {
+		info!("Project {} was deleted, closing {} sessions", self.project_id, self.sessions.len());
+		self.documents.drain().for_each(|(_, document)| document.do_send(Discard));
+		self.files_histories.clear();
+		self.sessions.drain().for_each(|(_, session)| {
+			session.recipient.do_send(CloseSession {
+				reason: "Project was deleted".to_owned()
+			});
+		});
+	}
+}
+
+/// Register new session in room
+impl Handler<Join> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: Join, ctx: &mut Context<Self>) -> Self::Result {
+		let id = msg.session_id;
+		self.broadcast(ProjectEvent::SessionJoined(SessionJoined {
+			session_id: id,
+			name: msg.user.name.clone(),
+		}));
+
+		let joined_after_event = self.last_event_id;
+		let user = msg.user;
+		let addr = msg.addr;
+		let project_id = self.project_id;
+		let session_user = user.clone();
+		// Waiting, so session is registered before room handles its next messages
+		run(move || {
+			let projects_service = crate::services::projects::new(user.clone());
+			let project = projects_service.get(project_id)?;
+			let role = projects_service.get_role(&project)?;
+			let files = match crate::services::projects_files::new(user.id, project_id) {
+				Ok(service) => service.get_tree()?,
+				Err(ServiceCreationError::DatabaseError(err)) => return Err(GetError::DatabaseError(err)),
+				Err(_) => return Err(GetError::AccessDenied)
+			};
+			Ok((project, role, files))
+		})
+			.into_actor(self)
+			.then(move |res, act, ctx| {
+				match res {
+					Ok((project, role, files)) => {
+						let session_data = SessionData {
+							id,
+							user: session_user,
+							recipient: addr.clone(),
+							content_revisions: HashMap::new(),
+							joined_after_event,
+							last_client_change_id: None,
+							presence: None,
+							active_file_id: None,
+							leader_id: None,
+							chat_messages_sent_at: VecDeque::new(),
+							role,
+						};
+						act.send_project_info(&addr, &session_data, project, files);
+						act.sessions.insert(id, session_data);
+						act.send_chat_history(id, None, ctx);
+						let mut threads: Vec<CommentThread> = act.comment_threads.values().cloned().collect();
+						threads.sort_by_key(|thread| thread.id);
+						addr.do_send(CommentThreads { threads });
+						let mut suggestions: Vec<Suggestion> = act.suggestions.values().cloned().collect();
+						suggestions.sort_by_key(|suggestion| suggestion.id);
+						addr.do_send(Suggestions { suggestions });
+						info!("New session with id {} in project {}, current sessions {}", id, act.project_id, act.sessions.len());
+					}
+					Err(GetError::DatabaseError(err)) => {
+						error!("Error while trying to get project for ProjectInfoDto: {}", err);
+						act.send_error(&addr, "Failed to load project, try again later".to_owned());
+					}
+					Err(_) => {
+						error!("Error while trying to get project for ProjectInfoDto: Project does not exists or user have no access");
+						act.send_error(&addr, "You don't have access to this project or this project does not exist".to_owned());
+					}
+				}
+				fut::ready(())
+			})
+			.wait(ctx);
+	}
+}
+
+impl Handler<Resume> for ProjectRoom {
+	type Result = bool;
+
+	fn handle(&mut self, msg: Resume, _: &mut Context<Self>) -> Self::Result {
+		let session_data;
+		match self.sessions.get_mut(&msg.session_id) {
+			Some(data) => session_data = data,
+			None => return false
+		}
+		let last_event_id = msg.last_event_id.unwrap_or(session_data.joined_after_event);
+		let first_remembered_event_id = self.last_event_id + 1 - self.events.len() as u64;
+		if last_event_id + 1 < first_remembered_event_id || last_event_id > self.last_event_id {
+			info!("Events after {} missed by session {} are not remembered", last_event_id, msg.session_id);
+			return false;
+		}
+		session_data.recipient = msg.addr;
+		let missed_events: Vec<&Event> = self.events.iter()
+			.filter(|event| event.id > last_event_id)
+			.collect();
+		info!("Session {} resumed in project {}, sending {} missed events", msg.session_id, self.project_id, missed_events.len());
+		missed_events.into_iter().for_each(|event| session_data.recipient.do_send(event.clone()));
+		// Presences are not remembered as events, so session gets the current ones
+		self.send_presences(msg.session_id);
+		true
+	}
+}
+
+impl Handler<Leave> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: Leave, _: &mut Context<Self>) {
+		if self.sessions.remove(&msg.session_id).is_none() {
+			return;
+		}
+		self.broadcast(ProjectEvent::SessionLeft(SessionLeft { session_id: msg.session_id }));
+		let followers: Vec<i32> = self.sessions.values()
+			.filter(|session| session.leader_id == Some(msg.session_id))
+			.map(|session| session.id)
+			.collect();
+		for follower_id in followers {
+			self.set_leader(follower_id, None);
+		}
+		self.close_unused_documents();
+	}
+}
+
+impl Handler<editor_session::FileCreationRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: FileCreationRequest, ctx: &mut Context<Self>) -> Self::Result {
+		let new_file = ProjectFile {
+			id: None,
+			name: msg.filename,
+			project_id: self.project_id,
+			folder_id: msg.folder_id,
+		};
+		self.run_with_files_service(msg.session_id, ctx, move |service| service.create(new_file), |act, res, recipient, ctx| {
+			let created_file;
+			match res {
+				Ok(file) => {
+					created_file = file;
+					info!("Created new file in project {} with name {} and id {}", created_file.project_id, created_file.name, created_file.id.unwrap());
+				}
+				Err(CreationError::NotAllowed) => {
+					act.send_error(recipient, NOT_ALLOWED_MESSAGE.to_owned());
+					return;
+				}
+				Err(CreationError::IllegalName) => {
+					act.send_error(recipient, "Illegal new file name".to_owned());
+					return;
+				}
+				Err(CreationError::DuplicateNames) => {
+					act.send_error(recipient, "File with that name already exists".to_owned());
+					return;
+				}
+				Err(CreationError::FolderDoesNotExist) => {
+					act.send_error(recipient, "Folder does not exist".to_owned());
+					return;
+				}
+				Err(CreationError::DatabaseError(err)) => {
+					error!("Unknown error occured while creating file: {}", err);
+					act.send_error(recipient, "Unknown error occured while creating file".to_owned());
+					return;
+				}
+			}
+			act.handle(FileCreated {
+				project_id: created_file.project_id,
+				id: created_file.id.expect("That would mean that file was not created"),
+				name: created_file.name,
+				folder_id: created_file.folder_id,
+			}, ctx);
+		});
+	}
+}
+
+impl Handler<editor_session::FileNameChangeRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: FileNameChangeRequest, ctx: &mut Context<Self>) -> Self::Result {
+		let session_id = msg.session_id;
+		let file_id = msg.file_id;
+		let new_filename = msg.new_filename;
+		self.run_with_files_service(session_id, ctx, move |service| {
+			// Update is not bound to project, so file has to be found in this project first
+			match service.get(file_id)? {
+				Some(mut file) => {
+					file.name = new_filename;
+					service.update(file)
+				}
+				None => Err(UpdateError::FileDoesNotExists)
+			}
+		}, move |act, res, recipient, ctx| {
+			match res {
+				Ok(file) => {
+					info!("Session {} renamed file {} in project {} to {}", session_id, file_id, file.project_id, file.name);
+					act.handle(FileRenamed {
+						project_id: file.project_id,
+						id: file_id,
+						name: file.name,
+					}, ctx);
+				}
+				Err(UpdateError::NotAllowed) => act.send_error(recipient, NOT_ALLOWED_MESSAGE.to_owned()),
+				Err(UpdateError::IllegalName) => {
+					act.send_error(recipient, "Illegal new file name".to_owned());
+				}
+				Err(UpdateError::DuplicateName) => {
+					act.send_error(recipient, "File with that name already exists".to_owned());
+				}
+				Err(UpdateError::FileDoesNotExists) => {
+					warn!("Session {} editing project {} tried to rename non existing file {}", session_id, act.project_id, file_id);
+					act.send_error(recipient, "File does not exist".to_owned());
+				}
+				Err(UpdateError::FolderDoesNotExist) => {
+					act.send_error(recipient, "Folder does not exist".to_owned());
+				}
+				Err(UpdateError::DatabaseError(err)) => {
+					error!("Unknown error occured while renaming file: {}", err);
+					act.send_error(recipient, "Unknown error occured while renaming file".to_owned());
+				}
+			}
+		});
+	}
+}
+
+impl Handler<editor_session::FileMoveRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: FileMoveRequest, ctx: &mut Context<Self>) -> Self::Result {
+		let session_id = msg.session_id;
+		let file_id = msg.file_id;
+		let folder_id = msg.folder_id;
+		self.run_with_files_service(session_id, ctx, move |service| {
+			match service.get(file_id)? {
+				Some(mut file) => {
+					file.folder_id = folder_id;
+					service.update(file)
+				}
+				None => Err(UpdateError::FileDoesNotExists)
+			}
+		}, move |act, res, recipient, ctx| {
+			match res {
+				Ok(file) => {
+					info!("Session {} moved file {} in project {} to folder {:?}", session_id, file_id, file.project_id, file.folder_id);
+					act.handle(FileMoved {
+						project_id: file.project_id,
+						id: file_id,
+						folder_id: file.folder_id,
+					}, ctx);
+				}
+				Err(UpdateError::DuplicateName) => {
+					act.send_error(recipient, "File with that name already exists in folder".to_owned());
+				}
+				Err(UpdateError::FileDoesNotExists) => {
+					act.send_error(recipient, "File does not exist".to_owned());
+				}
+				Err(UpdateError::FolderDoesNotExist) => {
+					act.send_error(recipient, "Folder does not exist".to_owned());
+				}
+				Err(UpdateError::NotAllowed) => act.send_error(recipient, NOT_ALLOWED_MESSAGE.to_owned()),
+				Err(UpdateError::IllegalName) => {
+					error!("File {} with illegal name exists in project {}", file_id, act.project_id);
+				}
+				Err(UpdateError::DatabaseError(err)) => {
+					error!("Unknown error occured while moving file: {}", err);
+					act.send_error(recipient, "Unknown error occured while moving file".to_owned());
+				}
+			}
+		});
+	}
+}
+
+impl Handler<editor_session::FolderCreationRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: FolderCreationRequest, ctx: &mut Context<Self>) -> Self::Result {
+		let session_id = msg.session_id;
+		let new_folder = Folder {
+			id: None,
+			name: msg.name,
+			project_id: self.project_id,
+			parent_id: msg.parent_id,
+		};
+		self.run_with_files_service(session_id, ctx, move |service| service.create_folder(new_folder), move |act, res, recipient, ctx| {
+			match res {
+				Ok(folder) => {
+					info!("Session {} created folder {} with id {} in project {}", session_id, folder.name, folder.id.unwrap(), folder.project_id);
+					act.handle(FolderChanged { folder }, ctx);
+				}
+				Err(err) => act.send_folder_save_error(recipient, err)
+			}
+		});
+	}
+}
+
+impl Handler<editor_session::FolderRenameRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: FolderRenameRequest, ctx: &mut Context<Self>) -> Self::Result {
+		let new_name = msg.new_name;
+		self.update_folder(msg.session_id, msg.folder_id, ctx, move |folder| folder.name = new_name);
+	}
+}
+
+impl Handler<editor_session::FolderMoveRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: FolderMoveRequest, ctx: &mut Context<Self>) -> Self::Result {
+		let parent_id = msg.parent_id;
+		self.update_folder(msg.session_id, msg.folder_id, ctx, move |folder| folder.parent_id = parent_id);
+	}
+}
+
+impl Handler<editor_session::FolderDeletionRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: FolderDeletionRequest, ctx: &mut Context<Self>) -> Self::Result {
+		let session_id = msg.session_id;
+		let folder_id = msg.folder_id;
+		self.run_with_files_service(session_id, ctx, move |service| {
+			match service.get_folder(folder_id)? {
+				Some(folder) => service.delete_folder(folder),
+				None => Err(FolderDeletionError::FolderDoesNotExistInProject)
+			}
+		}, move |act, res, recipient, ctx| {
+			match res {
+				Ok(file_ids) => {
+					info!("Session {} deleted folder {} with {} files from project {}", session_id, folder_id, file_ids.len(), act.project_id);
+					let project_id = act.project_id;
+					act.handle(FolderDeleted {
+						project_id,
+						id: folder_id,
+						file_ids,
+					}, ctx);
+				}
+				Err(FolderDeletionError::NotAllowed) => act.send_error(recipient, NOT_ALLOWED_MESSAGE.to_owned()),
+				Err(FolderDeletionError::FolderDoesNotExistInProject) => {
+					act.send_error(recipient, "Folder does not exist".to_owned());
+				}
+				Err(FolderDeletionError::DatabaseError(err)) => {
+					error!("Unknown error occured while deleting folder: {}", err);
+					act.send_error(recipient, "Unknown error occured while deleting folder".to_owned());
+				}
+			}
+		});
+	}
+}
+
+impl Handler<FileMoved> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: FileMoved, _: &mut Context<Self>) {
+		self.broadcast(ProjectEvent::FileMoved(msg));
+	}
+}
+
+impl Handler<FolderChanged> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: FolderChanged, _: &mut Context<Self>) {
+		self.broadcast(ProjectEvent::FolderChanged(msg));
+	}
+}
+
+/// Discards documents of files deleted with folder and notifies sessions about deleted files and folder
+impl Handler<FolderDeleted> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: FolderDeleted, ctx: &mut Context<Self>) {
+		for file_id in msg.file_ids.iter() {
+			self.handle(FileDeleted {
+				project_id: msg.project_id,
+				id: *file_id,
+			}, ctx);
+		}
+		self.broadcast(ProjectEvent::FolderDeleted(msg));
+	}
+}
+
+impl Handler<FileCreated> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: FileCreated, _: &mut Context<Self>) {
+		self.files.insert(msg.id);
+		self.broadcast(ProjectEvent::FileCreated(msg));
+	}
+}
+
+impl Handler<FileRenamed> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: FileRenamed, _: &mut Context<Self>) {
+		self.broadcast(ProjectEvent::FileRenamed(msg));
+	}
+}
+
+/// Discards document of deleted file and notifies sessions
+impl Handler<FileDeleted> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: FileDeleted, _: &mut Context<Self>) {
+		self.files.remove(&msg.id);
+		self.files_histories.remove(&msg.id);
+		self.checksums.remove(&msg.id);
+		if let Some(document) = self.documents.remove(&msg.id) {
+			document.do_send(Discard);
+		}
+		let deleted_file_id = msg.id;
+		self.comment_threads.retain(|_, thread| thread.file_id != deleted_file_id);
+		self.suggestions.retain(|_, suggestion| suggestion.file_id != deleted_file_id);
+		// Sessions learn from file_deleted that nobody has this file opened anymore
+		self.sessions.values_mut()
+			.filter(|session| session.active_file_id == Some(msg.id))
+			.for_each(|session| session.active_file_id = None);
+		self.broadcast(ProjectEvent::FileDeleted(msg));
+	}
+}
+
+impl Handler<ReadFileContent> for ProjectRoom {
+	type Result = ResponseFuture<Option<String>>;
+
+	fn handle(&mut self, msg: ReadFileContent, _: &mut Context<Self>) -> Self::Result {
+		let document = self.documents.get(&msg.file_id)
+			.filter(|document| document.connected())
+			.cloned();
+		Box::pin(async move {
+			match document {
+				Some(document) => document.send(GetContent).await.ok(),
+				None => None
+			}
+		})
+	}
+}
+
+impl Handler<ReadLineAuthors> for ProjectRoom {
+	type Result = ResponseFuture<Option<(String, Vec<LineAuthor>)>>;
+
+	fn handle(&mut self, msg: ReadLineAuthors, _: &mut Context<Self>) -> Self::Result {
+		let document = self.documents.get(&msg.file_id)
+			.filter(|document| document.connected())
+			.cloned();
+		Box::pin(async move {
+			match document {
+				Some(document) => document.send(GetLineAuthors).await.ok(),
+				None => None
+			}
+		})
+	}
+}
+
+/// Saves changes of opened documents and returns their revisions in history of files, None if
+/// changes of any document could not be saved
+impl Handler<FlushDocuments> for ProjectRoom {
+	type Result = ResponseFuture<Option<HashMap<i32, i32>>>;
+
+	fn handle(&mut self, _: FlushDocuments, _: &mut Context<Self>) -> Self::Result {
+		let documents: Vec<(i32, Addr<FileDocument>)> = self.documents.iter()
+			.filter(|(_, document)| document.connected())
+			.map(|(file_id, document)| (*file_id, document.clone()))
+			.collect();
+		Box::pin(async move {
+			let mut revisions = HashMap::new();
+			for (file_id, document) in documents {
+				match document.send(Flush).await {
+					Ok(Some(revision)) => {
+						revisions.insert(file_id, revision);
+					}
+					Ok(None) => return None,
+					// Stopped document started its last save already
+					Err(err) => warn!("Document of file {} was closed while saving it: {}", file_id, err)
+				}
+			}
+			Some(revisions)
+		})
+	}
+}
+
+/// Replaces content of file with one change, so sessions editing file get new content as any other change
+impl Handler<WriteFileContent> for ProjectRoom {
+	type Result = AtomicResponse<Self, Result<document::ContentSaved, DatabaseError>>;
+
+	fn handle(&mut self, msg: WriteFileContent, _: &mut Context<Self>) -> Self::Result {
+		let file_id = msg.file_id;
+		let content = msg.content;
+		let author_id = msg.author_id;
+		let document;
+		match self.document(file_id) {
+			Some(addr) => document = addr,
+			None => return AtomicResponse::new(Box::pin(fut::err(DatabaseError {
+				error_code: DatabaseError::QUERY_ERROR,
+				message: format!("File {} does not exist in project {}", file_id, self.project_id),
+			}))),
+		}
+		// Atomic, so no other change is applied between reading end of file and replacing content
+		AtomicResponse::new(Box::pin(document
+			.send(GetContent)
+			.into_actor(self)
+			.map(move |res, act, ctx| {
+				let old_content;
+				match res {
+					Ok(current_content) => old_content = current_content,
+					Err(err) => {
+						error!("Failed to replace content of file {}: {}", file_id, err);
+						return Err(DatabaseError {
+							error_code: DatabaseError::QUERY_ERROR,
+							message: format!("Document of file {} could not be loaded", file_id),
+						});
+					}
+				}
+				if old_content == content {
+					return Ok(Box::pin(async { Ok(()) }) as document::ContentSaved);
+				}
+				let change = FileChange {
+					session_id: SERVER_SESSION_ID,
+					start: Position { row: 0, column: 0 },
+					end: end_position(&old_content),
+					file_id,
+					lines: content.split('\n').map(|line| line.to_owned()).collect(),
+					revision: None,
+					client_change_id: None,
+				};
+				info!("Replacing content of file {} in project {}", file_id, act.project_id);
+				act.broadcast_change(&change);
+				act.apply_change(change, author_id, ctx);
+				// Flushed before it is closed, so saving is not waited for in room
+				let saved = document::saved_content(file_id, &document);
+				act.close_unused_documents();
+				Ok(saved)
+			})))
+	}
+}
+
+impl Handler<editor_session::FileContentRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::FileContentRequest, ctx: &mut Context<Self>) {
+		if !self.check_file(msg.session_id, msg.file_id) {
+			return;
+		}
+		self.send_file_content(msg.session_id, msg.file_id, ctx);
+		self.set_active_file(msg.session_id, Some(msg.file_id));
+	}
+}
+
+impl Handler<editor_session::FileChange> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::FileChange, ctx: &mut Context<Self>) {
+		let session_id = msg.session_id;
+		let file_id = msg.file_id;
+		if !self.check_file(session_id, file_id) {
+			return;
+		}
+		if !self.can_edit(session_id) {
+			// Client has to discard change it already made in its content
+			self.send_file_content(session_id, file_id, ctx);
+			return;
+		}
+		match self.merge_change(msg, ctx) {
+			MergeResult::Outdated => {
+				if let Some(session_data) = self.sessions.get(&session_id) {
+					self.send_error(&session_data.recipient, "Your version of file is outdated, reloading it".to_owned());
+				}
+				self.send_file_content(session_id, file_id, ctx);
+			}
+			MergeResult::UnknownSession => error!("Not registered session sent change in file"),
+			_ => {}
+		}
+	}
+}
+
+/// Rebases changes made offline onto current content of file. If revision they were made on is not
+/// remembered, they are applied only if content of file did not change since then.
+impl Handler<editor_session::OfflineChangesRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::OfflineChangesRequest, ctx: &mut Context<Self>) {
+		let has_content;
+		match self.sessions.get(&msg.session_id) {
+			Some(data) => has_content = data.content_revisions.contains_key(&msg.file_id),
+			None => {
+				error!("Not registered session sent offline changes");
+				return;
+			}
+		}
+		if !self.check_file(msg.session_id, msg.file_id) {
+			return;
+		}
+		if !self.can_edit(msg.session_id) {
+			self.reject_offline_changes(msg.session_id, msg.file_id, ctx);
+			return;
+		}
+		let base_revision = msg.revision
+			.filter(|revision| has_content && self.remembers_revision(msg.file_id, *revision));
+		if let Some(revision) = base_revision {
+			self.merge_offline_changes(msg.session_id, msg.file_id, revision, msg.changes, ctx);
+			return;
+		}
+		let session_id = msg.session_id;
+		let file_id = msg.file_id;
+		let changes = msg.changes;
+		match msg.base_checksum {
+			Some(base_checksum) => self.compute_checksum(file_id, ctx, move |act, revision, checksum, ctx| {
+				if checksum != base_checksum {
+					act.reject_offline_changes(session_id, file_id, ctx);
+					return;
+				}
+				// Session has the same content as file in this revision
+				if let Some(session_data) = act.sessions.get_mut(&session_id) {
+					session_data.content_revisions.insert(file_id, revision);
+				}
+				act.merge_offline_changes(session_id, file_id, revision, changes, ctx);
+			}),
+			None => self.reject_offline_changes(session_id, file_id, ctx)
+		}
+	}
+}
+
+/// Remembers presence of session and sends it to other sessions. Presences change often and only
+/// the last one matters, so they are not numbered and remembered as events.
+impl Handler<editor_session::PresenceUpdate> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::PresenceUpdate, _: &mut Context<Self>) {
+		let session_id = msg.session_id;
+		let presence = SessionPresence {
+			session_id: msg.session_id,
+			file_id: msg.file_id,
+			cursor: msg.cursor,
+			selections: msg.selections,
+			viewport: msg.viewport,
+		};
+		match self.sessions.get_mut(&session_id) {
+			Some(session_data) => session_data.presence = Some(presence),
+			None => {
+				error!("Not registered session sent presence");
+				return;
+			}
+		}
+		let session_data = &self.sessions[&session_id];
+		self.sessions.values()
+			.filter(|session| session.id != session_id)
+			.for_each(|session| {
+				if let Some(presence) = session_data.presence_for(session) {
+					session.recipient.do_send(presence);
+				}
+			});
+	}
+}
+
+/// Saves chat message and sends it to all sessions. Sessions can send only CHAT_RATE_LIMIT messages
+/// in CHAT_RATE_LIMIT_INTERVAL.
+impl Handler<editor_session::ChatMessageRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::ChatMessageRequest, ctx: &mut Context<Self>) {
+		let content = msg.content.trim().to_owned();
+		let session_data;
+		match self.sessions.get_mut(&msg.session_id) {
+			Some(data) => session_data = data,
+			None => {
+				error!("Not registered session sent chat message");
+				return;
+			}
+		}
+		let now = Instant::now();
+		while session_data.chat_messages_sent_at.front()
+			.is_some_and(|sent_at| now.duration_since(*sent_at) > CHAT_RATE_LIMIT_INTERVAL) {
+			session_data.chat_messages_sent_at.pop_front();
+		}
+		let recipient = session_data.recipient.clone();
+		if session_data.chat_messages_sent_at.len() >= CHAT_RATE_LIMIT {
+			self.send_error(&recipient, "You are sending chat messages too fast".to_owned());
+			return;
+		}
+		if content.is_empty() || content.chars().count() > MAX_CHAT_MESSAGE_LENGTH {
+			self.send_error(&recipient, format!("Chat message must have from 1 to {} characters", MAX_CHAT_MESSAGE_LENGTH));
+			return;
+		}
+		session_data.chat_messages_sent_at.push_back(now);
+		let author = session_data.user.clone();
+		let project_id = self.project_id;
+		// Waiting, so messages are broadcast in the same order they were sent
+		run(move || crate::repositories::chat_messages::new(project_id).create(&author, &content))
+			.into_actor(self)
+			.then(move |res, act, _| {
+				match res {
+					Ok(message) => act.broadcast(ProjectEvent::ChatMessage(message)),
+					Err(err) => {
+						error!("Failed to save chat message in project {}: {}", project_id, err);
+						act.send_error(&recipient, "Failed to send chat message, try again later".to_owned());
+					}
+				}
+				fut::ready(())
+			})
+			.wait(ctx);
+	}
+}
+
+impl Handler<editor_session::CommentThreadCreationRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::CommentThreadCreationRequest, ctx: &mut Context<Self>) {
+		let content;
+		match self.sessions.get(&msg.session_id) {
+			Some(session_data) => match self.validate_comment(&session_data.recipient, &msg.content) {
+				Some(valid_content) => content = valid_content,
+				None => return
+			},
+			None => {
+				error!("Not registered session sent comment");
+				return;
+			}
+		}
+		let file_id = msg.file_id;
+		let (start, end) = if msg.start <= msg.end { (msg.start, msg.end) } else { (msg.end, msg.start) };
+		self.run_with_comments_repository(msg.session_id, ctx, move |repository, user| {
+			repository.create_thread(file_id, start, end, user, &content)
+		}, |act, thread, recipient| {
+			match thread {
+				Some(thread) => act.broadcast_comment_thread(thread),
+				None => act.send_error(recipient, "File does not exist".to_owned())
+			}
+		});
+	}
+}
+
+impl Handler<editor_session::CommentRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::CommentRequest, ctx: &mut Context<Self>) {
+		let content;
+		match self.sessions.get(&msg.session_id) {
+			Some(session_data) if !self.comment_threads.contains_key(&msg.thread_id) => {
+				self.send_error(&session_data.recipient, "Comment thread does not exist".to_owned());
+				return;
+			}
+			Some(session_data) => match self.validate_comment(&session_data.recipient, &msg.content) {
+				Some(valid_content) => content = valid_content,
+				None => return
+			},
+			None => {
+				error!("Not registered session sent comment");
+				return;
+			}
+		}
+		let thread_id = msg.thread_id;
+		self.run_with_comments_repository(msg.session_id, ctx, move |repository, user| {
+			repository.add_comment(thread_id, user, &content)
+		}, move |act, comment, recipient| {
+			let thread = match (comment, act.comment_threads.get(&thread_id)) {
+				(Some(comment), Some(thread)) => {
+					let mut thread = thread.clone();
+					thread.comments.push(comment);
+					thread
+				}
+				_ => {
+					act.send_error(recipient, "Comment thread does not exist".to_owned());
+					return;
+				}
+			};
+			act.broadcast_comment_thread(thread);
+		});
+	}
+}
+
+impl Handler<editor_session::CommentThreadResolutionRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::CommentThreadResolutionRequest, ctx: &mut Context<Self>) {
+		match self.sessions.get(&msg.session_id) {
+			Some(session_data) if !self.comment_threads.contains_key(&msg.thread_id) => {
+				self.send_error(&session_data.recipient, "Comment thread does not exist".to_owned());
+				return;
+			}
+			Some(_) => {}
+			None => {
+				error!("Not registered session resolved comment thread");
+				return;
+			}
+		}
+		let thread_id = msg.thread_id;
+		let resolved = msg.resolved;
+		self.run_with_comments_repository(msg.session_id, ctx, move |repository, _| {
+			repository.set_resolved(thread_id, resolved)
+		}, move |act, _, _| {
+			if let Some(thread) = act.comment_threads.get(&thread_id) {
+				let mut thread = thread.clone();
+				thread.resolved = resolved;
+				act.broadcast_comment_thread(thread);
+			}
+		});
+	}
+}
+
+/// Saves change suggested by session, transformed against changes it has not seen yet, and shows it
+/// to all sessions. Suggestion is not applied until project owner accepts it.
+impl Handler<editor_session::SuggestionRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::SuggestionRequest, ctx: &mut Context<Self>) {
+		let mut change = msg.change;
+		let (user, recipient);
+		match self.sessions.get(&change.session_id) {
+			Some(session_data) => {
+				user = session_data.user.clone();
+				recipient = session_data.recipient.clone();
+			}
+			None => {
+				error!("Not registered session suggested change");
+				return;
+			}
+		}
+		if change.end < change.start {
+			std::mem::swap(&mut change.start, &mut change.end);
+		}
+		if self.transform_unseen_changes(&mut change).is_none() {
+			self.send_error(&recipient, "Your version of file is outdated, reloading it".to_owned());
+			self.send_file_content(change.session_id, change.file_id, ctx);
+			return;
+		}
+		let project_id = self.project_id;
+		// Waiting, so changes applied later move range of the suggestion
+		run(move || crate::repositories::suggestions::new(project_id).create(change.file_id, change.start, change.end, &user, &change.lines))
+			.into_actor(self)
+			.then(move |res, act, _| {
+				match res {
+					Ok(Some(suggestion)) => {
+						act.suggestions.insert(suggestion.id, suggestion.clone());
+						act.broadcast(ProjectEvent::SuggestionCreated(suggestion));
+					}
+					Ok(None) => act.send_error(&recipient, "File does not exist".to_owned()),
+					Err(err) => {
+						error!("Failed to save suggestion in project {}: {}", project_id, err);
+						act.send_error(&recipient, "Failed to save suggestion, try again later".to_owned());
+					}
+				}
+				fut::ready(())
+			})
+			.wait(ctx);
+	}
+}
+
+/// Deletes reviewed suggestion, accepted one is applied to file
+impl Handler<editor_session::SuggestionReviewRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::SuggestionReviewRequest, ctx: &mut Context<Self>) {
+		let recipient;
+		match self.sessions.get(&msg.session_id) {
+			Some(session_data) if session_data.role != ProjectRole::Owner => {
+				self.send_error(&session_data.recipient, "Only owner of project can review suggestions".to_owned());
+				return;
+			}
+			Some(session_data) if !self.suggestions.contains_key(&msg.suggestion_id) => {
+				self.send_error(&session_data.recipient, "Suggestion does not exist".to_owned());
+				return;
+			}
+			Some(session_data) => recipient = session_data.recipient.clone(),
+			None => {
+				error!("Not registered session reviewed suggestion");
+				return;
+			}
+		}
+		let project_id = self.project_id;
+		let suggestion_id = msg.suggestion_id;
+		let accepted = msg.accept;
+		// Waiting, so suggestion is applied in its current range
+		run(move || crate::repositories::suggestions::new(project_id).delete(suggestion_id))
+			.into_actor(self)
+			.then(move |res, act, ctx| {
+				match res {
+					Ok(deleted) => {
+						if !deleted {
+							warn!("Suggestion {} was already deleted from database", suggestion_id);
+						}
+						if let Some(suggestion) = act.suggestions.remove(&suggestion_id) {
+							act.moved_suggestions.remove(&suggestion_id);
+							act.broadcast(ProjectEvent::SuggestionReviewed(SuggestionReviewed {
+								suggestion_id,
+								accepted,
+							}));
+							if accepted {
+								act.apply_suggestion(suggestion, ctx);
+							}
+						}
+					}
+					Err(err) => {
+						error!("Failed to delete suggestion {} in project {}: {}", suggestion_id, project_id, err);
+						act.send_error(&recipient, "Failed to review suggestion, try again later".to_owned());
+					}
+				}
+				fut::ready(())
+			})
+			.wait(ctx);
+	}
+}
+
+impl Handler<editor_session::ChatHistoryRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::ChatHistoryRequest, ctx: &mut Context<Self>) {
+		self.send_chat_history(msg.session_id, msg.before_id, ctx);
+	}
+}
+
+/// Starts following other session. Follower gets file opened by leader and its presence with viewport
+/// right away, later it gets them whenever they change.
+impl Handler<editor_session::FollowRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::FollowRequest, _: &mut Context<Self>) {
+		let recipient;
+		match self.sessions.get(&msg.session_id) {
+			Some(data) => recipient = data.recipient.clone(),
+			None => {
+				error!("Not registered session wants to follow other session");
+				return;
+			}
+		}
+		let leader_id = match msg.leader_id {
+			Some(leader_id) => leader_id,
+			None => {
+				self.set_leader(msg.session_id, None);
+				return;
+			}
+		};
+		if leader_id == msg.session_id || !self.sessions.contains_key(&leader_id) {
+			self.send_error(&recipient, "Session you want to follow is not editing this project".to_owned());
+			return;
+		}
+		self.set_leader(msg.session_id, Some(leader_id));
+		let leader = &self.sessions[&leader_id];
+		recipient.do_send(LeaderFileChanged {
+			leader_id,
+			file_id: leader.active_file_id,
+		});
+		if let Some(presence) = leader.presence_for(&self.sessions[&msg.session_id]) {
+			recipient.do_send(presence);
+		}
+	}
+}
+
+impl Handler<editor_session::CrdtOperationsRequest> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::CrdtOperationsRequest, ctx: &mut Context<Self>) {
+		let recipient = match self.sessions.get(&msg.session_id) {
+			Some(data) => data.recipient.clone(),
+			None => {
+				error!("Not registered session sent CRDT operations");
+				return;
+			}
+		};
+		if self.document_model != DocumentModel::Crdt {
+			self.send_error(&recipient, "This server does not use CRDT documents".to_owned());
+			return;
+		}
+		if !self.check_file(msg.session_id, msg.file_id) {
+			return;
+		}
+		if !self.can_edit(msg.session_id) {
+			self.send_file_content(msg.session_id, msg.file_id, ctx);
+			return;
+		}
+		let session_id = msg.session_id;
+		let file_id = msg.file_id;
+		let operations = msg.operations.clone();
+		let author_id = self.sessions.get(&session_id).map(|session_data| session_data.user.id);
+		let document;
+		match self.document(file_id) {
+			Some(addr) => document = addr,
+			None => return
+		}
+		// Waiting for document, so changes made by other sessions in the meantime will be transformed against these
+		document
+			.send(IntegrateOperations { operations: msg.operations, author_id })
+			.into_actor(self)
+			.then(move |res, act, ctx| {
+				match res {
+					Ok(Ok(changes)) => {
+						for change in changes {
+							act.broadcast_change(&FileChange {
+								session_id,
+								start: change.start,
+								end: change.end,
+								file_id,
+								lines: change.text.split('\n').map(|line| line.to_owned()).collect(),
+								revision: None,
+								client_change_id: None,
+							});
+						}
+						act.broadcast_crdt_operations(file_id, operations);
+					}
+					Ok(Err(UnknownElement(id))) => {
+						warn!("Session {} sent CRDT operation referring to unknown element {:?} in file {}", session_id, id, file_id);
+						act.send_error(&recipient, "Your version of file is outdated, reloading it".to_owned());
+						act.send_file_content(session_id, file_id, ctx);
+					}
+					Err(err) => error!("Failed to integrate CRDT operations in file {}: {}", file_id, err)
+				}
+				fut::ready(())
+			})
+			.wait(ctx);
+	}
+}
+
+impl Handler<editor_session::FileChecksumReport> for ProjectRoom {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::FileChecksumReport, ctx: &mut Context<Self>) {
+		match self.sessions.get(&msg.session_id) {
+			Some(data) if data.content_revisions.contains_key(&msg.file_id) => {}
+			Some(_) => {
+				warn!("Session {} sent checksum of file {} it has not loaded", msg.session_id, msg.file_id);
+				return;
+			}
+			None => {
+				error!("Not registered session sent file checksum");
+				return;
+			}
+		}
+		let session_id = msg.session_id;
+		let file_id = msg.file_id;
+		let known_checksum = self.checksums.get(&file_id)
+			.filter(|(revision, _)| *revision == msg.revision)
+			.map(|(_, checksum)| checksum.clone());
+		match known_checksum {
+			Some(checksum) => self.verify_checksum(session_id, file_id, &checksum, &msg.checksum, ctx),
+			None if msg.revision == self.revision(file_id) => {
+				let session_checksum = msg.checksum;
+				self.compute_checksum(file_id, ctx, move |act, _, checksum, ctx| {
+					act.verify_checksum(session_id, file_id, checksum, &session_checksum, ctx);
+				});
+			}
+			None => info!("Can't verify checksum of file {} in revision {} sent by session {}", file_id, msg.revision, session_id)
+		}
+	}
+}
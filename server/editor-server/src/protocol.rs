@@ -0,0 +1,744 @@
+/// Messages exchanged with editor clients over websocket.
+///
+/// Clients choose format of messages during websocket handshake with `Sec-WebSocket-Protocol`
+/// header. Typed messages are objects with `type` field, first message sent by server is `welcome`
+/// with version of protocol and id of session. They are sent as JSON text frames or as MessagePack
+/// or CBOR binary frames. Clients which don't request any supported subprotocol use legacy format
+/// with one character codes (see `legacy` module).
+///
+/// Messages about project events have also `event_id` field. Client which lost connection can
+/// reconnect with `resume_token` from `welcome` and id of last received event in query of websocket
+/// url, then it gets the same session and events it missed.
+pub mod legacy;
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::crdt::{CrdtDocument, Operation};
+use crate::editor_session::{Position, Selection, Viewport};
+use crate::models::{Project, Folder, ChatMessage, CommentThread, Suggestion};
+use crate::server::{ProjectInfoDto, CrdtOperationsApplied, OfflineChangesResult, SessionPresence};
+
+/// Version of typed protocol sent in `welcome` message
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Subprotocol of typed messages serialized as JSON text frames
+pub const JSON_SUBPROTOCOL: &str = "online-editor.v1.json";
+/// Subprotocol of typed messages serialized as MessagePack binary frames
+pub const MESSAGE_PACK_SUBPROTOCOL: &str = "online-editor.v1.msgpack";
+/// Subprotocol of typed messages serialized as CBOR binary frames
+pub const CBOR_SUBPROTOCOL: &str = "online-editor.v1.cbor";
+/// Subprotocols accepted in websocket handshake
+pub const SUBPROTOCOLS: [&str; 3] = [JSON_SUBPROTOCOL, MESSAGE_PACK_SUBPROTOCOL, CBOR_SUBPROTOCOL];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Protocol {
+	Legacy,
+	Json,
+	MessagePack,
+	Cbor,
+}
+
+/// Websocket frame with encoded message
+pub enum Frame {
+	Text(String),
+	Binary(Vec<u8>),
+}
+
+/// Message sent by client
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IncomingMessage {
+	CreateFile {
+		name: String,
+		/// None creates file in root of project
+		#[serde(default)]
+		folder_id: Option<i32>,
+	},
+	DeleteFile {
+		file_id: i32
+	},
+	RenameFile {
+		file_id: i32,
+		name: String,
+	},
+	MoveFile {
+		file_id: i32,
+		folder_id: Option<i32>,
+	},
+	GetFileContent {
+		file_id: i32
+	},
+	ChangeInFile {
+		file_id: i32,
+		start: Position,
+		end: Position,
+		lines: Vec<String>,
+		/// Revision of file the change was made on, that is revision of last change or content
+		/// received from server
+		#[serde(alias = "last_change_applied_id")]
+		revision: Option<usize>,
+		/// Id of change sent back in `change_acknowledged`
+		#[serde(default)]
+		client_change_id: Option<u32>,
+	},
+	/// Changes made while client was offline, server rebases them onto current content of file
+	OfflineChanges(OfflineChanges),
+	/// Checksum of content of file in given revision, server sends file content again if it differs
+	FileChecksum {
+		file_id: i32,
+		revision: usize,
+		checksum: String,
+	},
+	/// Opened file, cursor and selections of user, relayed to other sessions of project
+	Presence {
+		#[serde(default)]
+		file_id: Option<i32>,
+		#[serde(default)]
+		cursor: Option<Position>,
+		#[serde(default)]
+		selections: Vec<Selection>,
+		/// Visible rows of file, sent only to sessions following this one
+		#[serde(default)]
+		viewport: Option<Viewport>,
+	},
+	/// Starts following given session, None stops following
+	Follow {
+		session_id: Option<i32>
+	},
+	/// Sends message to project chat, `@name` mentions project member
+	ChatMessage {
+		content: String
+	},
+	/// Requests chat messages older than given one, None requests the last messages
+	GetChatHistory {
+		#[serde(default)]
+		before_id: Option<i32>,
+	},
+	/// Starts comment thread about range of file
+	CreateCommentThread {
+		file_id: i32,
+		start: Position,
+		end: Position,
+		content: String,
+	},
+	/// Replies in comment thread
+	AddComment {
+		thread_id: i32,
+		content: String,
+	},
+	ResolveCommentThread {
+		thread_id: i32,
+		resolved: bool,
+	},
+	/// Suggests replacing text between start and end with lines, project owner reviews it
+	SuggestChange {
+		file_id: i32,
+		start: Position,
+		end: Position,
+		lines: Vec<String>,
+		/// Revision of file the change was made on
+		#[serde(default)]
+		revision: Option<usize>,
+	},
+	/// Accepts or rejects suggested change, allowed only for project owner
+	ReviewSuggestion {
+		suggestion_id: i32,
+		accept: bool,
+	},
+	CrdtOperations {
+		file_id: i32,
+		operations: Vec<Operation>,
+	},
+	CreateFolder {
+		name: String,
+		/// None creates folder in root of project
+		#[serde(default)]
+		parent_id: Option<i32>,
+	},
+	RenameFolder {
+		folder_id: i32,
+		name: String,
+	},
+	MoveFolder {
+		folder_id: i32,
+		parent_id: Option<i32>,
+	},
+	DeleteFolder {
+		folder_id: i32
+	},
+}
+
+/// Changes in file made by client while it was offline, each change is made on the result of previous ones
+#[derive(Deserialize, Debug)]
+pub struct OfflineChanges {
+	pub file_id: i32,
+	/// Last revision of file seen by client before it went offline
+	#[serde(default)]
+	pub revision: Option<usize>,
+	/// SHA-256 checksum of content of file in that revision. If server does not remember revision
+	/// anymore (e.g. everybody left project in the meantime), changes are merged only if current
+	/// content has the same checksum.
+	#[serde(default)]
+	pub base_checksum: Option<String>,
+	pub changes: Vec<OfflineChange>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OfflineChange {
+	pub start: Position,
+	pub end: Position,
+	pub lines: Vec<String>,
+	#[serde(default)]
+	pub client_change_id: Option<u32>,
+}
+
+/// Message sent by server
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutgoingMessage {
+	Welcome {
+		version: u32,
+		session_id: i32,
+		/// Token which client sends when it reconnects to resume this session
+		resume_token: String,
+		/// Whether connection resumed previous session, events it missed are sent right after
+		resumed: bool,
+	},
+	ProjectInfo(ProjectInfoDto),
+	ProjectUpdated {
+		project: Project
+	},
+	SessionJoined {
+		session_id: i32,
+		name: String,
+	},
+	SessionLeft {
+		session_id: i32
+	},
+	/// Session opened other file, None when it does not have any file opened
+	ActiveFileChanged {
+		session_id: i32,
+		file_id: Option<i32>,
+	},
+	/// Session started following other session, None when it stopped following
+	FollowChanged {
+		session_id: i32,
+		leader_id: Option<i32>,
+	},
+	/// Followed session opened other file, sent only to its followers
+	LeaderFileChanged {
+		leader_id: i32,
+		file_id: Option<i32>,
+	},
+	FileCreated {
+		file_id: i32,
+		name: String,
+		folder_id: Option<i32>,
+	},
+	FileDeleted {
+		file_id: i32
+	},
+	FileRenamed {
+		file_id: i32,
+		name: String,
+	},
+	FileMoved {
+		file_id: i32,
+		folder_id: Option<i32>,
+	},
+	FileContent {
+		file_id: i32,
+		content: String,
+		revision: usize,
+	},
+	/// Ids of characters and tombstones of file, sent after its content in CRDT model
+	CrdtState {
+		file_id: i32,
+		document: CrdtDocument,
+		revision: usize,
+	},
+	/// Change made by other session or server
+	ChangeInFile {
+		file_id: i32,
+		start: Position,
+		end: Position,
+		revision: usize,
+		lines: Vec<String>,
+	},
+	/// Change sent by this session was applied as given revision of file
+	ChangeAcknowledged {
+		file_id: i32,
+		revision: usize,
+		client_change_id: Option<u32>,
+	},
+	OfflineChangesResult(OfflineChangesResult),
+	/// SHA-256 checksum of content of file in given revision, as lowercase hex string
+	FileChecksum {
+		file_id: i32,
+		revision: usize,
+		checksum: String,
+	},
+	/// All comment threads in project, sent after joining it
+	CommentThreads {
+		threads: Vec<CommentThread>
+	},
+	/// Comment thread was created, replied to, resolved or unresolved
+	CommentThreadChanged(CommentThread),
+	/// Suggested changes waiting for review, sent after joining project
+	Suggestions {
+		suggestions: Vec<Suggestion>
+	},
+	/// Change was suggested, range is in current revision of file on server
+	SuggestionCreated(Suggestion),
+	/// Suggestion was accepted and applied as change in file, or rejected
+	SuggestionReviewed {
+		suggestion_id: i32,
+		accepted: bool,
+	},
+	ChatMessage(ChatMessageDto),
+	/// Messages from chat, from the oldest one. Sent after joining project and on request.
+	ChatHistory {
+		messages: Vec<ChatMessageDto>
+	},
+	/// Presence of other session, positions are in current revision of file on server
+	Presence(SessionPresence),
+	CrdtOperations(CrdtOperationsApplied),
+	FolderChanged {
+		folder: Folder
+	},
+	FolderDeleted {
+		folder_id: i32
+	},
+	Error {
+		message: String
+	},
+}
+
+/// Chat message as seen by user of session
+#[derive(Serialize)]
+pub struct ChatMessageDto {
+	#[serde(flatten)]
+	pub message: ChatMessage,
+	/// Whether user of session is mentioned in message
+	pub mentions_you: bool,
+}
+
+impl ChatMessageDto {
+	pub fn new(message: ChatMessage, user_id: i32) -> ChatMessageDto {
+		let mentions_you = message.mentions.contains(&user_id);
+		ChatMessageDto {
+			message,
+			mentions_you,
+		}
+	}
+}
+
+/// Message about project event. Id of last received event is sent by client when it resumes session.
+#[derive(Serialize)]
+struct EventMessage<'a> {
+	event_id: u64,
+	#[serde(flatten)]
+	message: &'a OutgoingMessage,
+}
+
+/// Message from client which could not be decoded
+#[derive(Debug)]
+pub struct DecodeError(pub String);
+
+impl fmt::Display for DecodeError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.0)
+	}
+}
+
+/// Message which could not be serialized
+#[derive(Debug)]
+pub struct EncodeError(pub String);
+
+impl fmt::Display for EncodeError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.0)
+	}
+}
+
+impl From<serde_json::Error> for EncodeError {
+	fn from(err: serde_json::Error) -> Self {
+		EncodeError(err.to_string())
+	}
+}
+
+impl Protocol {
+	/// Chooses protocol from value of `Sec-WebSocket-Protocol` header sent by client. First supported
+	/// subprotocol is chosen, the same one actix confirms in handshake response.
+	pub fn negotiate(requested_protocols: Option<&str>) -> Protocol {
+		let requested_protocols = match requested_protocols {
+			Some(protocols) => protocols,
+			None => return Protocol::Legacy
+		};
+		for protocol in requested_protocols.split(',').map(|protocol| protocol.trim()) {
+			match protocol {
+				JSON_SUBPROTOCOL => return Protocol::Json,
+				MESSAGE_PACK_SUBPROTOCOL => return Protocol::MessagePack,
+				CBOR_SUBPROTOCOL => return Protocol::Cbor,
+				_ => {}
+			}
+		}
+		Protocol::Legacy
+	}
+
+	pub fn decode_text(&self, text: &str) -> Result<IncomingMessage, DecodeError> {
+		match self {
+			Protocol::Legacy => legacy::decode(text),
+			Protocol::Json => serde_json::from_str(text).map_err(|err| DecodeError(err.to_string())),
+			_ => Err(DecodeError("Text frame sent in binary protocol".to_owned()))
+		}
+	}
+
+	pub fn decode_binary(&self, bytes: &[u8]) -> Result<IncomingMessage, DecodeError> {
+		match self {
+			Protocol::MessagePack => rmp_serde::from_slice(bytes).map_err(|err| DecodeError(err.to_string())),
+			Protocol::Cbor => ciborium::de::from_reader(bytes).map_err(|err| DecodeError(err.to_string())),
+			_ => Err(DecodeError("Binary frame sent in text protocol".to_owned()))
+		}
+	}
+
+	/// Encodes message as websocket frames. Legacy protocol has no equivalent of some messages, so
+	/// there may be no frames to send.
+	pub fn encode(&self, message: &OutgoingMessage) -> Result<Vec<Frame>, EncodeError> {
+		match self {
+			Protocol::Legacy => Ok(legacy::encode(message)?.into_iter().map(Frame::Text).collect()),
+			_ => Ok(vec![self.encode_typed(message)?])
+		}
+	}
+
+	/// Encodes message about project event with its id. Legacy protocol does not send ids of events.
+	pub fn encode_event(&self, event_id: u64, message: &OutgoingMessage) -> Result<Vec<Frame>, EncodeError> {
+		match self {
+			Protocol::Legacy => self.encode(message),
+			_ => Ok(vec![self.encode_typed(&EventMessage { event_id, message })?])
+		}
+	}
+
+	fn encode_typed<T: Serialize>(&self, message: &T) -> Result<Frame, EncodeError> {
+		let frame = match self {
+			Protocol::Legacy => return Err(EncodeError("Typed message encoded in legacy protocol".to_owned())),
+			Protocol::Json => Frame::Text(serde_json::to_string(message)?),
+			// Structs are encoded as maps, so messages have the same shape as in JSON
+			Protocol::MessagePack => Frame::Binary(rmp_serde::to_vec_named(message)
+				.map_err(|err| EncodeError(err.to_string()))?),
+			Protocol::Cbor => {
+				let mut bytes = Vec::new();
+				ciborium::ser::into_writer(message, &mut bytes).map_err(|err| EncodeError(err.to_string()))?;
+				Frame::Binary(bytes)
+			}
+		};
+		Ok(frame)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::crdt::ElementId;
+	use crate::models::{Comment, FileTreeNode, ProjectRole, User};
+	use crate::server::{ChangeConflict, SessionDataDto};
+
+	fn position(row: u32, column: u32) -> Position {
+		Position { row, column }
+	}
+
+	fn user() -> User {
+		User { id: 1, name: "owner".to_owned() }
+	}
+
+	/// One message of every type sent by clients, as JSON
+	const INCOMING_MESSAGES: [&str; 24] = [
+		r#"{"type": "create_file", "name": "my file.rs"}"#,
+		r#"{"type": "delete_file", "file_id": 1}"#,
+		r#"{"type": "rename_file", "file_id": 1, "name": "new name.rs"}"#,
+		r#"{"type": "move_file", "file_id": 1, "folder_id": null}"#,
+		r#"{"type": "get_file_content", "file_id": 1}"#,
+		r#"{"type": "change_in_file", "file_id": 1, "start": {"row": 0, "column": 1}, "end": {"row": 0, "column": 1}, "lines": ["a"], "revision": 3, "client_change_id": 7}"#,
+		r#"{"type": "offline_changes", "file_id": 1, "revision": 2, "changes": [{"start": {"row": 0, "column": 0}, "end": {"row": 0, "column": 0}, "lines": ["a"]}]}"#,
+		r#"{"type": "file_checksum", "file_id": 1, "revision": 2, "checksum": "abc"}"#,
+		r#"{"type": "presence", "file_id": 1, "cursor": {"row": 1, "column": 2}}"#,
+		r#"{"type": "follow", "session_id": 5}"#,
+		r#"{"type": "chat_message", "content": "hello @owner"}"#,
+		r#"{"type": "get_chat_history"}"#,
+		r#"{"type": "create_comment_thread", "file_id": 1, "start": {"row": 0, "column": 0}, "end": {"row": 0, "column": 3}, "content": "why?"}"#,
+		r#"{"type": "add_comment", "thread_id": 1, "content": "because"}"#,
+		r#"{"type": "resolve_comment_thread", "thread_id": 1, "resolved": true}"#,
+		r#"{"type": "suggest_change", "file_id": 1, "start": {"row": 0, "column": 0}, "end": {"row": 0, "column": 3}, "lines": ["b"]}"#,
+		r#"{"type": "review_suggestion", "suggestion_id": 1, "accept": false}"#,
+		r#"{"type": "crdt_operations", "file_id": 1, "operations": [{"type": "insert", "id": {"counter": 1, "site": 2}, "after": null, "value": "a"}, {"type": "delete", "id": {"counter": 1, "site": 2}}]}"#,
+		r#"{"type": "create_folder", "name": "my folder"}"#,
+		r#"{"type": "rename_folder", "folder_id": 2, "name": "other folder"}"#,
+		r#"{"type": "move_folder", "folder_id": 2, "parent_id": 3}"#,
+		r#"{"type": "delete_folder", "folder_id": 2}"#,
+		r#"{"type": "change_in_file", "file_id": 1, "start": {"row": 0, "column": 1}, "end": {"row": 0, "column": 1}, "lines": ["a"], "last_change_applied_id": 3}"#,
+		r#"{"type": "create_file", "name": "main.rs", "folder_id": 4}"#,
+	];
+
+	/// Name of enum variant for `type` tag of message
+	fn variant_name(message_type: &str) -> String {
+		message_type.split('_')
+			.map(|word| word[..1].to_uppercase() + &word[1..])
+			.collect()
+	}
+
+	fn message_type(json: &str) -> String {
+		let value: serde_json::Value = serde_json::from_str(json).unwrap();
+		value["type"].as_str().unwrap().to_owned()
+	}
+
+	/// One message of every type sent by server
+	fn outgoing_messages() -> Vec<OutgoingMessage> {
+		let project = Project {
+			id: Some(1),
+			name: "my project".to_owned(),
+			description: String::new(),
+			owner: user(),
+		};
+		let thread = CommentThread {
+			id: 1,
+			file_id: 1,
+			start: position(0, 0),
+			end: position(0, 3),
+			resolved: false,
+			comments: vec![Comment { id: 1, author: user(), content: "why?".to_owned(), sent_at: 1000 }],
+		};
+		let suggestion = Suggestion {
+			id: 1,
+			file_id: 1,
+			author: user(),
+			start: position(0, 0),
+			end: position(0, 3),
+			lines: vec!["b".to_owned()],
+		};
+		let chat_message = || ChatMessageDto::new(ChatMessage {
+			id: 1,
+			author: user(),
+			content: "hello @owner".to_owned(),
+			sent_at: 1000,
+			mentions: vec![1],
+		}, 1);
+		let presence = SessionPresence {
+			session_id: 2,
+			file_id: Some(1),
+			cursor: Some(position(1, 2)),
+			selections: vec![Selection { start: position(0, 0), end: position(1, 0) }],
+			viewport: Some(Viewport { first_row: 0, last_row: 40 }),
+		};
+		vec![
+			OutgoingMessage::Welcome { version: PROTOCOL_VERSION, session_id: 2, resume_token: "token".to_owned(), resumed: false },
+			OutgoingMessage::ProjectInfo(ProjectInfoDto {
+				project: project.clone(),
+				files: vec![FileTreeNode::Folder {
+					id: 1,
+					name: "src".to_owned(),
+					children: vec![FileTreeNode::File { id: 1, name: "main file.rs".to_owned() }],
+				}],
+				sessions: vec![SessionDataDto {
+					id: 2,
+					name: "owner".to_owned(),
+					active_file_id: None,
+					leader_id: None,
+					role: ProjectRole::Owner,
+					presence: None,
+				}],
+			}),
+			OutgoingMessage::ProjectUpdated { project },
+			OutgoingMessage::SessionJoined { session_id: 2, name: "owner".to_owned() },
+			OutgoingMessage::SessionLeft { session_id: 2 },
+			OutgoingMessage::ActiveFileChanged { session_id: 2, file_id: Some(1) },
+			OutgoingMessage::FollowChanged { session_id: 2, leader_id: None },
+			OutgoingMessage::LeaderFileChanged { leader_id: 3, file_id: Some(1) },
+			OutgoingMessage::FileCreated { file_id: 1, name: "my file.rs".to_owned(), folder_id: Some(2) },
+			OutgoingMessage::FileDeleted { file_id: 1 },
+			OutgoingMessage::FileRenamed { file_id: 1, name: "new name.rs".to_owned() },
+			OutgoingMessage::FileMoved { file_id: 1, folder_id: None },
+			OutgoingMessage::FileContent { file_id: 1, content: "fn main() {}\n".to_owned(), revision: 3 },
+			OutgoingMessage::CrdtState { file_id: 1, document: CrdtDocument::from_text("ab", 0), revision: 3 },
+			OutgoingMessage::ChangeInFile { file_id: 1, start: position(0, 1), end: position(0, 2), revision: 4, lines: vec!["a".to_owned(), "b".to_owned()] },
+			OutgoingMessage::ChangeAcknowledged { file_id: 1, revision: 4, client_change_id: Some(7) },
+			OutgoingMessage::OfflineChangesResult(OfflineChangesResult {
+				file_id: 1,
+				merged: true,
+				conflicts: vec![ChangeConflict { client_change_id: Some(7), revision: 5, start: position(0, 0), end: position(0, 1), session_ids: vec![3] }],
+			}),
+			OutgoingMessage::FileChecksum { file_id: 1, revision: 4, checksum: "abc".to_owned() },
+			OutgoingMessage::CommentThreads { threads: vec![thread.clone()] },
+			OutgoingMessage::CommentThreadChanged(thread),
+			OutgoingMessage::Suggestions { suggestions: vec![suggestion.clone()] },
+			OutgoingMessage::SuggestionCreated(suggestion),
+			OutgoingMessage::SuggestionReviewed { suggestion_id: 1, accepted: true },
+			OutgoingMessage::ChatMessage(chat_message()),
+			OutgoingMessage::ChatHistory { messages: vec![chat_message()] },
+			OutgoingMessage::Presence(presence),
+			OutgoingMessage::CrdtOperations(CrdtOperationsApplied {
+				file_id: 1,
+				operations: vec![Operation::Delete { id: ElementId { counter: 1, site: -1 } }],
+			}),
+			OutgoingMessage::FolderChanged { folder: Folder { id: Some(2), name: "my folder".to_owned(), project_id: 1, parent_id: None } },
+			OutgoingMessage::FolderDeleted { folder_id: 2 },
+			OutgoingMessage::Error { message: "File does not exist".to_owned() },
+		]
+	}
+
+	fn encode_json(message: &OutgoingMessage) -> serde_json::Value {
+		match Protocol::Json.encode(message).unwrap().as_slice() {
+			[Frame::Text(text)] => serde_json::from_str(text).unwrap(),
+			_ => panic!("JSON message was not encoded as one text frame")
+		}
+	}
+
+	#[test]
+	fn negotiation_chooses_first_supported_subprotocol() {
+		assert_eq!(Protocol::negotiate(None), Protocol::Legacy);
+		assert_eq!(Protocol::negotiate(Some("chat, superchat")), Protocol::Legacy);
+		assert_eq!(Protocol::negotiate(Some(JSON_SUBPROTOCOL)), Protocol::Json);
+		assert_eq!(Protocol::negotiate(Some("chat, online-editor.v1.cbor, online-editor.v1.json")), Protocol::Cbor);
+		assert_eq!(Protocol::negotiate(Some("online-editor.v1.msgpack,online-editor.v1.json")), Protocol::MessagePack);
+		assert_eq!(Protocol::negotiate(Some("online-editor.v2.json")), Protocol::Legacy);
+	}
+
+	#[test]
+	fn every_incoming_message_is_decoded_from_json() {
+		for json in INCOMING_MESSAGES.iter() {
+			let message = Protocol::Json.decode_text(json).unwrap_or_else(|err| panic!("{} was not decoded: {}", json, err));
+			assert!(format!("{:?}", message).starts_with(&variant_name(&message_type(json))), "{} decoded as {:?}", json, message);
+		}
+	}
+
+	#[test]
+	fn names_with_spaces_are_decoded_whole() {
+		match Protocol::Json.decode_text(INCOMING_MESSAGES[2]).unwrap() {
+			IncomingMessage::RenameFile { file_id, name } => {
+				assert_eq!(file_id, 1);
+				assert_eq!(name, "new name.rs");
+			}
+			message => panic!("Decoded as {:?}", message)
+		}
+	}
+
+	#[test]
+	fn optional_fields_of_json_messages_have_defaults() {
+		match Protocol::Json.decode_text(INCOMING_MESSAGES[0]).unwrap() {
+			IncomingMessage::CreateFile { name, folder_id } => {
+				assert_eq!(name, "my file.rs");
+				assert_eq!(folder_id, None);
+			}
+			message => panic!("Decoded as {:?}", message)
+		}
+		match Protocol::Json.decode_text(INCOMING_MESSAGES[22]).unwrap() {
+			IncomingMessage::ChangeInFile { revision, client_change_id, .. } => {
+				assert_eq!(revision, Some(3));
+				assert_eq!(client_change_id, None);
+			}
+			message => panic!("Decoded as {:?}", message)
+		}
+	}
+
+	#[test]
+	fn unknown_and_malformed_json_messages_are_rejected() {
+		assert!(Protocol::Json.decode_text(r#"{"type": "format_disk"}"#).is_err());
+		assert!(Protocol::Json.decode_text(r#"{"type": "delete_file"}"#).is_err());
+		assert!(Protocol::Json.decode_text("2 1").is_err());
+	}
+
+	#[test]
+	fn every_outgoing_message_is_encoded_with_its_type() {
+		let types: Vec<String> = outgoing_messages().iter()
+			.map(|message| encode_json(message)["type"].as_str().unwrap().to_owned())
+			.collect();
+		assert_eq!(types, vec!["welcome", "project_info", "project_updated", "session_joined", "session_left",
+			"active_file_changed", "follow_changed", "leader_file_changed", "file_created", "file_deleted", "file_renamed",
+			"file_moved", "file_content", "crdt_state", "change_in_file", "change_acknowledged", "offline_changes_result",
+			"file_checksum", "comment_threads", "comment_thread_changed", "suggestions", "suggestion_created",
+			"suggestion_reviewed", "chat_message", "chat_history", "presence", "crdt_operations", "folder_changed",
+			"folder_deleted", "error"]);
+	}
+
+	#[test]
+	fn outgoing_message_fields_are_encoded_next_to_type() {
+		let renamed = encode_json(&OutgoingMessage::FileRenamed { file_id: 1, name: "new name.rs".to_owned() });
+		assert_eq!(renamed, serde_json::json!({"type": "file_renamed", "file_id": 1, "name": "new name.rs"}));
+		let chat_message = encode_json(&outgoing_messages()[23]);
+		assert_eq!(chat_message["content"], "hello @owner");
+		assert_eq!(chat_message["mentions_you"], true);
+	}
+
+	/// Encodes message sent by client in binary protocol
+	fn encode_incoming(protocol: Protocol, json: &str) -> Vec<u8> {
+		let value: serde_json::Value = serde_json::from_str(json).unwrap();
+		match protocol {
+			Protocol::MessagePack => rmp_serde::to_vec_named(&value).unwrap(),
+			Protocol::Cbor => {
+				let mut bytes = Vec::new();
+				ciborium::ser::into_writer(&value, &mut bytes).unwrap();
+				bytes
+			}
+			_ => panic!("{:?} is not binary protocol", protocol)
+		}
+	}
+
+	/// Decodes message encoded by server in binary protocol
+	fn decode_outgoing(protocol: Protocol, frames: Vec<Frame>) -> serde_json::Value {
+		let bytes = match frames.as_slice() {
+			[Frame::Binary(bytes)] => bytes.clone(),
+			_ => panic!("Message was not encoded as one binary frame")
+		};
+		match protocol {
+			Protocol::MessagePack => rmp_serde::from_slice(&bytes).unwrap(),
+			Protocol::Cbor => ciborium::de::from_reader(bytes.as_slice()).unwrap(),
+			_ => panic!("{:?} is not binary protocol", protocol)
+		}
+	}
+
+	#[test]
+	fn incoming_messages_survive_binary_round_trip() {
+		for protocol in [Protocol::MessagePack, Protocol::Cbor].iter() {
+			for json in INCOMING_MESSAGES.iter() {
+				let message = protocol.decode_binary(&encode_incoming(*protocol, json))
+					.unwrap_or_else(|err| panic!("{} was not decoded in {:?}: {}", json, protocol, err));
+				assert_eq!(format!("{:?}", message), format!("{:?}", Protocol::Json.decode_text(json).unwrap()), "{:?}", protocol);
+			}
+		}
+	}
+
+	#[test]
+	fn outgoing_messages_survive_binary_round_trip() {
+		for protocol in [Protocol::MessagePack, Protocol::Cbor].iter() {
+			for message in outgoing_messages().iter() {
+				let decoded = decode_outgoing(*protocol, protocol.encode(message).unwrap());
+				assert_eq!(decoded, encode_json(message), "{:?}", protocol);
+			}
+			let event = decode_outgoing(*protocol, protocol.encode_event(12, &OutgoingMessage::FileDeleted { file_id: 1 }).unwrap());
+			assert_eq!(event, serde_json::json!({"event_id": 12, "type": "file_deleted", "file_id": 1}));
+		}
+	}
+
+	#[test]
+	fn frames_of_other_kind_than_protocol_are_rejected() {
+		assert!(Protocol::MessagePack.decode_text(INCOMING_MESSAGES[1]).is_err());
+		assert!(Protocol::Cbor.decode_text(INCOMING_MESSAGES[1]).is_err());
+		let bytes = encode_incoming(Protocol::MessagePack, INCOMING_MESSAGES[1]);
+		assert!(Protocol::Json.decode_binary(&bytes).is_err());
+		assert!(Protocol::Legacy.decode_binary(&bytes).is_err());
+		// MessagePack and CBOR are not interchangeable
+		assert!(Protocol::Cbor.decode_binary(&bytes).is_err());
+	}
+
+	#[test]
+	fn events_have_their_id() {
+		let message = OutgoingMessage::FileDeleted { file_id: 1 };
+		match Protocol::Json.encode_event(12, &message).unwrap().as_slice() {
+			[Frame::Text(text)] => assert_eq!(serde_json::from_str::<serde_json::Value>(text).unwrap(),
+				serde_json::json!({"event_id": 12, "type": "file_deleted", "file_id": 1})),
+			_ => panic!("Event was not encoded as one text frame")
+		}
+		match Protocol::Legacy.encode_event(12, &message).unwrap().as_slice() {
+			[Frame::Text(text)] => assert_eq!(text, "41"),
+			_ => panic!("Legacy event was not encoded as one text frame")
+		}
+	}
+}
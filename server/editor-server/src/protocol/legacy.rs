@@ -0,0 +1,358 @@
+/// Legacy protocol: every message starts with one character code followed by fields separated by
+/// spaces. Folder id 0 means root of project.
+use serde::Deserialize;
+use crate::crdt::Operation;
+use crate::editor_session::Position;
+use crate::protocol::{IncomingMessage, OutgoingMessage, DecodeError, EncodeError};
+
+const INCOMING_CODE_NEW_FILE: &str = "1";
+const INCOMING_CODE_DELETE_FILE: &str = "2";
+const INCOMING_CODE_RENAME_FILE: &str = "3";
+const INCOMING_CODE_GET_FILE_CONTENT: &str = "4";
+const INCOMING_CODE_CHANGE_IN_FILE: &str = "5";
+const INCOMING_CODE_CRDT_OPERATIONS: &str = "6";
+const INCOMING_CODE_NEW_FOLDER: &str = "7";
+const INCOMING_CODE_RENAME_FOLDER: &str = "8";
+const INCOMING_CODE_MOVE_FOLDER: &str = "9";
+const INCOMING_CODE_DELETE_FOLDER: &str = "a";
+const INCOMING_CODE_MOVE_FILE: &str = "b";
+const INCOMING_CODE_NEW_FILE_IN_FOLDER: &str = "c";
+const INCOMING_CODE_FILE_CHECKSUM: &str = "d";
+const INCOMING_CODE_OFFLINE_CHANGES: &str = "e";
+
+#[derive(Deserialize)]
+struct CrdtOperationsDto {
+	file_id: i32,
+	operations: Vec<Operation>,
+}
+
+pub fn decode(message: &str) -> Result<IncomingMessage, DecodeError> {
+	if message.len() <= 1 {
+		return Err(DecodeError("Message with only code and no content".to_owned()));
+	}
+	if !message.is_char_boundary(1) {
+		return Err(DecodeError("Message does not start with code".to_owned()));
+	}
+	let (incoming_code, incoming_message) = message.split_at(1);
+	match incoming_code {
+		INCOMING_CODE_NEW_FILE => Ok(IncomingMessage::CreateFile {
+			name: incoming_message.to_owned(),
+			folder_id: None,
+		}),
+		INCOMING_CODE_NEW_FILE_IN_FOLDER => {
+			let (folder_id, name) = split_id_and_argument(incoming_message)?;
+			Ok(IncomingMessage::CreateFile {
+				name: name.to_owned(),
+				folder_id: to_folder_id(folder_id),
+			})
+		}
+		INCOMING_CODE_DELETE_FILE => Ok(IncomingMessage::DeleteFile {
+			file_id: parse_id(incoming_message)?
+		}),
+		INCOMING_CODE_RENAME_FILE => {
+			let (file_id, name) = split_id_and_argument(incoming_message)?;
+			Ok(IncomingMessage::RenameFile {
+				file_id,
+				name: name.to_owned(),
+			})
+		}
+		INCOMING_CODE_MOVE_FILE => {
+			let (file_id, folder_id) = split_id_and_argument(incoming_message)?;
+			Ok(IncomingMessage::MoveFile {
+				file_id,
+				folder_id: to_folder_id(parse_id(folder_id)?),
+			})
+		}
+		INCOMING_CODE_GET_FILE_CONTENT => Ok(IncomingMessage::GetFileContent {
+			file_id: parse_id(incoming_message)?
+		}),
+		INCOMING_CODE_CHANGE_IN_FILE => decode_change_in_file(incoming_message),
+		INCOMING_CODE_CRDT_OPERATIONS => {
+			let dto: CrdtOperationsDto = serde_json::from_str(incoming_message)
+				.map_err(|err| DecodeError(format!("Unparsable CRDT operations: {}", err)))?;
+			Ok(IncomingMessage::CrdtOperations {
+				file_id: dto.file_id,
+				operations: dto.operations,
+			})
+		}
+		INCOMING_CODE_NEW_FOLDER => {
+			let (parent_id, name) = split_id_and_argument(incoming_message)?;
+			Ok(IncomingMessage::CreateFolder {
+				name: name.to_owned(),
+				parent_id: to_folder_id(parent_id),
+			})
+		}
+		INCOMING_CODE_RENAME_FOLDER => {
+			let (folder_id, name) = split_id_and_argument(incoming_message)?;
+			Ok(IncomingMessage::RenameFolder {
+				folder_id,
+				name: name.to_owned(),
+			})
+		}
+		INCOMING_CODE_MOVE_FOLDER => {
+			let (folder_id, parent_id) = split_id_and_argument(incoming_message)?;
+			Ok(IncomingMessage::MoveFolder {
+				folder_id,
+				parent_id: to_folder_id(parse_id(parent_id)?),
+			})
+		}
+		INCOMING_CODE_DELETE_FOLDER => Ok(IncomingMessage::DeleteFolder {
+			folder_id: parse_id(incoming_message)?
+		}),
+		INCOMING_CODE_FILE_CHECKSUM => decode_file_checksum(incoming_message),
+		INCOMING_CODE_OFFLINE_CHANGES => serde_json::from_str(incoming_message)
+			.map(IncomingMessage::OfflineChanges)
+			.map_err(|err| DecodeError(format!("Unparsable offline changes: {}", err))),
+		_ => Err(DecodeError(format!("Unknown message code: {}", incoming_code)))
+	}
+}
+
+/// Encodes message as legacy text frames
+pub fn encode(message: &OutgoingMessage) -> Result<Vec<String>, EncodeError> {
+	let frames = match message {
+		OutgoingMessage::Welcome { .. } | OutgoingMessage::Presence(_) | OutgoingMessage::ActiveFileChanged { .. }
+		| OutgoingMessage::FollowChanged { .. } | OutgoingMessage::LeaderFileChanged { .. }
+		| OutgoingMessage::ChatMessage(_) | OutgoingMessage::ChatHistory { .. }
+		| OutgoingMessage::CommentThreads { .. } | OutgoingMessage::CommentThreadChanged(_)
+		| OutgoingMessage::Suggestions { .. } | OutgoingMessage::SuggestionCreated(_) | OutgoingMessage::SuggestionReviewed { .. } => Vec::new(),
+		OutgoingMessage::SessionJoined { session_id, name } => vec![format!("1{} {}", session_id, name)],
+		OutgoingMessage::SessionLeft { session_id } => vec![format!("2{}", session_id)],
+		OutgoingMessage::FileCreated { file_id, name, folder_id } => {
+			let mut frames = vec![format!("3{} {}", file_id, name)];
+			if let Some(folder_id) = folder_id {
+				frames.push(format!("e{} {}", file_id, folder_id));
+			}
+			frames
+		}
+		OutgoingMessage::FileDeleted { file_id } => vec![format!("4{}", file_id)],
+		OutgoingMessage::FileContent { file_id, content, .. } => vec![format!("5{} {}", file_id, content)],
+		OutgoingMessage::ChangeInFile { file_id, start, end, revision, lines } => {
+			vec![format!("6{} {} {} {} {} {} {}", file_id, start.row, start.column, end.row, end.column, revision, lines.join("\n"))]
+		}
+		OutgoingMessage::ChangeAcknowledged { file_id, revision, .. } => vec![format!("f{} {}", file_id, revision)],
+		OutgoingMessage::FileChecksum { file_id, revision, checksum } => vec![format!("g{} {} {}", file_id, revision, checksum)],
+		OutgoingMessage::OfflineChangesResult(result) => vec![format!("h{}", serde_json::to_string(result)?)],
+		OutgoingMessage::FileRenamed { file_id, name } => vec![format!("7{} {}", file_id, name)],
+		OutgoingMessage::ProjectUpdated { project } => vec![format!("8{}", serde_json::to_string(project)?)],
+		OutgoingMessage::ProjectInfo(project_info) => vec![format!("9{}", serde_json::to_string(project_info)?)],
+		OutgoingMessage::Error { message } => vec![format!("a{}", message)],
+		OutgoingMessage::CrdtOperations(operations) => vec![format!("b{}", serde_json::to_string(operations)?)],
+		OutgoingMessage::FolderChanged { folder } => vec![format!("c{}", serde_json::to_string(folder)?)],
+		OutgoingMessage::FolderDeleted { folder_id } => vec![format!("d{}", folder_id)],
+		OutgoingMessage::FileMoved { file_id, folder_id } => vec![format!("e{} {}", file_id, folder_id.unwrap_or(0))],
+		OutgoingMessage::CrdtState { file_id, document, revision } => vec![format!("i{} {} {}", file_id, revision, serde_json::to_string(document)?)],
+	};
+	Ok(frames)
+}
+
+/// Decodes change in form "{file_id} {start_row} {start_column} {end_row} {end_column} {revision} {text}"
+fn decode_change_in_file(message: &str) -> Result<IncomingMessage, DecodeError> {
+	let splitted_message: Vec<&str> = message.splitn(7, " ").collect();
+	if splitted_message.len() != 7 {
+		return Err(DecodeError("Illegal change in file package sent: could not split in 7 required parts".to_owned()));
+	}
+	let file_id = parse_id(splitted_message[0])?;
+	let start = convert_to_position([splitted_message[1], splitted_message[2]])?;
+	let end = convert_to_position([splitted_message[3], splitted_message[4]])?;
+	let revision = splitted_message[5].parse::<usize>().ok();
+	Ok(IncomingMessage::ChangeInFile {
+		file_id,
+		start,
+		end,
+		lines: splitted_message[6].split("\n").map(|str_ref| { return str_ref.to_owned(); }).collect(),
+		revision,
+		client_change_id: None,
+	})
+}
+
+/// Decodes checksum in form "{file_id} {revision} {checksum}"
+fn decode_file_checksum(message: &str) -> Result<IncomingMessage, DecodeError> {
+	let splitted_message: Vec<&str> = message.splitn(3, " ").collect();
+	if splitted_message.len() != 3 {
+		return Err(DecodeError("Illegal file checksum package sent: could not split in 3 required parts".to_owned()));
+	}
+	let revision = splitted_message[1].parse::<usize>()
+		.map_err(|_| DecodeError(format!("Unparsable revision: {}", splitted_message[1])))?;
+	Ok(IncomingMessage::FileChecksum {
+		file_id: parse_id(splitted_message[0])?,
+		revision,
+		checksum: splitted_message[2].to_owned(),
+	})
+}
+
+/// Tries to convert array of 2 strings into Position.
+/// # Params
+///
+///  * `positions_as_strings` - Position represented as array of strings. First string is row and second is column
+///
+/// # Returns
+///
+/// Position if both of strings were parsable to u32, Err otherwise
+fn convert_to_position(positions_as_strings: [&str; 2]) -> Result<Position, DecodeError> {
+	let row;
+	match positions_as_strings[0].parse::<u32>() {
+		Ok(n) => row = n,
+		Err(_) => {
+			return Err(DecodeError(format!("Unparsable row: {}", positions_as_strings[0])));
+		}
+	}
+	return match positions_as_strings[1].parse::<u32>() {
+		Ok(column) => {
+			Ok(Position {
+				row,
+				column,
+			})
+		}
+		Err(_) => {
+			return Err(DecodeError(format!("Unparsable column: {}", positions_as_strings[1])));
+		}
+	};
+}
+
+fn parse_id(text: &str) -> Result<i32, DecodeError> {
+	text.parse::<i32>().map_err(|_| DecodeError(format!("Unparsable id: {}", text)))
+}
+
+/// Splits message in form "{id} {argument}" used by file and folder requests
+fn split_id_and_argument(message: &str) -> Result<(i32, &str), DecodeError> {
+	let id_and_argument: Vec<&str> = message.splitn(2, " ").collect();
+	if id_and_argument.len() != 2 {
+		return Err(DecodeError("Request without argument after id".to_owned()));
+	}
+	Ok((parse_id(id_and_argument[0])?, id_and_argument[1]))
+}
+
+/// Folder id 0 means root of project
+fn to_folder_id(id: i32) -> Option<i32> {
+	if id == 0 { None } else { Some(id) }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::crdt::CrdtDocument;
+
+	fn position(row: u32, column: u32) -> Position {
+		Position { row, column }
+	}
+
+	fn encode_one(message: OutgoingMessage) -> String {
+		let mut frames = encode(&message).unwrap();
+		assert_eq!(frames.len(), 1);
+		frames.remove(0)
+	}
+
+	#[test]
+	fn file_requests_keep_spaces_in_names() {
+		match decode("1my file.rs").unwrap() {
+			IncomingMessage::CreateFile { name, folder_id } => assert_eq!((name.as_str(), folder_id), ("my file.rs", None)),
+			message => panic!("Decoded as {:?}", message)
+		}
+		match decode("c3 my file.rs").unwrap() {
+			IncomingMessage::CreateFile { name, folder_id } => assert_eq!((name.as_str(), folder_id), ("my file.rs", Some(3))),
+			message => panic!("Decoded as {:?}", message)
+		}
+		match decode("37 new name .rs").unwrap() {
+			IncomingMessage::RenameFile { file_id, name } => assert_eq!((file_id, name.as_str()), (7, "new name .rs")),
+			message => panic!("Decoded as {:?}", message)
+		}
+		match decode("80 my folder").unwrap() {
+			IncomingMessage::RenameFolder { folder_id, name } => assert_eq!((folder_id, name.as_str()), (0, "my folder")),
+			message => panic!("Decoded as {:?}", message)
+		}
+		match decode("70 new folder").unwrap() {
+			IncomingMessage::CreateFolder { name, parent_id } => assert_eq!((name.as_str(), parent_id), ("new folder", None)),
+			message => panic!("Decoded as {:?}", message)
+		}
+	}
+
+	#[test]
+	fn id_requests_are_decoded() {
+		assert!(matches!(decode("25").unwrap(), IncomingMessage::DeleteFile { file_id: 5 }));
+		assert!(matches!(decode("45").unwrap(), IncomingMessage::GetFileContent { file_id: 5 }));
+		assert!(matches!(decode("a5").unwrap(), IncomingMessage::DeleteFolder { folder_id: 5 }));
+		assert!(matches!(decode("b5 0").unwrap(), IncomingMessage::MoveFile { file_id: 5, folder_id: None }));
+		assert!(matches!(decode("b5 2").unwrap(), IncomingMessage::MoveFile { file_id: 5, folder_id: Some(2) }));
+		assert!(matches!(decode("95 2").unwrap(), IncomingMessage::MoveFolder { folder_id: 5, parent_id: Some(2) }));
+	}
+
+	#[test]
+	fn change_in_file_is_decoded_with_multiline_text() {
+		match decode("51 0 2 1 4 3 hello world\nsecond line").unwrap() {
+			IncomingMessage::ChangeInFile { file_id, start, end, lines, revision, client_change_id } => {
+				assert_eq!(file_id, 1);
+				assert_eq!((start, end), (position(0, 2), position(1, 4)));
+				assert_eq!(lines, vec!["hello world", "second line"]);
+				assert_eq!(revision, Some(3));
+				assert_eq!(client_change_id, None);
+			}
+			message => panic!("Decoded as {:?}", message)
+		}
+		// Old clients send text instead of revision
+		assert!(matches!(decode("51 0 0 0 0 null a").unwrap(), IncomingMessage::ChangeInFile { revision: None, .. }));
+		assert!(decode("51 0 0 0 a").is_err());
+		assert!(decode("51 x 0 0 0 1 a").is_err());
+	}
+
+	#[test]
+	fn json_requests_are_decoded() {
+		match decode(r#"6{"file_id": 1, "operations": [{"type": "delete", "id": {"counter": 2, "site": 3}}]}"#).unwrap() {
+			IncomingMessage::CrdtOperations { file_id, operations } => assert_eq!((file_id, operations.len()), (1, 1)),
+			message => panic!("Decoded as {:?}", message)
+		}
+		match decode(r#"e{"file_id": 1, "changes": []}"#).unwrap() {
+			IncomingMessage::OfflineChanges(changes) => assert_eq!((changes.file_id, changes.revision), (1, None)),
+			message => panic!("Decoded as {:?}", message)
+		}
+		match decode("d1 4 abc").unwrap() {
+			IncomingMessage::FileChecksum { file_id, revision, checksum } => assert_eq!((file_id, revision, checksum.as_str()), (1, 4, "abc")),
+			message => panic!("Decoded as {:?}", message)
+		}
+	}
+
+	#[test]
+	fn malformed_messages_are_rejected() {
+		assert!(decode("").is_err());
+		assert!(decode("1").is_err());
+		assert!(decode("z1").is_err());
+		assert!(decode("ąb").is_err());
+		assert!(decode("3x name").is_err());
+		assert!(decode("37").is_err());
+		assert!(decode("6{not json").is_err());
+	}
+
+	#[test]
+	fn file_events_are_encoded_with_codes() {
+		assert_eq!(encode_one(OutgoingMessage::SessionJoined { session_id: 2, name: "John Smith".to_owned() }), "12 John Smith");
+		assert_eq!(encode_one(OutgoingMessage::SessionLeft { session_id: 2 }), "22");
+		assert_eq!(encode_one(OutgoingMessage::FileCreated { file_id: 1, name: "my file.rs".to_owned(), folder_id: None }), "31 my file.rs");
+		assert_eq!(encode(&OutgoingMessage::FileCreated { file_id: 1, name: "my file.rs".to_owned(), folder_id: Some(2) }).unwrap(),
+			vec!["31 my file.rs", "e1 2"]);
+		assert_eq!(encode_one(OutgoingMessage::FileDeleted { file_id: 1 }), "41");
+		assert_eq!(encode_one(OutgoingMessage::FileRenamed { file_id: 1, name: "new name.rs".to_owned() }), "71 new name.rs");
+		assert_eq!(encode_one(OutgoingMessage::FileMoved { file_id: 1, folder_id: None }), "e1 0");
+		assert_eq!(encode_one(OutgoingMessage::FolderDeleted { folder_id: 2 }), "d2");
+		assert_eq!(encode_one(OutgoingMessage::Error { message: "File does not exist".to_owned() }), "aFile does not exist");
+	}
+
+	#[test]
+	fn content_events_are_encoded_with_codes() {
+		assert_eq!(encode_one(OutgoingMessage::FileContent { file_id: 1, content: "a b\nc".to_owned(), revision: 3 }), "51 a b\nc");
+		assert_eq!(encode_one(OutgoingMessage::ChangeInFile {
+			file_id: 1,
+			start: position(0, 1),
+			end: position(2, 3),
+			revision: 4,
+			lines: vec!["a b".to_owned(), "c".to_owned()],
+		}), "61 0 1 2 3 4 a b\nc");
+		assert_eq!(encode_one(OutgoingMessage::ChangeAcknowledged { file_id: 1, revision: 4, client_change_id: Some(7) }), "f1 4");
+		assert_eq!(encode_one(OutgoingMessage::FileChecksum { file_id: 1, revision: 4, checksum: "abc".to_owned() }), "g1 4 abc");
+		assert!(encode_one(OutgoingMessage::CrdtState { file_id: 1, document: CrdtDocument::from_text("a", 0), revision: 4 }).starts_with("i1 4 {"));
+	}
+
+	#[test]
+	fn messages_without_legacy_code_are_not_sent() {
+		assert!(encode(&OutgoingMessage::Welcome { version: 1, session_id: 2, resume_token: "token".to_owned(), resumed: false }).unwrap().is_empty());
+		assert!(encode(&OutgoingMessage::SuggestionReviewed { suggestion_id: 1, accepted: true }).unwrap().is_empty());
+	}
+}
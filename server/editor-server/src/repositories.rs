@@ -1,34 +1,122 @@
-use postgres::{Client, NoTls};
+use postgres::{Config, NoTls};
+pub use postgres::Transaction;
+use r2d2::{Pool, PooledConnection};
+use r2d2_postgres::PostgresConnectionManager;
+use actix_web::error::BlockingError;
+use once_cell::sync::Lazy;
 use log::error;
 use std::env;
+use std::fmt;
+use std::time::Duration;
 
 pub mod users;
 pub mod projects;
 pub mod projects_files;
+pub mod folders;
 pub mod file_content_repository;
+pub mod crdt_documents;
+pub mod chat_messages;
+pub mod comments;
+pub mod suggestions;
+pub mod file_history;
+pub mod checkpoints;
 
-fn get_client() -> Client {
+const DEFAULT_POOL_SIZE: u32 = 16;
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+type Client = PooledConnection<PostgresConnectionManager<NoTls>>;
+
+/// Connections are opened lazily, so server starts even if database is not available yet
+static POOL: Lazy<Pool<PostgresConnectionManager<NoTls>>> = Lazy::new(|| {
 	let password = env::var("ONLINE_EDITOR_DATABASE_PASSWORD").expect("SET \"ONLINE_EDITOR_DATABASE_PASSWORD\" env variable");
 	let username = env::var("ONLINE_EDITOR_DATABASE_USERNAME").expect("SET \"ONLINE_EDITOR_DATABASE_USERNAME\" env variable");
 	let host = env::var("ONLINE_EDITOR_DATABASE_HOST").expect("SET \"ONLINE_EDITOR_DATABASE_HOST\" env variable");
 	let dbname = env::var("ONLINE_EDITOR_DATABASE_DBNAME").expect("SET \"ONLINE_EDITOR_DATABASE_DBNAME\" env variable");
+	let pool_size = env::var("ONLINE_EDITOR_DATABASE_POOL_SIZE").ok()
+		.and_then(|size| size.parse::<u32>().ok())
+		.unwrap_or(DEFAULT_POOL_SIZE);
 
-	let client = Client::configure()
-		.dbname(&dbname)
+	let mut config = Config::new();
+	config.dbname(&dbname)
 		.password(password)
 		.user(&username)
-		.host(&host)
-		.connect(NoTls);
-	match client {
-		Ok(c) => return c,
-		Err(error) => {
-			error!("Error occured while trying to connect to postgres database: {}", error);
-			panic!(error)
+		.host(&host);
+	Pool::builder()
+		.max_size(pool_size)
+		.min_idle(Some(0))
+		.connection_timeout(CONNECTION_TIMEOUT)
+		.build_unchecked(PostgresConnectionManager::new(config, NoTls))
+});
+
+/// Takes connection from pool. Connection goes back to pool when it is dropped.
+///
+/// This call blocks, so it should be used only inside closures passed to `run`.
+fn get_client() -> Result<Client, DatabaseError> {
+	POOL.get().map_err(|err| {
+		error!("Error occured while trying to connect to postgres database: {}", err);
+		DatabaseError {
+			error_code: DatabaseError::CONNECTION_ERROR,
+			message: err.to_string(),
 		}
-	}
+	})
 }
 
+/// Runs function in one transaction, which is committed only if function succeeds.
+///
+/// This call blocks, so it should be used only inside closures passed to `run`.
+pub fn in_transaction<F, T>(f: F) -> Result<T, DatabaseError>
+	where F: FnOnce(&mut Transaction) -> Result<T, DatabaseError> {
+	let mut client = get_client()?;
+	let mut transaction = client.transaction()?;
+	let result = f(&mut transaction)?;
+	transaction.commit()?;
+	Ok(result)
+}
+
+/// Runs function using database on thread pool, so it does not block actors and request handlers.
+/// Repositories are synchronous, because async postgres clients need newer tokio than actix-web 3
+/// runs on (see README).
+pub async fn run<F, T, E>(f: F) -> Result<T, E>
+	where F: FnOnce() -> Result<T, E> + Send + 'static,
+		  T: Send + 'static,
+		  E: From<DatabaseError> + Send + fmt::Debug + 'static {
+	actix_web::web::block(f).await.map_err(|err| match err {
+		BlockingError::Error(err) => err,
+		BlockingError::Canceled => E::from(DatabaseError {
+			error_code: DatabaseError::CANCELED,
+			message: "Database thread pool is gone".to_owned(),
+		})
+	})
+}
+
+#[derive(Debug)]
 pub struct DatabaseError {
 	pub error_code: i32,
 	pub message: String,
-}
\ No newline at end of file
+}
+
+impl DatabaseError {
+	/// Could not get connection from pool
+	pub const CONNECTION_ERROR: i32 = -1;
+	/// Query failed
+	pub const QUERY_ERROR: i32 = -2;
+	/// Function using database was not run
+	pub const CANCELED: i32 = -3;
+	/// Saved data differs from data which was supposed to be saved again
+	pub const CONFLICT: i32 = -4;
+}
+
+impl From<postgres::Error> for DatabaseError {
+	fn from(err: postgres::Error) -> Self {
+		DatabaseError {
+			error_code: DatabaseError::QUERY_ERROR,
+			message: err.to_string(),
+		}
+	}
+}
+
+impl fmt::Display for DatabaseError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "database error {}: {}", self.error_code, self.message)
+	}
+}
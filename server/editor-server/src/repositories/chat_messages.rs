@@ -0,0 +1,75 @@
+use crate::models::{ChatMessage, User};
+use crate::repositories::{get_client, DatabaseError};
+use log::error;
+use postgres::Row;
+
+/// Stores messages of chat of one project
+pub trait IChatMessagesRepository {
+	/// Saves message and its mentions of project members
+	fn create(&self, author: &User, content: &str) -> Result<ChatMessage, DatabaseError>;
+
+	/// Returns at most `limit` last messages sent before message with given id (or last messages if
+	/// it is None), from the oldest one
+	fn get_last(&self, before_id: Option<i32>, limit: i64) -> Result<Vec<ChatMessage>, DatabaseError>;
+}
+
+pub fn new(project_id: i32) -> Box<dyn IChatMessagesRepository> {
+	Box::new(ChatMessagesRepository { project_id })
+}
+
+struct ChatMessagesRepository {
+	project_id: i32
+}
+
+impl IChatMessagesRepository for ChatMessagesRepository {
+	fn create(&self, author: &User, content: &str) -> Result<ChatMessage, DatabaseError> {
+		let mentioned_names = ChatMessage::mentioned_names(content);
+		let mut client = get_client()?;
+		let mut transaction = client.transaction()?;
+		let row = transaction.query_one("INSERT INTO chat_messages (project_id, author_id, content) VALUES ($1, $2, $3)
+			RETURNING id, (EXTRACT(EPOCH FROM sent_at) * 1000)::BIGINT", &[&self.project_id, &author.id, &content])
+			.map_err(|err| {
+				error!("Failed to save chat message in project {}: {}", self.project_id, err);
+				DatabaseError::from(err)
+			})?;
+		let id: i32 = row.get(0);
+		let mentions = transaction.query("INSERT INTO chat_mentions (message_id, user_id)
+			SELECT $1, id FROM users WHERE rtrim(name) = ANY($2) AND has_access_to_project($3, id)
+			RETURNING user_id", &[&id, &mentioned_names, &self.project_id])?
+			.iter()
+			.map(|row| row.get(0))
+			.collect();
+		transaction.commit()?;
+		Ok(ChatMessage {
+			id,
+			author: User { id: author.id, name: author.name.trim_end().to_owned() },
+			content: content.to_owned(),
+			sent_at: row.get(1),
+			mentions,
+		})
+	}
+
+	fn get_last(&self, before_id: Option<i32>, limit: i64) -> Result<Vec<ChatMessage>, DatabaseError> {
+		let rows = get_client()?
+			.query("SELECT m.id, u.id, rtrim(u.name), m.content, (EXTRACT(EPOCH FROM m.sent_at) * 1000)::BIGINT,
+				ARRAY(SELECT user_id FROM chat_mentions WHERE message_id = m.id)
+			FROM chat_messages m JOIN users u ON u.id = m.author_id
+			WHERE m.project_id = $1 AND ($2::INT IS NULL OR m.id < $2)
+			ORDER BY m.id DESC LIMIT $3", &[&self.project_id, &before_id, &limit])
+			.map_err(|err| {
+				error!("Failed to get chat messages of project {}: {}", self.project_id, err);
+				DatabaseError::from(err)
+			})?;
+		Ok(rows.iter().rev().map(row_to_message).collect())
+	}
+}
+
+fn row_to_message(row: &Row) -> ChatMessage {
+	ChatMessage {
+		id: row.get(0),
+		author: User { id: row.get(1), name: row.get(2) },
+		content: row.get(3),
+		sent_at: row.get(4),
+		mentions: row.get(5),
+	}
+}
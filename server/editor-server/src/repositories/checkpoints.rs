@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use crate::models::{Checkpoint, CheckpointFile, User};
+use crate::repositories::{get_client, DatabaseError};
+use log::error;
+use postgres::Row;
+
+/// Stores checkpoints of one project
+pub trait ICheckpointsRepository {
+	/// Creates checkpoint with given revisions of files. Keys are file ids.
+	fn create(&self, name: &str, author: &User, revisions: &HashMap<i32, i32>) -> Result<Checkpoint, DatabaseError>;
+
+	/// Returns all checkpoints of project, from the newest
+	fn get_all(&self) -> Result<Vec<Checkpoint>, DatabaseError>;
+
+	/// Returns None if checkpoint does not exist in project
+	fn get(&self, checkpoint_id: i32) -> Result<Option<Checkpoint>, DatabaseError>;
+}
+
+pub fn new(project_id: i32) -> Box<dyn ICheckpointsRepository> {
+	Box::new(CheckpointsRepository { project_id })
+}
+
+struct CheckpointsRepository {
+	project_id: i32
+}
+
+impl ICheckpointsRepository for CheckpointsRepository {
+	fn create(&self, name: &str, author: &User, revisions: &HashMap<i32, i32>) -> Result<Checkpoint, DatabaseError> {
+		let mut client = get_client()?;
+		let mut transaction = client.transaction()?;
+		let row = transaction.query_one("INSERT INTO checkpoints (project_id, name, author_id) VALUES ($1, $2, $3)
+			RETURNING id, (EXTRACT(EPOCH FROM created_at) * 1000)::BIGINT", &[&self.project_id, &name, &author.id])
+			.map_err(|err| {
+				error!("Failed to create checkpoint in project {}: {}", self.project_id, err);
+				DatabaseError::from(err)
+			})?;
+		let id: i32 = row.get(0);
+		let file_ids: Vec<i32> = revisions.keys().cloned().collect();
+		let file_revisions: Vec<i32> = file_ids.iter().map(|file_id| revisions[file_id]).collect();
+		// Files deleted in the meantime are skipped
+		let files = transaction.query("INSERT INTO checkpoints_files (checkpoint_id, file_id, revision)
+			SELECT $1, f.id, r.revision FROM unnest($2::INT[], $3::INT[]) AS r(file_id, revision)
+			JOIN files f ON f.id = r.file_id AND f.project_id = $4
+			RETURNING file_id, revision", &[&id, &file_ids, &file_revisions, &self.project_id])?
+			.iter()
+			.map(|row| CheckpointFile {
+				file_id: row.get(0),
+				revision: row.get(1),
+			})
+			.collect();
+		transaction.commit()?;
+		Ok(Checkpoint {
+			id,
+			name: name.to_owned(),
+			author: User { id: author.id, name: author.name.trim_end().to_owned() },
+			created_at: row.get(1),
+			files,
+		})
+	}
+
+	fn get_all(&self) -> Result<Vec<Checkpoint>, DatabaseError> {
+		self.query_checkpoints(None)
+	}
+
+	fn get(&self, checkpoint_id: i32) -> Result<Option<Checkpoint>, DatabaseError> {
+		Ok(self.query_checkpoints(Some(checkpoint_id))?.pop())
+	}
+}
+
+impl CheckpointsRepository {
+	/// Returns all checkpoints of project or only the one with given id
+	fn query_checkpoints(&self, checkpoint_id: Option<i32>) -> Result<Vec<Checkpoint>, DatabaseError> {
+		let mut client = get_client()?;
+		let mut checkpoints: Vec<Checkpoint> = client
+			.query("SELECT c.id, c.name, u.id, rtrim(u.name), (EXTRACT(EPOCH FROM c.created_at) * 1000)::BIGINT
+			FROM checkpoints c JOIN users u ON u.id = c.author_id
+			WHERE c.project_id = $1 AND ($2::INT IS NULL OR c.id = $2) ORDER BY c.id DESC", &[&self.project_id, &checkpoint_id])
+			.map_err(|err| {
+				error!("Failed to get checkpoints of project {}: {}", self.project_id, err);
+				DatabaseError::from(err)
+			})?
+			.iter()
+			.map(row_to_checkpoint)
+			.collect();
+		let mut files: HashMap<i32, Vec<CheckpointFile>> = HashMap::new();
+		for row in client.query("SELECT cf.checkpoint_id, cf.file_id, cf.revision
+			FROM checkpoints_files cf JOIN checkpoints c ON c.id = cf.checkpoint_id
+			WHERE c.project_id = $1 AND ($2::INT IS NULL OR c.id = $2) ORDER BY cf.file_id", &[&self.project_id, &checkpoint_id])? {
+			files.entry(row.get(0)).or_default().push(CheckpointFile {
+				file_id: row.get(1),
+				revision: row.get(2),
+			});
+		}
+		checkpoints.iter_mut().for_each(|checkpoint| checkpoint.files = files.remove(&checkpoint.id).unwrap_or_default());
+		Ok(checkpoints)
+	}
+}
+
+fn row_to_checkpoint(row: &Row) -> Checkpoint {
+	Checkpoint {
+		id: row.get(0),
+		name: row.get(1),
+		author: User { id: row.get(2), name: row.get(3) },
+		created_at: row.get(4),
+		files: Vec::new(),
+	}
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use crate::editor_session::Position;
+use crate::models::{Comment, CommentThread, User};
+use crate::repositories::{get_client, DatabaseError};
+use log::error;
+use postgres::Row;
+
+/// Stores comment threads in files of one project
+pub trait ICommentsRepository {
+	/// Returns all threads in files of project with their comments
+	fn get_threads(&self) -> Result<Vec<CommentThread>, DatabaseError>;
+
+	/// Creates thread with its first comment
+	///
+	/// # Returns
+	///
+	/// None if file does not exist in project
+	fn create_thread(&self, file_id: i32, start: Position, end: Position, author: &User, content: &str) -> Result<Option<CommentThread>, DatabaseError>;
+
+	/// Adds comment to thread, returns None if thread does not exist in project
+	fn add_comment(&self, thread_id: i32, author: &User, content: &str) -> Result<Option<Comment>, DatabaseError>;
+
+	fn set_resolved(&self, thread_id: i32, resolved: bool) -> Result<(), DatabaseError>;
+
+	/// Saves ranges of threads moved by changes in files
+	fn update_ranges(&self, ranges: &[(i32, Position, Position)]) -> Result<(), DatabaseError>;
+}
+
+pub fn new(project_id: i32) -> Box<dyn ICommentsRepository> {
+	Box::new(CommentsRepository { project_id })
+}
+
+struct CommentsRepository {
+	project_id: i32
+}
+
+impl ICommentsRepository for CommentsRepository {
+	fn get_threads(&self) -> Result<Vec<CommentThread>, DatabaseError> {
+		let mut client = get_client()?;
+		let mut threads: Vec<CommentThread> = client
+			.query("SELECT t.id, t.file_id, t.start_row, t.start_column, t.end_row, t.end_column, t.resolved
+			FROM comment_threads t JOIN files f ON f.id = t.file_id
+			WHERE f.project_id = $1 ORDER BY t.id", &[&self.project_id])
+			.map_err(|err| {
+				error!("Failed to get comment threads of project {}: {}", self.project_id, err);
+				DatabaseError::from(err)
+			})?
+			.iter()
+			.map(row_to_thread)
+			.collect();
+		let mut comments: HashMap<i32, Vec<Comment>> = HashMap::new();
+		for row in client.query("SELECT c.thread_id, c.id, u.id, rtrim(u.name), c.content, (EXTRACT(EPOCH FROM c.sent_at) * 1000)::BIGINT
+			FROM comments c JOIN users u ON u.id = c.author_id JOIN comment_threads t ON t.id = c.thread_id JOIN files f ON f.id = t.file_id
+			WHERE f.project_id = $1 ORDER BY c.id", &[&self.project_id])? {
+			comments.entry(row.get(0)).or_default().push(Comment {
+				id: row.get(1),
+				author: User { id: row.get(2), name: row.get(3) },
+				content: row.get(4),
+				sent_at: row.get(5),
+			});
+		}
+		threads.iter_mut().for_each(|thread| thread.comments = comments.remove(&thread.id).unwrap_or_default());
+		Ok(threads)
+	}
+
+	fn create_thread(&self, file_id: i32, start: Position, end: Position, author: &User, content: &str) -> Result<Option<CommentThread>, DatabaseError> {
+		let mut client = get_client()?;
+		let mut transaction = client.transaction()?;
+		let row = transaction.query_opt("INSERT INTO comment_threads (file_id, start_row, start_column, end_row, end_column)
+			SELECT id, $3, $4, $5, $6 FROM files WHERE id = $1 AND project_id = $2
+			RETURNING id, file_id, start_row, start_column, end_row, end_column, resolved",
+										&[&file_id, &self.project_id, &(start.row as i32), &(start.column as i32), &(end.row as i32), &(end.column as i32)])
+			.map_err(|err| {
+				error!("Failed to create comment thread in file {}: {}", file_id, err);
+				DatabaseError::from(err)
+			})?;
+		let mut thread = match row {
+			Some(row) => row_to_thread(&row),
+			None => return Ok(None)
+		};
+		let comment = insert_comment(&mut transaction, thread.id, author, content)?;
+		transaction.commit()?;
+		thread.comments.push(comment);
+		Ok(Some(thread))
+	}
+
+	fn add_comment(&self, thread_id: i32, author: &User, content: &str) -> Result<Option<Comment>, DatabaseError> {
+		let mut client = get_client()?;
+		let mut transaction = client.transaction()?;
+		let exists: bool = transaction.query_one("SELECT EXISTS(SELECT t.id FROM comment_threads t JOIN files f ON f.id = t.file_id
+			WHERE t.id = $1 AND f.project_id = $2)", &[&thread_id, &self.project_id])?
+			.get(0);
+		if !exists {
+			return Ok(None);
+		}
+		let comment = insert_comment(&mut transaction, thread_id, author, content)?;
+		transaction.commit()?;
+		Ok(Some(comment))
+	}
+
+	fn set_resolved(&self, thread_id: i32, resolved: bool) -> Result<(), DatabaseError> {
+		match get_client()?
+			.execute("UPDATE comment_threads SET resolved = $2 WHERE id = $1", &[&thread_id, &resolved]) {
+			Err(err) => {
+				error!("Failed to resolve comment thread {}: {}", thread_id, err);
+				Err(DatabaseError::from(err))
+			}
+			_ => Ok(())
+		}
+	}
+
+	fn update_ranges(&self, ranges: &[(i32, Position, Position)]) -> Result<(), DatabaseError> {
+		let mut client = get_client()?;
+		let mut transaction = client.transaction()?;
+		let statement = transaction.prepare("UPDATE comment_threads
+			SET start_row = $2, start_column = $3, end_row = $4, end_column = $5 WHERE id = $1")?;
+		for (thread_id, start, end) in ranges {
+			transaction.execute(&statement, &[thread_id, &(start.row as i32), &(start.column as i32), &(end.row as i32), &(end.column as i32)])?;
+		}
+		transaction.commit()?;
+		Ok(())
+	}
+}
+
+fn insert_comment(transaction: &mut postgres::Transaction, thread_id: i32, author: &User, content: &str) -> Result<Comment, DatabaseError> {
+	let row = transaction.query_one("INSERT INTO comments (thread_id, author_id, content) VALUES ($1, $2, $3)
+		RETURNING id, (EXTRACT(EPOCH FROM sent_at) * 1000)::BIGINT", &[&thread_id, &author.id, &content])?;
+	Ok(Comment {
+		id: row.get(0),
+		author: User { id: author.id, name: author.name.trim_end().to_owned() },
+		content: content.to_owned(),
+		sent_at: row.get(1),
+	})
+}
+
+fn row_to_thread(row: &Row) -> CommentThread {
+	let position = |row_index, column_index| Position {
+		row: row.get::<_, i32>(row_index) as u32,
+		column: row.get::<_, i32>(column_index) as u32,
+	};
+	CommentThread {
+		id: row.get(0),
+		file_id: row.get(1),
+		start: position(2, 3),
+		end: position(4, 5),
+		resolved: row.get(6),
+		comments: Vec::new(),
+	}
+}
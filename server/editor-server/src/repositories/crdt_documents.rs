@@ -0,0 +1,62 @@
+use crate::repositories::{DatabaseError, Transaction};
+use crate::crdt::CrdtDocument;
+use log::error;
+
+/// Stores CRDT state of single file
+pub trait ICrdtDocumentsRepository {
+	/// Returns None if file has no CRDT state yet or saved state is corrupted
+	fn get(&self, transaction: &mut Transaction) -> Result<Option<CrdtDocument>, DatabaseError>;
+
+	fn save(&self, transaction: &mut Transaction, document: &CrdtDocument) -> Result<(), DatabaseError>;
+}
+
+pub fn new(file_id: i32) -> Box<dyn ICrdtDocumentsRepository> {
+	Box::new(CrdtDocumentsRepository { file_id })
+}
+
+struct CrdtDocumentsRepository {
+	file_id: i32
+}
+
+impl ICrdtDocumentsRepository for CrdtDocumentsRepository {
+	fn get(&self, transaction: &mut Transaction) -> Result<Option<CrdtDocument>, DatabaseError> {
+		let state: String = match transaction
+			.query_opt("SELECT state FROM files_crdt_states WHERE file_id = $1", &[&self.file_id]) {
+			Ok(Some(row)) => row.get(0),
+			Ok(None) => return Ok(None),
+			Err(err) => {
+				error!("Error while retrieving CRDT state of file {}: {}", self.file_id, err);
+				return Err(DatabaseError::from(err));
+			}
+		};
+		match serde_json::from_str(&state) {
+			Ok(document) => Ok(Some(document)),
+			Err(err) => {
+				error!("CRDT state of file {} is corrupted: {}", self.file_id, err);
+				Ok(None)
+			}
+		}
+	}
+
+	fn save(&self, transaction: &mut Transaction, document: &CrdtDocument) -> Result<(), DatabaseError> {
+		let state = match serde_json::to_string(document) {
+			Ok(json) => json,
+			Err(err) => {
+				error!("Error while serializing CRDT state of file {}: {}", self.file_id, err);
+				return Err(DatabaseError {
+					error_code: DatabaseError::QUERY_ERROR,
+					message: err.to_string(),
+				});
+			}
+		};
+		match transaction
+			.execute("INSERT INTO files_crdt_states (file_id, state) VALUES ($1, $2)
+			ON CONFLICT (file_id) DO UPDATE SET state = EXCLUDED.state", &[&self.file_id, &state]) {
+			Err(err) => {
+				error!("Failed to save CRDT state of file {}: {}", self.file_id, err);
+				Err(DatabaseError::from(err))
+			}
+			_ => Ok(())
+		}
+	}
+}
@@ -1,19 +1,34 @@
-use crate::repositories::get_client;
+use crate::repositories::{get_client, DatabaseError, Transaction};
 use log::{warn, error};
 
+/// Who modified line last and when
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct LineAuthor {
+    /// None if author is unknown or line was changed by server
+    pub author_id: Option<i32>,
+    /// Milliseconds since Unix epoch, None if unknown
+    pub modified_at: Option<i64>,
+}
+
 /// Allows to manipulate single file content
 pub trait IFileContentRepository {
-    fn get_content(&self) -> Vec<String>;
+    fn get_content(&self, transaction: &mut Transaction) -> Result<Vec<String>, DatabaseError>;
+
+    fn get_line(&self, index: u32) -> Result<Option<String>, DatabaseError>;
+
+    fn delete_line(&self, index: u32) -> Result<(), DatabaseError>;
 
-    fn get_line(&self, index: u32) -> Option<String>;
+    fn insert_new_line(&self, index: u32, content: Option<String>) -> Result<(), DatabaseError>;
 
-    fn delete_line(&self, index: u32);
+    fn update(&self, index: u32, content: String) -> Result<(), DatabaseError>;
 
-    fn insert_new_line(&self, index: u32, content: Option<String>);
+    fn get_lines(&self, from_inclusive: u32, to_inclusive: u32) -> Result<Vec<String>, DatabaseError>;
 
-    fn update(&self, index: u32, content: String);
+    /// Returns authors of all lines of file, ordered like lines
+    fn get_authors(&self, transaction: &mut Transaction) -> Result<Vec<LineAuthor>, DatabaseError>;
 
-    fn get_lines(&self, from_inclusive: u32, to_inclusive: u32) -> Vec<String>;
+    /// Replaces whole content of file with given lines and their authors
+    fn replace_content(&self, transaction: &mut Transaction, lines: &[String], authors: &[LineAuthor]) -> Result<(), DatabaseError>;
 }
 
 pub fn new(file_id: i32) -> Box<dyn IFileContentRepository> {
@@ -25,66 +40,97 @@ struct FileContentRepository {
 }
 
 impl IFileContentRepository for FileContentRepository {
-    fn get_content(&self) -> Vec<String> {
-        get_client()
-            .query("SELECT content FROM files_lines WHERE file_id = $1 ORDER BY line_number", &[&self.file_id])
-            .unwrap()
+    fn get_content(&self, transaction: &mut Transaction) -> Result<Vec<String>, DatabaseError> {
+        Ok(transaction
+            .query("SELECT content FROM files_lines WHERE file_id = $1 ORDER BY line_number", &[&self.file_id])?
             .iter()
             .map(|row| { return row.get(0); })
-            .collect()
+            .collect())
     }
 
-    fn get_line(&self, index: u32) -> Option<String> {
-        return match get_client()
-            .query_one("SELECT content FROM files_lines WHERE file_id = $1 AND line_number = $2",
+    fn get_line(&self, index: u32) -> Result<Option<String>, DatabaseError> {
+        return match get_client()?
+            .query_opt("SELECT content FROM files_lines WHERE file_id = $1 AND line_number = $2",
                                             &[&self.file_id, &(index as i32)],
         ) {
-            Ok(row) => Some(row.get(0)),
+            Ok(row) => Ok(row.map(|row| row.get(0))),
             Err(err) => {
                 error!("Error while retriving line {}: {}", index, err);
-                None}
+                Err(DatabaseError::from(err))}
         };
     }
 
-    fn delete_line(&self, index: u32) {
-        match get_client()
+    fn delete_line(&self, index: u32) -> Result<(), DatabaseError> {
+        match get_client()?
             .execute("DELETE FROM files_lines WHERE file_id = $1 AND line_number = $2", &[&self.file_id, &(index as i32)]){
             Err(err) => {
                 error!("Error occurred while trying to delete line {} in file {}: {}", index, self.file_id, err);
+                Err(DatabaseError::from(err))
             },
-            _ => {}
+            _ => Ok(())
         }
     }
 
-    fn insert_new_line(&self, index: u32, content: Option<String>) {
-        let result_code: i32 = get_client()
-            .query_one("SELECT * FROM insert_line_in_file($1, $2, $3)", &[&self.file_id, &(index as i32), &content])
-            .unwrap()
+    fn insert_new_line(&self, index: u32, content: Option<String>) -> Result<(), DatabaseError> {
+        let result_code: i32 = get_client()?
+            .query_one("SELECT * FROM insert_line_in_file($1, $2, $3)", &[&self.file_id, &(index as i32), &content])?
             .get(0);
         match result_code {
             -1 => warn!("Someone tried to insert text in negative line"),
             -2 => error!("Someone tried to insert line in nonexisting file {}", self.file_id),
             _ => { }
         }
+        Ok(())
     }
 
-    fn update(&self, index: u32, content: String) {
-        match get_client()
+    fn update(&self, index: u32, content: String) -> Result<(), DatabaseError> {
+        match get_client()?
             .execute("UPDATE files_lines SET content = $1 WHERE file_id = $2 AND line_number = $3", &[&content, &self.file_id, &(index as i32)]){
-            Err(err) => error!("Failed to update line {} in line {}: {}", index, self.file_id, err),
-            _ => { }
+            Err(err) => {
+                error!("Failed to update line {} in line {}: {}", index, self.file_id, err);
+                Err(DatabaseError::from(err))
+            },
+            _ => Ok(())
         }
     }
 
-    fn get_lines(&self, from_inclusive: u32, to_inclusive: u32) -> Vec<String> {
-        get_client()
+    fn get_lines(&self, from_inclusive: u32, to_inclusive: u32) -> Result<Vec<String>, DatabaseError> {
+        Ok(get_client()?
             .query("SELECT content FROM files_lines WHERE file_id = $1 AND line_number >= $2 AND line_number <= $3 ORDER BY line_number",
-                   &[&self.file_id, &from_inclusive, &to_inclusive])
-            .unwrap()
+                   &[&self.file_id, &(from_inclusive as i32), &(to_inclusive as i32)])?
             .iter()
             .map(|row| {return row.get(0)})
-            .collect()
+            .collect())
+    }
+
+    fn get_authors(&self, transaction: &mut Transaction) -> Result<Vec<LineAuthor>, DatabaseError> {
+        Ok(transaction
+            .query("SELECT author_id, (EXTRACT(EPOCH FROM modified_at) * 1000)::BIGINT FROM files_lines WHERE file_id = $1 ORDER BY line_number",
+                   &[&self.file_id])?
+            .iter()
+            .map(|row| LineAuthor { author_id: row.get(0), modified_at: row.get(1) })
+            .collect())
+    }
+
+    fn replace_content(&self, transaction: &mut Transaction, lines: &[String], authors: &[LineAuthor]) -> Result<(), DatabaseError> {
+        let author_ids: Vec<Option<i32>> = authors.iter().map(|author| author.author_id).collect();
+        let modification_times: Vec<Option<i64>> = authors.iter().map(|author| author.modified_at).collect();
+        let result = transaction.execute("DELETE FROM files_lines WHERE file_id = $1", &[&self.file_id]).and_then(|_| {
+            // Lines without author get NULL author, unnest pads shorter arrays with NULLs
+            transaction.execute("INSERT INTO files_lines (file_id, line_number, content, author_id, modified_at)
+                SELECT $1, (line.number - 1)::INT, line.content, line.author_id, to_timestamp(line.modified_at / 1000.0)
+                FROM unnest($2::TEXT[], $3::INT[], $4::BIGINT[]) WITH ORDINALITY AS line(content, author_id, modified_at, number)
+                WHERE line.content IS NOT NULL",
+                                &[&self.file_id, &lines, &author_ids, &modification_times])
+        });
+        match result {
+            Err(err) => {
+                error!("Failed to save content of file {}: {}", self.file_id, err);
+                Err(DatabaseError::from(err))
+            },
+            _ => Ok(())
+        }
     }
 
 
-}
\ No newline at end of file
+}
@@ -0,0 +1,172 @@
+use crate::editor_session::Position;
+use crate::models::{FileRevision, User};
+use crate::repositories::{get_client, DatabaseError, Transaction};
+use postgres::types::ToSql;
+use log::error;
+
+/// Change applied to file as given revision
+#[derive(Clone)]
+pub struct HistoryChange {
+	pub revision: i32,
+	/// None if change was not made by user
+	pub author_id: Option<i32>,
+	pub start: Position,
+	pub end: Position,
+	/// Lines replacing text between start and end
+	pub lines: Vec<String>,
+}
+
+/// Stores log of changes and snapshots of content of single file
+pub trait IFileHistoryRepository {
+	/// Locks file until end of transaction, so history and content of file are changed by one
+	/// transaction at a time
+	fn lock(&self, transaction: &mut Transaction) -> Result<(), DatabaseError>;
+
+	/// Returns None if history of file was not started yet
+	fn last_revision(&self, transaction: &mut Transaction) -> Result<Option<i32>, DatabaseError>;
+
+	/// Locks file and saves changes and snapshot of content after given revision. Changes have to
+	/// follow the last saved revision. Changes and snapshots which are already saved are skipped only
+	/// if they are the same, so failed saves can be repeated, different ones fail with CONFLICT error.
+	fn append(&self, transaction: &mut Transaction, changes: &[HistoryChange], snapshot: Option<(i32, &str)>) -> Result<(), DatabaseError>;
+
+	/// Returns the newest snapshot made in given revision or before it, with its revision
+	fn get_snapshot(&self, revision: i32) -> Result<Option<(i32, String)>, DatabaseError>;
+
+	/// Returns changes after `from_revision` up to `to_revision` inclusive, ordered by revision
+	fn get_changes(&self, from_revision: i32, to_revision: i32) -> Result<Vec<HistoryChange>, DatabaseError>;
+
+	/// Returns saved revisions older than given one, or the last ones, from the newest
+	fn get_revisions(&self, before: Option<i32>, limit: i64) -> Result<Vec<FileRevision>, DatabaseError>;
+}
+
+pub fn new(file_id: i32) -> Box<dyn IFileHistoryRepository> {
+	Box::new(FileHistoryRepository { file_id })
+}
+
+struct FileHistoryRepository {
+	file_id: i32
+}
+
+impl FileHistoryRepository {
+	fn append_locked(&self, transaction: &mut Transaction, changes: &[HistoryChange], snapshot: Option<(i32, &str)>) -> Result<(), DatabaseError> {
+		let mut last_revision = self.last_revision(transaction)?.unwrap_or(0);
+		let insert = transaction.prepare("INSERT INTO files_changes
+			(file_id, revision, author_id, start_row, start_column, end_row, end_column, content)
+			VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT DO NOTHING")?;
+		let find_same = transaction.prepare("SELECT 1 FROM files_changes WHERE file_id = $1 AND revision = $2
+			AND author_id IS NOT DISTINCT FROM $3 AND start_row = $4 AND start_column = $5 AND end_row = $6 AND end_column = $7
+			AND content = $8")?;
+		for change in changes {
+			if change.revision > last_revision + 1 {
+				return Err(conflict(format!("revision {} does not follow the last saved revision {}", change.revision, last_revision)));
+			}
+			let (start_row, start_column) = (change.start.row as i32, change.start.column as i32);
+			let (end_row, end_column) = (change.end.row as i32, change.end.column as i32);
+			let content = change.lines.join("\n");
+			let params: [&(dyn ToSql + Sync); 8] = [&self.file_id, &change.revision, &change.author_id,
+				&start_row, &start_column, &end_row, &end_column, &content];
+			// Only repeated save of the same change can be skipped
+			if transaction.execute(&insert, &params)? == 0 && transaction.query_opt(&find_same, &params)?.is_none() {
+				return Err(conflict(format!("revision {} is already saved with different change", change.revision)));
+			}
+			last_revision = last_revision.max(change.revision);
+		}
+		if let Some((revision, content)) = snapshot {
+			if revision > last_revision {
+				return Err(conflict(format!("snapshot of revision {} is newer than the last saved revision {}", revision, last_revision)));
+			}
+			let inserted = transaction.execute("INSERT INTO files_snapshots (file_id, revision, content) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+											   &[&self.file_id, &revision, &content])?;
+			if inserted == 0 && transaction.query_opt("SELECT 1 FROM files_snapshots WHERE file_id = $1 AND revision = $2 AND content = $3",
+													  &[&self.file_id, &revision, &content])?.is_none() {
+				return Err(conflict(format!("snapshot of revision {} is already saved with different content", revision)));
+			}
+		}
+		Ok(())
+	}
+}
+
+fn conflict(message: String) -> DatabaseError {
+	DatabaseError {
+		error_code: DatabaseError::CONFLICT,
+		message,
+	}
+}
+
+impl IFileHistoryRepository for FileHistoryRepository {
+	fn lock(&self, transaction: &mut Transaction) -> Result<(), DatabaseError> {
+		match transaction.execute("SELECT id FROM files WHERE id = $1 FOR UPDATE", &[&self.file_id]) {
+			Err(err) => {
+				error!("Failed to lock file {}: {}", self.file_id, err);
+				Err(DatabaseError::from(err))
+			}
+			_ => Ok(())
+		}
+	}
+
+	fn last_revision(&self, transaction: &mut Transaction) -> Result<Option<i32>, DatabaseError> {
+		match transaction.query_one("SELECT GREATEST((SELECT max(revision) FROM files_changes WHERE file_id = $1),
+			(SELECT max(revision) FROM files_snapshots WHERE file_id = $1))", &[&self.file_id]) {
+			Ok(row) => Ok(row.get(0)),
+			Err(err) => {
+				error!("Failed to get last revision of file {}: {}", self.file_id, err);
+				Err(DatabaseError::from(err))
+			}
+		}
+	}
+
+	fn append(&self, transaction: &mut Transaction, changes: &[HistoryChange], snapshot: Option<(i32, &str)>) -> Result<(), DatabaseError> {
+		self.lock(transaction)?;
+		let result = self.append_locked(transaction, changes, snapshot);
+		if let Err(err) = &result {
+			error!("Failed to save history of file {}: {}", self.file_id, err);
+		}
+		result
+	}
+
+	fn get_snapshot(&self, revision: i32) -> Result<Option<(i32, String)>, DatabaseError> {
+		Ok(get_client()?
+			.query_opt("SELECT revision, content FROM files_snapshots WHERE file_id = $1 AND revision <= $2
+				ORDER BY revision DESC LIMIT 1", &[&self.file_id, &revision])?
+			.map(|row| (row.get(0), row.get(1))))
+	}
+
+	fn get_changes(&self, from_revision: i32, to_revision: i32) -> Result<Vec<HistoryChange>, DatabaseError> {
+		let rows = get_client()?
+			.query("SELECT revision, author_id, start_row, start_column, end_row, end_column, content FROM files_changes
+				WHERE file_id = $1 AND revision > $2 AND revision <= $3 ORDER BY revision", &[&self.file_id, &from_revision, &to_revision])
+			.map_err(|err| {
+				error!("Failed to get changes of file {}: {}", self.file_id, err);
+				DatabaseError::from(err)
+			})?;
+		Ok(rows.iter()
+			.map(|row| HistoryChange {
+				revision: row.get(0),
+				author_id: row.get(1),
+				start: Position { row: row.get::<_, i32>(2) as u32, column: row.get::<_, i32>(3) as u32 },
+				end: Position { row: row.get::<_, i32>(4) as u32, column: row.get::<_, i32>(5) as u32 },
+				lines: row.get::<_, String>(6).split('\n').map(|line| line.to_owned()).collect(),
+			})
+			.collect())
+	}
+
+	fn get_revisions(&self, before: Option<i32>, limit: i64) -> Result<Vec<FileRevision>, DatabaseError> {
+		let rows = get_client()?
+			.query("SELECT c.revision, u.id, rtrim(u.name), (EXTRACT(EPOCH FROM c.made_at) * 1000)::BIGINT
+			FROM files_changes c LEFT JOIN users u ON u.id = c.author_id
+			WHERE c.file_id = $1 AND ($2::INT IS NULL OR c.revision < $2)
+			ORDER BY c.revision DESC LIMIT $3", &[&self.file_id, &before, &limit])
+			.map_err(|err| {
+				error!("Failed to get revisions of file {}: {}", self.file_id, err);
+				DatabaseError::from(err)
+			})?;
+		Ok(rows.iter()
+			.map(|row| FileRevision {
+				revision: row.get(0),
+				author: row.get::<_, Option<i32>>(1).map(|id| User { id, name: row.get(2) }),
+				made_at: row.get(3),
+			})
+			.collect())
+	}
+}
@@ -0,0 +1,149 @@
+use crate::models::{Folder, Project};
+use crate::repositories::{get_client, DatabaseError};
+use log::{info, error};
+use postgres::Row;
+
+/// Operates CRUD operations on folders. Each IFoldersRepository
+/// implementation must operate only on one project
+pub trait IFoldersRepository {
+	fn create(&self, folder: Folder) -> Result<Folder, FolderUpdateError>;
+
+	/// Renames folder and moves it to its parent
+	fn update(&self, folder: &Folder) -> Result<(), FolderUpdateError>;
+
+	/// Deletes folder with all its subfolders and files
+	///
+	/// # Returns
+	///
+	/// Ids of deleted files or None if folder does not exist in project
+	fn delete(&self, folder: Folder) -> Result<Option<Vec<i32>>, DatabaseError>;
+
+	fn get(&self, folder_id: i32) -> Result<Option<Folder>, DatabaseError>;
+
+	fn get_all(&self) -> Result<Vec<Folder>, DatabaseError>;
+}
+
+#[derive(Debug)]
+pub enum FolderUpdateError {
+	IllegalName,
+	DuplicateNames,
+	FolderDoesNotExist,
+	/// Parent folder does not exist in project or it is moved folder or its subfolder
+	IllegalParent,
+	DatabaseError(DatabaseError),
+}
+
+impl From<DatabaseError> for FolderUpdateError {
+	fn from(err: DatabaseError) -> Self {
+		FolderUpdateError::DatabaseError(err)
+	}
+}
+
+pub fn new(project: Project) -> Box<dyn IFoldersRepository> {
+	Box::new(FoldersRepository { project })
+}
+
+struct FoldersRepository {
+	project: Project
+}
+
+impl IFoldersRepository for FoldersRepository {
+	fn create(&self, mut folder: Folder) -> Result<Folder, FolderUpdateError> {
+		let result_code: i32 = get_client()?
+			.query_one("SELECT * FROM create_folder($1, $2, $3)", &[&self.project.id, &folder.parent_id, &folder.name])
+			.map_err(DatabaseError::from)?
+			.get(0);
+
+		return match result_code {
+			-1 => Err(FolderUpdateError::IllegalName),
+			-3 => Err(FolderUpdateError::DuplicateNames),
+			-4 => Err(FolderUpdateError::IllegalParent),
+			_ => {
+				folder.id = Some(result_code);
+				Ok(folder)
+			}
+		};
+	}
+
+	fn update(&self, folder: &Folder) -> Result<(), FolderUpdateError> {
+		let folder_id = match folder.id {
+			Some(id) => id,
+			None => return Err(FolderUpdateError::FolderDoesNotExist)
+		};
+		let result_code: i32 = get_client()?
+			.query_one("SELECT * FROM update_folder($1, $2, $3)", &[&folder_id, &folder.name, &folder.parent_id])
+			.map_err(DatabaseError::from)?
+			.get(0);
+		return match result_code {
+			-1 => Err(FolderUpdateError::IllegalName),
+			-2 => Err(FolderUpdateError::FolderDoesNotExist),
+			-3 => Err(FolderUpdateError::DuplicateNames),
+			-4 => Err(FolderUpdateError::IllegalParent),
+			_ => {
+				info!("Updated folder {}", folder_id);
+				Ok(())
+			}
+		};
+	}
+
+	fn delete(&self, folder: Folder) -> Result<Option<Vec<i32>>, DatabaseError> {
+		let folder_id = match folder.id {
+			Some(id) => id,
+			None => return Ok(None)
+		};
+		let mut client = get_client()?;
+		let result = client.transaction().and_then(|mut transaction| {
+			let file_ids: Vec<i32> = transaction.query("WITH RECURSIVE subtree(id) AS (
+					SELECT id FROM folders WHERE id = $1 AND project_id = $2
+					UNION ALL
+					SELECT f.id FROM folders f JOIN subtree s ON f.parent_id = s.id
+				)
+				SELECT id FROM files WHERE folder_id IN (SELECT id FROM subtree)", &[&folder_id, &self.project.id])?
+				.iter()
+				.map(|row| row.get(0))
+				.collect();
+			// Subfolders and files are deleted by cascade
+			let modified_rows = transaction.execute("DELETE FROM folders WHERE id = $1 AND project_id = $2",
+													&[&folder_id, &self.project.id])?;
+			transaction.commit()?;
+			Ok(if modified_rows > 0 { Some(file_ids) } else { None })
+		});
+		match result {
+			Ok(file_ids) => Ok(file_ids),
+			Err(err) => {
+				error!("Error happened when trying to delete folder {}: {}", folder_id, err);
+				Err(DatabaseError::from(err))
+			}
+		}
+	}
+
+	fn get(&self, folder_id: i32) -> Result<Option<Folder>, DatabaseError> {
+		return match get_client()?
+			.query_opt("SELECT id, name, parent_id FROM folders WHERE project_id = $1 AND id = $2",
+					   &[&self.project.id, &folder_id]) {
+			Ok(row) => Ok(row.map(|row| self.convert_to_folder(&row))),
+			Err(err) => {
+				error!("Error happened when trying to get folder in project: {}", err);
+				Err(DatabaseError::from(err))
+			}
+		};
+	}
+
+	fn get_all(&self) -> Result<Vec<Folder>, DatabaseError> {
+		Ok(get_client()?.query("SELECT id, name, parent_id FROM folders WHERE project_id = $1", &[&self.project.id])?
+			.iter()
+			.map(|row| self.convert_to_folder(row))
+			.collect())
+	}
+}
+
+impl FoldersRepository {
+	fn convert_to_folder(&self, row: &Row) -> Folder {
+		Folder {
+			id: row.get(0),
+			name: row.get(1),
+			project_id: self.project.id.unwrap(),
+			parent_id: row.get(2),
+		}
+	}
+}
@@ -1,7 +1,7 @@
-use crate::repositories::{get_client};
+use crate::repositories::{get_client, DatabaseError};
 use postgres::Row;
 use log::{error};
-use crate::models::{Project, User};
+use crate::models::{Project, User, ProjectRole};
 
 pub fn new() -> Box<dyn IProjectsRepository> {
 	Box::new(ProjectRepository {})
@@ -12,43 +12,73 @@ pub trait IProjectsRepository {
 
 	fn update(&self, project: Project) -> Result<Project, ProjectsUpdateError>;
 
-	fn get(&self, id: i32) -> Option<Project>;
+	/// Deletes project with all its files
+	///
+	/// # Returns
+	///
+	/// false if project did not exist
+	fn delete(&self, project: &Project) -> Result<bool, DatabaseError>;
 
-	fn get_all_shared_to(&self, user: &User) -> Vec<Project>;
+	fn get(&self, id: i32) -> Result<Option<Project>, DatabaseError>;
 
-	fn get_all_owned_by(&self, user: &User) -> Vec<Project>;
+	fn get_all_shared_to(&self, user: &User) -> Result<Vec<Project>, DatabaseError>;
 
-	fn has_access(&self, project: &Project, user: &User) -> bool;
+	fn get_all_owned_by(&self, user: &User) -> Result<Vec<Project>, DatabaseError>;
 
-	fn grant_access(&self, project: &Project, user: &User) -> Result<(), AccessManagementError>;
+	fn has_access(&self, project: &Project, user: &User) -> Result<bool, DatabaseError>;
+
+	/// Shares project with user with given role, or changes role of user it is already shared with
+	fn grant_access(&self, project: &Project, user: &User, role: ProjectRole) -> Result<(), AccessManagementError>;
+
+	/// Returns None if user has no access to project
+	fn get_role(&self, project: &Project, user: &User) -> Result<Option<ProjectRole>, DatabaseError>;
 
 	fn revoke_access(&self, project: &Project, user: &User) -> Result<(), AccessManagementError>;
 }
 
+#[derive(Debug)]
 pub enum AccessManagementError {
 	UserIsOwner,
 	UserDoesNotExists,
+	DatabaseError(DatabaseError),
 }
 
+#[derive(Debug)]
 pub enum ProjectsUpdateError {
 	ProjectWithSameNameExists,
 	IllegalName,
 	ProjectDoesNotExist,
+	DatabaseError(DatabaseError),
+}
+
+impl From<DatabaseError> for AccessManagementError {
+	fn from(err: DatabaseError) -> Self {
+		AccessManagementError::DatabaseError(err)
+	}
+}
+
+impl From<DatabaseError> for ProjectsUpdateError {
+	fn from(err: DatabaseError) -> Self {
+		ProjectsUpdateError::DatabaseError(err)
+	}
 }
 
 struct ProjectRepository {}
 
 impl IProjectsRepository for ProjectRepository {
 	fn create(&self, project: Project) -> Result<Project, ProjectsUpdateError> {
-		let error_code_or_project_id: i32 = get_client().query_one(
+		let error_code_or_project_id: i32 = get_client()?.query_one(
 			"SELECT * FROM create_project($1, $2, $3)",
 			&[&project.name, &project.description, &project.owner.id],
-		).unwrap().get(0);
+		).map_err(DatabaseError::from)?.get(0);
 
 		return match error_code_or_project_id {
 			-1 => {
 				error!("Tried to create project with non existing user");
-				panic!("aaa");
+				Err(ProjectsUpdateError::DatabaseError(DatabaseError {
+					error_code: DatabaseError::QUERY_ERROR,
+					message: format!("User {} does not exist", project.owner.id),
+				}))
 			}
 			-2 => {
 				Err(ProjectsUpdateError::IllegalName)
@@ -66,11 +96,35 @@ impl IProjectsRepository for ProjectRepository {
 	}
 
 	fn update(&self, project: Project) -> Result<Project, ProjectsUpdateError> {
-		unimplemented!()
+		let project_id = match project.id {
+			Some(id) => id,
+			None => return Err(ProjectsUpdateError::ProjectDoesNotExist)
+		};
+		let result_code: i32 = get_client()?.query_one(
+			"SELECT * FROM update_project($1, $2, $3)",
+			&[&project_id, &project.name, &project.description],
+		).map_err(DatabaseError::from)?.get(0);
+
+		return match result_code {
+			-1 => Err(ProjectsUpdateError::ProjectDoesNotExist),
+			-2 => Err(ProjectsUpdateError::IllegalName),
+			-3 => Err(ProjectsUpdateError::ProjectWithSameNameExists),
+			_ => Ok(project)
+		};
 	}
 
-	fn get(&self, id: i32) -> Option<Project> {
-		return match get_client().query(
+	fn delete(&self, project: &Project) -> Result<bool, DatabaseError> {
+		return match get_client()?.execute("DELETE FROM projects WHERE id = $1", &[&project.id]) {
+			Ok(modified_rows) => Ok(modified_rows > 0),
+			Err(error) => {
+				error!("Error occured while deleting project {:?}: {}", project.id, error);
+				Err(DatabaseError::from(error))
+			}
+		};
+	}
+
+	fn get(&self, id: i32) -> Result<Option<Project>, DatabaseError> {
+		return match get_client()?.query(
 			"SELECT p.id, p.name, p.description, u.id, u.name
 		FROM projects p
 		JOIN users u on p.owner_id = u.id
@@ -78,68 +132,84 @@ impl IProjectsRepository for ProjectRepository {
 		) {
 			Ok(rows) => {
 				if rows.len() != 1 {
-					return None;
+					return Ok(None);
 				}
-				Some(convert_to_project(rows.first().unwrap()))
+				Ok(Some(convert_to_project(rows.first().unwrap())))
 			}
 			Err(error) => {
 				error!("Error occured while fetching project {}: {}", id, error);
-				None
+				Err(DatabaseError::from(error))
 			}
 		};
 	}
 
-	fn get_all_shared_to(&self, user: &User) -> Vec<Project> {
-		return get_client().query(
+	fn get_all_shared_to(&self, user: &User) -> Result<Vec<Project>, DatabaseError> {
+		return Ok(get_client()?.query(
 			"SELECT p.id, p.name, p.description, u.id, u.name
 			FROM projects_shared_for_users psfu
 			JOIN projects p on p.id = psfu.project_id
 			JOIN users u on u.id = p.owner_id
 			WHERE psfu.user_id = $1", &[&user.id],
-		).unwrap().iter()
+		)?.iter()
 			.map(|row| convert_to_project(row))
-			.collect();
+			.collect());
 	}
 
-	fn get_all_owned_by(&self, user: &User) -> Vec<Project> {
-		return get_client().query(
+	fn get_all_owned_by(&self, user: &User) -> Result<Vec<Project>, DatabaseError> {
+		return Ok(get_client()?.query(
 			"SELECT p.id, p.name, p.description, u.id, u.name
 			FROM projects p
 			JOIN users u on u.id = p.owner_id
 			WHERE owner_id = $1",
 			&[&user.id],
-		).unwrap().iter()
+		)?.iter()
 			.map(|row| convert_to_project(&row))
-			.collect();
+			.collect());
 	}
 
-	fn has_access(&self, project: &Project, user: &User) -> bool {
-		return match get_client().query_one("SELECT * FROM has_access_to_project($1, $2)", &[&project.id, &user.id]) {
+	fn has_access(&self, project: &Project, user: &User) -> Result<bool, DatabaseError> {
+		return match get_client()?.query_one("SELECT * FROM has_access_to_project($1, $2)", &[&project.id, &user.id]) {
 			Ok(row) => {
-				row.get(0)
+				Ok(row.get(0))
 			}
 			Err(error) => {
 				error!("Error occured while checking access to project: {}", error);
-				false
+				Err(DatabaseError::from(error))
 			}
 		};
 	}
 
-	fn grant_access(&self, project: &Project, user: &User) -> Result<(), AccessManagementError> {
-		return match get_client().query_one(
-			"SELECT * FROM grant_access_to_project($1, $2)",
-			&[&project.id, &user.id]
-		).unwrap().get(0) {
+	fn grant_access(&self, project: &Project, user: &User, role: ProjectRole) -> Result<(), AccessManagementError> {
+		return match get_client()?.query_one(
+			"SELECT * FROM grant_access_to_project($1, $2, $3)",
+			&[&project.id, &user.id, &role.as_str()]
+		).map_err(DatabaseError::from)?.get(0) {
 			-1 => Err(AccessManagementError::UserIsOwner),
 			_ => Ok(())
 		};
 	}
 
+	fn get_role(&self, project: &Project, user: &User) -> Result<Option<ProjectRole>, DatabaseError> {
+		if project.owner == *user {
+			return Ok(Some(ProjectRole::Owner));
+		}
+		return match get_client()?.query_opt(
+			"SELECT role FROM projects_shared_for_users WHERE project_id = $1 AND user_id = $2",
+			&[&project.id, &user.id]
+		) {
+			Ok(row) => Ok(row.and_then(|row| ProjectRole::parse(row.get(0)))),
+			Err(error) => {
+				error!("Error occured while getting role of user in project: {}", error);
+				Err(DatabaseError::from(error))
+			}
+		};
+	}
+
 	fn revoke_access(&self, project: &Project, user: &User) -> Result<(), AccessManagementError> {
-		return match get_client().query_one(
+		return match get_client()?.query_one(
 			"SELECT * FROM revoke_access_to_project($1, $2)",
 			&[&project.id, &user.id]
-		).unwrap().get(0) {
+		).map_err(DatabaseError::from)?.get(0) {
 			-1 => Err(AccessManagementError::UserDoesNotExists),
 			_ => Ok(())
 		};
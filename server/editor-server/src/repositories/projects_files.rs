@@ -1,5 +1,5 @@
 use crate::models::{ProjectFile, Project};
-use crate::repositories::get_client;
+use crate::repositories::{get_client, DatabaseError};
 use log::{info, warn, error};
 use postgres::{Row};
 
@@ -10,22 +10,40 @@ pub trait IProjectsFilesRepository {
 
 	fn update(&self, file: &ProjectFile) -> Result<(), ProjectFileUpdateError>;
 
-	fn delete(&self, file: ProjectFile) -> bool;
+	fn delete(&self, file: ProjectFile) -> Result<bool, DatabaseError>;
 
-	fn get(&self, file_id: i32) -> Option<ProjectFile>;
+	fn get(&self, file_id: i32) -> Result<Option<ProjectFile>, DatabaseError>;
 
-	fn get_all(&self) -> Vec<ProjectFile>;
+	fn get_all(&self) -> Result<Vec<ProjectFile>, DatabaseError>;
 }
 
+#[derive(Debug)]
 pub enum ProjectFileCreationError {
 	IllegalName,
 	DuplicateNames,
+	FolderDoesNotExist,
+	DatabaseError(DatabaseError),
 }
 
+#[derive(Debug)]
 pub enum ProjectFileUpdateError {
 	IllegalName,
 	DuplicateNames,
 	FileDoesNotExist,
+	FolderDoesNotExist,
+	DatabaseError(DatabaseError),
+}
+
+impl From<DatabaseError> for ProjectFileCreationError {
+	fn from(err: DatabaseError) -> Self {
+		ProjectFileCreationError::DatabaseError(err)
+	}
+}
+
+impl From<DatabaseError> for ProjectFileUpdateError {
+	fn from(err: DatabaseError) -> Self {
+		ProjectFileUpdateError::DatabaseError(err)
+	}
 }
 
 pub fn new(project: Project) -> Box<dyn IProjectsFilesRepository> {
@@ -38,15 +56,17 @@ struct ProjectFileRepository {
 
 impl IProjectsFilesRepository for ProjectFileRepository {
 	fn create(&self, mut file: ProjectFile) -> Result<ProjectFile, ProjectFileCreationError> {
-		let result_code: i32 = get_client()
-			.query_one("SELECT * FROM create_file($1, $2)", &[&self.project.id, &file.name])
-			.unwrap()
+		let result_code: i32 = get_client()?
+			.query_one("SELECT * FROM create_file($1, $2, $3)", &[&self.project.id, &file.name, &file.folder_id])
+			.map_err(DatabaseError::from)?
 			.get(0);
 
 		return if result_code == -1 {
 			Err(ProjectFileCreationError::IllegalName)
 		} else if result_code == -3 {
 			Err(ProjectFileCreationError::DuplicateNames)
+		} else if result_code == -4 {
+			Err(ProjectFileCreationError::FolderDoesNotExist)
 		} else {
 			file.id = Some(result_code);
 			Ok(file)
@@ -56,14 +76,15 @@ impl IProjectsFilesRepository for ProjectFileRepository {
 	fn update(&self, file: &ProjectFile) -> Result<(), ProjectFileUpdateError> {
 		return match file.id {
 			Some(file_id) => {
-				let result_code: i32 = get_client()
-					.query_one("SELECT * FROM update_file($1, $2)", &[&file_id, &file.name])
-					.unwrap()
+				let result_code: i32 = get_client()?
+					.query_one("SELECT * FROM update_file($1, $2, $3)", &[&file_id, &file.name, &file.folder_id])
+					.map_err(DatabaseError::from)?
 					.get(0);
 				match result_code {
 					-1 => Err(ProjectFileUpdateError::IllegalName),
 					-2 => Err(ProjectFileUpdateError::FileDoesNotExist),
 					-3 => Err(ProjectFileUpdateError::DuplicateNames),
+					-4 => Err(ProjectFileUpdateError::FolderDoesNotExist),
 					_ => {
 						info!("Updated file {}", file_id);
 						Ok(())
@@ -74,38 +95,37 @@ impl IProjectsFilesRepository for ProjectFileRepository {
 		};
 	}
 
-	fn delete(&self, file: ProjectFile) ->bool {
+	fn delete(&self, file: ProjectFile) -> Result<bool, DatabaseError> {
 		return match file.id {
 			Some(file_id) => {
-				let modified_rows = get_client().execute("DELETE FROM files WHERE id = $1 AND project_id = $2",
-											 &[&file_id, &self.project.id]).unwrap();
-				modified_rows > 0
+				let modified_rows = get_client()?.execute("DELETE FROM files WHERE id = $1 AND project_id = $2",
+											 &[&file_id, &self.project.id])?;
+				Ok(modified_rows > 0)
 			}
 			None => {
 				warn!("SSomeone tried to delete file without id");
-				false
+				Ok(false)
 			}
 		}
 	}
 
-	fn get(&self, file_id: i32) -> Option<ProjectFile> {
-		return match get_client()
-			.query_one("SELECT id, name FROM files WHERE project_id = $1 AND id = $2",
+	fn get(&self, file_id: i32) -> Result<Option<ProjectFile>, DatabaseError> {
+		return match get_client()?
+			.query_opt("SELECT id, name, folder_id FROM files WHERE project_id = $1 AND id = $2",
 				   &[&self.project.id, &file_id]){
-			Ok(row) => Some(self.convert_to_project(&row)),
+			Ok(row) => Ok(row.map(|row| self.convert_to_project(&row))),
 			Err(err) => {
 				error!("Error happened when trying to get file in project: {}",err);
-				None
+				Err(DatabaseError::from(err))
 			}
 		}
 	}
 
-	fn get_all(&self) -> Vec<ProjectFile> {
-		 get_client().query("SELECT id, name FROM files WHERE project_id = $1", &[&self.project.id])
-			.unwrap()
+	fn get_all(&self) -> Result<Vec<ProjectFile>, DatabaseError> {
+		Ok(get_client()?.query("SELECT id, name, folder_id FROM files WHERE project_id = $1", &[&self.project.id])?
 			.iter()
 			.map(|row| self.convert_to_project(row))
-			.collect()
+			.collect())
 	}
 }
 impl ProjectFileRepository {
@@ -113,7 +133,8 @@ impl ProjectFileRepository {
 		ProjectFile{
 			id: row.get(0),
 			name: row.get(1),
-			project_id: self.project.id.clone().unwrap()
+			project_id: self.project.id.unwrap(),
+			folder_id: row.get(2),
 		}
 	}
 }
\ No newline at end of file
@@ -0,0 +1,97 @@
+use crate::editor_session::Position;
+use crate::models::{Suggestion, User};
+use crate::repositories::{get_client, DatabaseError};
+use log::error;
+use postgres::Row;
+
+/// Stores changes suggested in files of one project which were not reviewed yet
+pub trait ISuggestionsRepository {
+	fn get_all(&self) -> Result<Vec<Suggestion>, DatabaseError>;
+
+	/// Saves change suggested by author
+	///
+	/// # Returns
+	///
+	/// None if file does not exist in project
+	fn create(&self, file_id: i32, start: Position, end: Position, author: &User, lines: &[String]) -> Result<Option<Suggestion>, DatabaseError>;
+
+	/// Deletes reviewed suggestion, returns false if it did not exist
+	fn delete(&self, suggestion_id: i32) -> Result<bool, DatabaseError>;
+
+	/// Saves ranges of suggestions moved by changes in files
+	fn update_ranges(&self, ranges: &[(i32, Position, Position)]) -> Result<(), DatabaseError>;
+}
+
+pub fn new(project_id: i32) -> Box<dyn ISuggestionsRepository> {
+	Box::new(SuggestionsRepository { project_id })
+}
+
+struct SuggestionsRepository {
+	project_id: i32
+}
+
+impl ISuggestionsRepository for SuggestionsRepository {
+	fn get_all(&self) -> Result<Vec<Suggestion>, DatabaseError> {
+		match get_client()?.query("SELECT s.id, s.file_id, s.start_row, s.start_column, s.end_row, s.end_column, s.content, u.id, rtrim(u.name)
+			FROM suggestions s JOIN files f ON f.id = s.file_id JOIN users u ON u.id = s.author_id
+			WHERE f.project_id = $1 ORDER BY s.id", &[&self.project_id]) {
+			Ok(rows) => Ok(rows.iter().map(row_to_suggestion).collect()),
+			Err(err) => {
+				error!("Failed to get suggestions in project {}: {}", self.project_id, err);
+				Err(DatabaseError::from(err))
+			}
+		}
+	}
+
+	fn create(&self, file_id: i32, start: Position, end: Position, author: &User, lines: &[String]) -> Result<Option<Suggestion>, DatabaseError> {
+		let row = get_client()?.query_opt("WITH s AS (INSERT INTO suggestions (file_id, author_id, start_row, start_column, end_row, end_column, content)
+			SELECT id, $3, $4, $5, $6, $7, $8 FROM files WHERE id = $1 AND project_id = $2
+			RETURNING id, file_id, start_row, start_column, end_row, end_column, content, author_id)
+			SELECT s.*, rtrim(u.name) FROM s JOIN users u ON u.id = s.author_id",
+										  &[&file_id, &self.project_id, &author.id, &(start.row as i32), &(start.column as i32),
+											  &(end.row as i32), &(end.column as i32), &lines.join("\n")])
+			.map_err(|err| {
+				error!("Failed to save suggestion in file {}: {}", file_id, err);
+				DatabaseError::from(err)
+			})?;
+		Ok(row.map(|row| row_to_suggestion(&row)))
+	}
+
+	fn delete(&self, suggestion_id: i32) -> Result<bool, DatabaseError> {
+		match get_client()?.execute("DELETE FROM suggestions s USING files f
+			WHERE s.id = $1 AND f.id = s.file_id AND f.project_id = $2", &[&suggestion_id, &self.project_id]) {
+			Ok(deleted) => Ok(deleted > 0),
+			Err(err) => {
+				error!("Failed to delete suggestion {}: {}", suggestion_id, err);
+				Err(DatabaseError::from(err))
+			}
+		}
+	}
+
+	fn update_ranges(&self, ranges: &[(i32, Position, Position)]) -> Result<(), DatabaseError> {
+		let mut client = get_client()?;
+		let mut transaction = client.transaction()?;
+		let statement = transaction.prepare("UPDATE suggestions
+			SET start_row = $2, start_column = $3, end_row = $4, end_column = $5 WHERE id = $1")?;
+		for (suggestion_id, start, end) in ranges {
+			transaction.execute(&statement, &[suggestion_id, &(start.row as i32), &(start.column as i32), &(end.row as i32), &(end.column as i32)])?;
+		}
+		transaction.commit()?;
+		Ok(())
+	}
+}
+
+fn row_to_suggestion(row: &Row) -> Suggestion {
+	let position = |row_index, column_index| Position {
+		row: row.get::<_, i32>(row_index) as u32,
+		column: row.get::<_, i32>(column_index) as u32,
+	};
+	Suggestion {
+		id: row.get(0),
+		file_id: row.get(1),
+		start: position(2, 3),
+		end: position(4, 5),
+		lines: row.get::<_, String>(6).split('\n').map(|line| line.to_owned()).collect(),
+		author: User { id: row.get(7), name: row.get(8) },
+	}
+}
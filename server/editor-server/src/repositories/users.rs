@@ -1,51 +1,76 @@
-use crate::repositories::get_client;
+use crate::repositories::{get_client, DatabaseError};
 use postgres::Row;
 use log::{error};
 use crate::models::User;
 
-pub fn create_user(login: &String, password: &String) -> Result<User, String> {
-	let mut client = get_client();
-	if user_exists(login) {
-		return Err(String::from("User with this username already exists"));
+#[derive(Debug)]
+pub enum RegistrationError {
+	UserAlreadyExists,
+	DatabaseError(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum LoginError {
+	InvalidCredentials,
+	DatabaseError(DatabaseError),
+}
+
+impl From<DatabaseError> for RegistrationError {
+	fn from(err: DatabaseError) -> Self {
+		RegistrationError::DatabaseError(err)
+	}
+}
+
+impl From<DatabaseError> for LoginError {
+	fn from(err: DatabaseError) -> Self {
+		LoginError::DatabaseError(err)
+	}
+}
+
+pub fn create_user(login: &String, password: &String) -> Result<User, RegistrationError> {
+	if user_exists(login)? {
+		return Err(RegistrationError::UserAlreadyExists);
 	}
-	match client.query_one("INSERT INTO users (name, password_hash) VALUES ($1, md5($2)) RETURNING id", &[login, password]) {
+	match get_client()?.query_one("INSERT INTO users (name, password_hash) VALUES ($1, md5($2)) RETURNING id", &[login, password]) {
 		Ok(row) => {
 			println!("Created user {}", login);
 			Ok(User { id: row.get(0), name: login.clone() })
 		}
 		Err(error) => {
 			println!("Error occured while createing user: {}", error);
-			Err(String::from("Unknown database error"))
+			Err(RegistrationError::DatabaseError(DatabaseError::from(error)))
 		}
 	}
 }
 
-fn user_exists(username: &String) -> bool {
-	get_client().query_one("SELECT(EXISTS(SELECT id FROM users WHERE name = $1))", &[username])
-		.expect("error occured while fetching data").get(0)
+fn user_exists(username: &String) -> Result<bool, DatabaseError> {
+	Ok(get_client()?
+		.query_one("SELECT(EXISTS(SELECT id FROM users WHERE name = $1))", &[username])?
+		.get(0))
 }
 
-pub fn login(username: &String, password: &String) -> Result<User, String> {
-	match get_client()
-		.query_one("SELECT id, name FROM users WHERE name = $1 AND password_hash = md5($2)",
+pub fn login(username: &String, password: &String) -> Result<User, LoginError> {
+	match get_client()?
+		.query_opt("SELECT id, name FROM users WHERE name = $1 AND password_hash = md5($2)",
 				   &[username, password]) {
-		Ok(row) => Ok(User { id: row.get(0), name: row.get(1) })
+		Ok(Some(row)) => Ok(User { id: row.get(0), name: row.get(1) })
 		,
+		Ok(None) => Err(LoginError::InvalidCredentials),
 		Err(error) => {
 			println!("Error occured while loggging: {}", error);
-			Err(String::from("Unknown server error"))
+			Err(LoginError::DatabaseError(DatabaseError::from(error)))
 		}
 	}
 }
 
-pub fn get_user(id: i32) -> Option<User> {
-	return match get_client().query_one("SELECT id, name FROM users WHERE id = $1", &[&id]) {
+pub fn get_user(id: i32) -> Result<Option<User>, DatabaseError> {
+	return match get_client()?.query_opt("SELECT id, name FROM users WHERE id = $1", &[&id]) {
 		Ok(row) => {
-			Some(convert_to_user(&row))
+			Ok(row.map(|row| convert_to_user(&row)))
 		}
 		Err(error) => {
 			error!("Error occured while fetching user by id {}: {}", id, error);
-			None
+			Err(DatabaseError::from(error))
 		}
 	};
 }
@@ -1,44 +1,96 @@
 /// Editor server and messages generated by server(like connected itp)
 use actix::prelude::*;
+use actix::dev::ToEnvelope;
 use rand::{self, rngs::ThreadRng, Rng};
 use std::collections::{HashMap};
+use std::time::{Duration, Instant};
 use crate::editor_session;
-use crate::editor_session::{FileCreationRequest, EditorSession, FileDeletionRequest, FileChange};
-use crate::models::{User, Project, ProjectFile};
+use crate::editor_session::{EditorSession, Connected, Position, Selection, Viewport};
+use crate::models::{Project, Folder, FileTreeNode, ChatMessage, CommentThread, Suggestion, ProjectRole};
 use log::{info, error, warn};
 use serde::Serialize;
-use crate::services::projects_files::CreationError;
-use std::cmp::min;
+use crate::crdt::{CrdtDocument, Operation};
+use crate::repositories::file_content_repository::LineAuthor;
+use crate::project_room::{ProjectRoom, Join, Resume, Leave, CloseRoom};
+use crate::document;
+use crate::repositories::DatabaseError;
+use std::env;
 
+/// How long disconnected session can be resumed by new connection
+const RESUME_TIMEOUT: Duration = Duration::from_secs(60);
 
+
+/// Session joined project room
 #[derive(Message)]
 #[rtype(result = "()")]
-pub struct Message(pub String);
+#[derive(Clone)]
+pub struct SessionJoined {
+	pub session_id: i32,
+	/// Name of user editing in session
+	pub name: String,
+}
 
 #[derive(Message)]
-#[rtype(usize)]
-pub struct Connect {
-	pub addr: Recipient<Message>,
-	pub project_id: i32,
+#[rtype(result = "()")]
+#[derive(Clone)]
+pub struct SessionLeft {
+	pub session_id: i32
+}
+
+/// Session opened other file
+#[derive(Message)]
+#[rtype(result = "()")]
+#[derive(Clone)]
+pub struct ActiveFileChanged {
+	pub session_id: i32,
+	pub file_id: Option<i32>,
 }
 
+/// Session started or stopped following other session
+#[derive(Message)]
+#[rtype(result = "()")]
+#[derive(Clone)]
+pub struct FollowChanged {
+	pub session_id: i32,
+	/// None if session stopped following
+	pub leader_id: Option<i32>,
+}
 
+/// All comment threads in project
 #[derive(Message)]
 #[rtype(result = "()")]
-pub struct Disconnect {
-	pub id: i32,
+pub struct CommentThreads {
+	pub threads: Vec<CommentThread>
 }
 
+/// Suggested changes waiting for review
 #[derive(Message)]
 #[rtype(result = "()")]
-pub struct ClientMessage {
-	/// Id of the client session
-	pub id: i32,
-	/// Peer message
-	pub msg: String,
-	pub project_id: i32,
+pub struct Suggestions {
+	pub suggestions: Vec<Suggestion>
+}
+
+/// Project owner accepted or rejected suggestion
+#[derive(Clone)]
+pub struct SuggestionReviewed {
+	pub suggestion_id: i32,
+	pub accepted: bool,
 }
 
+/// Messages from project chat, from the oldest one
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ChatHistory {
+	pub messages: Vec<ChatMessage>
+}
+
+/// Followed session opened other file, sent to its followers
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct LeaderFileChanged {
+	pub leader_id: i32,
+	pub file_id: Option<i32>,
+}
 
 #[derive(Message)]
 #[rtype(result = "()")]
@@ -46,17 +98,60 @@ pub struct ErrorMessage {
 	pub msg: String
 }
 
-struct SessionData {
-	pub id: i32,
-	pub user: User,
-	pub project_id: i32,
-	pub recipient: Addr<EditorSession>,
+/// Closes editor session, reason is sent to client in close frame
+#[derive(Message)]
+#[rtype(result = "()")]
+#[derive(Clone)]
+pub struct CloseSession {
+	pub reason: String
+}
+
+/// How content of files is stored and how concurrent changes are merged
+#[derive(Clone, Copy, PartialEq)]
+pub enum DocumentModel {
+	/// Changes are applied directly on lines stored in `files_lines` table
+	Lines,
+	/// Files are stored as CRDT documents, see crate::crdt
+	Crdt,
 }
 
+impl DocumentModel {
+	/// Reads model from "ONLINE_EDITOR_DOCUMENT_MODEL" env variable ("lines" or "crdt").
+	/// Lines model is used if variable is not set.
+	pub fn from_env() -> DocumentModel {
+		match env::var("ONLINE_EDITOR_DOCUMENT_MODEL") {
+			Ok(model) if model == "crdt" => DocumentModel::Crdt,
+			Ok(model) if model != "lines" => {
+				warn!("Unknown document model {}, using lines", model);
+				DocumentModel::Lines
+			}
+			_ => DocumentModel::Lines
+		}
+	}
+}
+
+/// Number of threads running project rooms, from ONLINE_EDITOR_ROOM_THREADS or number of CPUs
+fn room_threads() -> usize {
+	env::var("ONLINE_EDITOR_ROOM_THREADS").ok()
+		.and_then(|threads| threads.parse::<usize>().ok())
+		.filter(|threads| *threads > 0)
+		.unwrap_or_else(|| std::thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1))
+}
+
+/// Registry of project rooms. Routes messages from sessions to room of project they edit,
+/// rooms are created when first session connects to project and stopped when last one disconnects.
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct EditorServer {
-	sessions_2: HashMap<i32, SessionData>,
+	rooms: HashMap<i32, Addr<ProjectRoom>>,
+	/// Threads running rooms with documents of their files, new rooms are assigned to them in turns
+	arbiters: Vec<Arbiter>,
+	next_arbiter: usize,
+	/// Project edited in each session. Keys are session ids.
+	sessions_projects: HashMap<i32, i32>,
+	/// Keys are session ids
+	resumable_sessions: HashMap<i32, ResumableSession>,
+	document_model: DocumentModel,
 	rng: ThreadRng,
 }
 
@@ -64,15 +159,122 @@ pub struct EditorServer {
 #[rtype(result = "()")]
 #[derive(Clone)]
 pub struct FileCreated {
+	pub project_id: i32,
 	pub id: i32,
 	pub name: String,
+	/// None if file is in root of project
+	pub folder_id: Option<i32>,
 }
 
 #[derive(Message)]
 #[rtype(result = "()")]
 #[derive(Clone)]
 pub struct FileDeleted {
-	pub id: i32
+	pub project_id: i32,
+	pub id: i32,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+#[derive(Clone)]
+pub struct FileRenamed {
+	pub project_id: i32,
+	pub id: i32,
+	pub name: String,
+}
+
+/// File was moved to other folder
+#[derive(Message)]
+#[rtype(result = "()")]
+#[derive(Clone)]
+pub struct FileMoved {
+	pub project_id: i32,
+	pub id: i32,
+	/// None if file was moved to root of project
+	pub folder_id: Option<i32>,
+}
+
+/// Folder was created, renamed or moved
+#[derive(Message)]
+#[rtype(result = "()")]
+#[derive(Clone)]
+pub struct FolderChanged {
+	pub folder: Folder
+}
+
+/// Folder was deleted with its subfolders and files
+#[derive(Message)]
+#[rtype(result = "()")]
+#[derive(Clone)]
+pub struct FolderDeleted {
+	pub project_id: i32,
+	pub id: i32,
+	/// Ids of deleted files from folder and its subfolders
+	pub file_ids: Vec<i32>,
+}
+
+/// Name or description of project changed
+#[derive(Message)]
+#[rtype(result = "()")]
+#[derive(Clone)]
+pub struct ProjectUpdated {
+	pub project: Project
+}
+
+/// Project was deleted, all sessions editing it are closed
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ProjectDeleted {
+	pub project_id: i32
+}
+
+/// Reads content of file from its opened document.
+///
+/// # Returns
+///
+/// None if nobody edits project or file is not opened
+#[derive(Message)]
+#[rtype(result = "Option<String>")]
+pub struct ReadFileContent {
+	pub project_id: i32,
+	pub file_id: i32,
+}
+
+/// Reads content of file and authors of its lines from its opened document.
+///
+/// # Returns
+///
+/// None if nobody edits project or file is not opened
+#[derive(Message)]
+#[rtype(result = "Option<(String, Vec<LineAuthor>)>")]
+pub struct ReadLineAuthors {
+	pub project_id: i32,
+	pub file_id: i32,
+}
+
+/// Replaces whole content of file in its document and sends change to sessions editing project.
+/// Content is saved in database if nobody edits project. Returns future resolving when content
+/// is saved.
+#[derive(Message)]
+#[rtype(result = "Result<document::ContentSaved, DatabaseError>")]
+pub struct WriteFileContent {
+	pub project_id: i32,
+	pub file_id: i32,
+	pub content: String,
+	/// User replacing content, saved in history of file
+	pub author_id: Option<i32>,
+}
+
+/// Saves changes of files opened in editor and waits for saves of closed files.
+///
+/// # Returns
+///
+/// Revisions of opened files in their history, keys are file ids. Empty if nobody edits project,
+/// None if changes could not be saved.
+#[derive(Message)]
+#[rtype(result = "Option<HashMap<i32, i32>>")]
+pub struct FlushDocuments {
+	pub project_id: i32,
 }
 
 #[derive(Message)]
@@ -80,6 +282,29 @@ pub struct FileDeleted {
 pub struct FileContent {
 	pub file_id: i32,
 	pub content: String,
+	/// Revision of file this content is in
+	pub revision: usize,
+}
+
+/// CRDT state of file sent with its content to clients using CRDT model, so they know ids of
+/// characters and tombstones referred by operations
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CrdtState {
+	pub file_id: i32,
+	pub document: CrdtDocument,
+	/// Revision of file this state is in
+	pub revision: usize,
+}
+
+/// CRDT operations integrated in file, sent to clients using CRDT model
+#[derive(Serialize)]
+#[derive(Message)]
+#[rtype(result = "()")]
+#[derive(Clone)]
+pub struct CrdtOperationsApplied {
+	pub file_id: i32,
+	pub operations: Vec<Operation>,
 }
 
 #[derive(Serialize)]
@@ -87,14 +312,37 @@ pub struct FileContent {
 #[rtype(result = "()")]
 pub struct ProjectInfoDto {
 	pub project: Project,
-	pub files: Vec<ProjectFile>,
+	pub files: Vec<FileTreeNode>,
 	pub sessions: Vec<SessionDataDto>,
 }
 
 #[derive(Serialize)]
 pub struct SessionDataDto {
-	id: i32,
-	name: String,
+	pub id: i32,
+	pub name: String,
+	/// File opened by user of session, None if no file was opened yet
+	pub active_file_id: Option<i32>,
+	/// Session followed by this one
+	pub leader_id: Option<i32>,
+	pub role: ProjectRole,
+	/// None if session did not send its presence yet
+	pub presence: Option<SessionPresence>,
+}
+
+/// Opened file, cursor and selections of user in session. Positions are moved by server when
+/// changes are applied to file, so they are valid in its current revision.
+#[derive(Serialize)]
+#[derive(Message)]
+#[rtype(result = "()")]
+#[derive(Clone)]
+pub struct SessionPresence {
+	pub session_id: i32,
+	pub file_id: Option<i32>,
+	pub cursor: Option<Position>,
+	pub selections: Vec<Selection>,
+	/// Visible rows of file, sent only to followers of session
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub viewport: Option<Viewport>,
 }
 
 #[derive(Message)]
@@ -106,197 +354,215 @@ pub struct ChangeInFile {
 	pub start_column: u32,
 	pub end_row: u32,
 	pub end_column: u32,
-	pub change_id: i32,
+	/// Revision of file made by this change
+	pub revision: usize,
 	pub change: String,
+	/// Session which made the change, it gets only acknowledgement
+	pub session_id: i32,
+	/// Id given to change by client
+	pub client_change_id: Option<u32>,
+}
+
+/// Checksum of file content in given revision, sent to sessions editing file to detect diverged content
+#[derive(Message)]
+#[rtype(result = "()")]
+#[derive(Clone)]
+pub struct FileChecksum {
+	pub file_id: i32,
+	pub revision: usize,
+	pub checksum: String,
+}
+
+/// Result of merging changes made by session while it was offline
+#[derive(Serialize)]
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct OfflineChangesResult {
+	pub file_id: i32,
+	/// False if changes could not be merged, because server does not know version of file they were
+	/// made on. Session gets current content of file then.
+	pub merged: bool,
+	pub conflicts: Vec<ChangeConflict>,
+}
+
+/// Offline change which modified the same part of file as changes of other sessions made in the meantime
+#[derive(Serialize)]
+pub struct ChangeConflict {
+	pub client_change_id: Option<u32>,
+	/// Revision of file made by the change
+	pub revision: usize,
+	/// Start of text written by the change in its revision
+	pub start: Position,
+	/// End of text written by the change in its revision
+	pub end: Position,
+	/// Sessions which made conflicting changes, 0 for changes made outside of editor
+	pub session_ids: Vec<i32>,
+}
+
+/// Event sent to all sessions editing project. Rooms remember recent events, so sessions which
+/// reconnect can get events they missed.
+#[derive(Clone)]
+pub enum ProjectEvent {
+	SessionJoined(SessionJoined),
+	SessionLeft(SessionLeft),
+	ActiveFileChanged(ActiveFileChanged),
+	FollowChanged(FollowChanged),
+	ProjectUpdated(ProjectUpdated),
+	FileCreated(FileCreated),
+	FileDeleted(FileDeleted),
+	FileRenamed(FileRenamed),
+	FileMoved(FileMoved),
+	FolderChanged(FolderChanged),
+	FolderDeleted(FolderDeleted),
+	ChangeInFile(ChangeInFile),
+	CrdtOperations(CrdtOperationsApplied),
+	ChatMessage(ChatMessage),
+	CommentThreadChanged(CommentThread),
+	SuggestionCreated(Suggestion),
+	SuggestionReviewed(SuggestionReviewed),
+}
+
+/// Project event with its id, sent to session
+#[derive(Message)]
+#[rtype(result = "()")]
+#[derive(Clone)]
+pub struct Event {
+	/// Events of project are numbered with consecutive ids starting from 1
+	pub id: u64,
+	pub event: ProjectEvent,
+}
+
+/// Session which can be resumed by new connection with its token
+struct ResumableSession {
+	token: String,
+	user_id: i32,
+	/// Connection currently attached to session
+	addr: Addr<EditorSession>,
+	/// None if session is connected
+	disconnected_at: Option<Instant>,
 }
 
 impl Default for EditorServer {
 	fn default() -> EditorServer {
 		EditorServer {
-			sessions_2: HashMap::new(),
+			rooms: HashMap::new(),
+			arbiters: (0..room_threads()).map(|_| Arbiter::new()).collect(),
+			next_arbiter: 0,
+			sessions_projects: HashMap::new(),
+			resumable_sessions: HashMap::new(),
+			document_model: DocumentModel::from_env(),
 			rng: rand::thread_rng(),
 		}
 	}
 }
 
 impl EditorServer {
-	fn send_message(&self, project_id: i32, message: &str) {
-		println!("Sending message to all editin: {}", project_id);
-		let _ = self.sessions_2.values().into_iter()
-			.filter(|session_data| session_data.project_id == project_id)
-			.for_each(|session| {
-				let _ = session.recipient.do_send(Message(message.to_owned()));
-			});
-	}
-
-	/// Tries to send ProjectInfoDto to actor. If actor does not have access to project or this project
-	/// does not exist. It will send error message to actor and return false
-	///
-	/// # Returns
-	/// - false if this actor does not have access to this project or project does not exists.
-	/// - true otherwise
-	///
-	fn send_project_info(&self, addr: &Addr<EditorSession>, session_data: &SessionData) -> bool {
-		let projects_service = crate::services::projects::new(session_data.user.clone());
-		let project;
-		match projects_service.get(session_data.project_id) {
-			Ok(p) => project = p,
-			Err(_) => {
-				error!("Error while trying to get project for ProjectInfoDto: Project does not exists or user have no access");
-				self.send_error(addr, "You don't have access to this project or this project does not exist".to_owned());
-				return false;
-			}
+	/// Returns room of project, creating it if it does not exist
+	fn room(&mut self, project_id: i32) -> Addr<ProjectRoom> {
+		if let Some(room) = self.rooms.get(&project_id) {
+			return room.clone();
 		}
-		let projects_files_service;
-		match crate::services::projects_files::new(session_data.user.id, project.id.unwrap()) {
-			Ok(service) => projects_files_service = service,
-			Err(_) => return false//already handled
-		}
-		let files = projects_files_service.get_all();
-		let mut sessions: Vec<SessionDataDto> = self.sessions_2.iter()
-			.filter(|session| { session.1.project_id == session_data.project_id })
-			.map(|full_session_data| {
-				return SessionDataDto { id: full_session_data.0.to_owned(), name: full_session_data.1.user.name.clone() };
-			}).collect();
-		sessions.push(SessionDataDto {
-			id: session_data.id,
-			name: session_data.user.name.clone(),
-		});
-		addr.do_send(ProjectInfoDto {
-			project,
-			files,
-			sessions,
-		});
-		return true;
-	}
-
-	/// Sends error message to given actor
-	///
-	fn send_error(&self, addr: &Addr<EditorSession>, msg: String) {
-		addr.do_send(ErrorMessage { msg });
+		let document_model = self.document_model;
+		let arbiter = &self.arbiters[self.next_arbiter];
+		self.next_arbiter = (self.next_arbiter + 1) % self.arbiters.len();
+		let room = ProjectRoom::start_in_arbiter(arbiter, move |_| ProjectRoom::new(project_id, document_model));
+		self.rooms.insert(project_id, room.clone());
+		room
 	}
 
-	fn apply_change(&self, change: &FileChange) {
-		if change.start == change.end && change.lines.is_empty() {
-			return;
-		}
-
-		if change.start == change.end {
-			self.apply_insertion(change);
-		} else {
-			self.apply_deletion(change);
+	/// Sends message to room of project edited in given session
+	fn forward<M>(&self, session_id: i32, msg: M)
+		where M: actix::Message + Send + 'static,
+			  M::Result: Send,
+			  ProjectRoom: Handler<M>,
+			  <ProjectRoom as Actor>::Context: ToEnvelope<ProjectRoom, M> {
+		match self.sessions_projects.get(&session_id).and_then(|project_id| self.rooms.get(project_id)) {
+			Some(room) => room.do_send(msg),
+			None => error!("Session {} is not registered in any project room", session_id)
 		}
 	}
 
-	fn apply_deletion(&self, change: &FileChange) {
-		if change.start.row == change.end.row {
-			self.apply_single_line_deletion(change);
-		} else {
-			self.apply_multi_line_deletion(change);
+	/// Generates id of new session. Ids are positive, so they never collide with SERVER_SESSION_ID
+	/// and sites of CRDT documents reserved for server.
+	fn new_session_id(&mut self) -> i32 {
+		loop {
+			let id = self.rng.gen_range(1, i32::MAX);
+			if !self.sessions_projects.contains_key(&id) && !self.resumable_sessions.contains_key(&id) {
+				return id;
+			}
 		}
 	}
 
-	fn apply_single_line_deletion(&self, change: &FileChange) {
-		let file_content_repository = crate::repositories::file_content_repository::new(change.file_id);
-		let current_line_value;
-		match file_content_repository.get_line(change.start.row) {
-			Some(line) => {
-				current_line_value = line;
-				let prefix_cutting_index = min(change.start.column as usize, current_line_value.len());
-				let prefix: String = current_line_value.chars()
-					.take(prefix_cutting_index)
-					.collect();
-
-				let suffix: String = current_line_value.chars()
-					.skip(change.end.column as usize)
-					.collect();
-				file_content_repository.update(change.start.row, format!("{}{}", prefix, suffix));
-			}
-			None => {
-				warn!("Someone tried to remove part of line {} in file {}, but this line does not exists", change.file_id, change.start.row);
-				return;
-			}
+	/// Registers new session in room of project
+	fn join(&mut self, msg: editor_session::Connect) -> Connected {
+		let id = self.new_session_id();
+		let resume_token = format!("{:032x}", self.rng.gen::<u128>());
+		self.sessions_projects.insert(id, msg.project_id);
+		self.resumable_sessions.insert(id, ResumableSession {
+			token: resume_token.clone(),
+			user_id: msg.user.id,
+			addr: msg.addr.clone(),
+			disconnected_at: None,
+		});
+		self.room(msg.project_id).do_send(Join {
+			session_id: id,
+			addr: msg.addr,
+			user: msg.user,
+		});
+		Connected {
+			session_id: id,
+			resume_token,
+			resumed: false,
 		}
 	}
 
-	fn apply_multi_line_deletion(&self, change: &FileChange) {
-		let file_content_repository = crate::repositories::file_content_repository::new(change.file_id);
-
-		match file_content_repository.get_line(change.start.row) {
-			Some(current_first_line) => {
-				let mut new_first_line: String = current_first_line.chars()
-					.take(change.start.column as usize)
-					.collect();
-				match file_content_repository.get_line(change.end.row) {
-					Some(last_edited_line) => {
-						last_edited_line.chars()
-							.skip(change.end.column as usize)
-							.for_each(|e|{new_first_line.push(e)});
-					}
-					_ => {}
-				}
-				file_content_repository.update(change.start.row, new_first_line);
-			}
-			None => {}
-		}
-		let lines_to_delete = change.end.row - change.start.row + 1;
-		for _ in 0..lines_to_delete{
-			file_content_repository.delete_line(change.start.row + 1);
-		}
+	/// Returns id of session of user in project with given resume token
+	fn find_resumable_session(&self, resume_token: &str, project_id: i32, user_id: i32) -> Option<i32> {
+		self.resumable_sessions.iter()
+			.find(|(session_id, session)| session.token == resume_token
+				&& session.user_id == user_id
+				&& self.sessions_projects.get(session_id) == Some(&project_id))
+			.map(|(session_id, _)| *session_id)
 	}
 
-	fn apply_insertion(&self, change: &FileChange) {
-		if change.lines.len() == 1 {
-			self.apply_single_line_insertion(change);
-		} else {
-			self.apply_multi_line_insertion(change);
+	/// Removes session if it was not resumed in time
+	fn expire_session(&mut self, session_id: i32) {
+		let expired = self.resumable_sessions.get(&session_id)
+			.and_then(|session| session.disconnected_at)
+			.map(|disconnected_at| disconnected_at.elapsed() >= RESUME_TIMEOUT)
+			.unwrap_or(false);
+		if expired {
+			info!("Session {} was not resumed", session_id);
+			self.remove_session(session_id);
 		}
 	}
 
-	fn apply_single_line_insertion(&self, change: &FileChange) {
-		let file_content_repository = crate::repositories::file_content_repository::new(change.file_id);
-		match file_content_repository.get_line(change.start.row) {
-			Some(current_line) => {
-				let prefix_cutting_index = min(change.start.column, current_line.len() as u32) as usize;
-				let prefix: String = current_line.chars()
-					.take(prefix_cutting_index)
-					.collect();
-				let suffix: String = current_line.chars()
-					.skip(prefix_cutting_index)
-					.collect();
-				file_content_repository.update(change.start.row, format!("{}{}{}", prefix, change.lines[0], suffix));
-			}
-			None => {
-				file_content_repository.insert_new_line(change.start.row, Some(change.lines[0].clone()));
+	/// Removes session from room of its project. Room is closed if it was last session editing project.
+	fn remove_session(&mut self, session_id: i32) {
+		info!("Session {} ended", session_id);
+		self.resumable_sessions.remove(&session_id);
+		self.forward(session_id, Leave { session_id });
+		let project_id;
+		match self.sessions_projects.remove(&session_id) {
+			Some(id) => project_id = id,
+			_ => return
+		}
+		if !self.sessions_projects.values().any(|id| *id == project_id) {
+			if let Some(room) = self.rooms.remove(&project_id) {
+				room.do_send(CloseRoom);
 			}
 		}
 	}
 
-	fn apply_multi_line_insertion(&self, change: &FileChange) {
-		let file_content_repository = crate::repositories::file_content_repository::new(change.file_id);
-		let mut suffix_for_last_line = String::new();
-		match file_content_repository.get_line(change.start.row) {
-			Some(current_row) => {
-				let cutting_index = min(change.start.column, current_row.len() as u32) as usize;
-				let prefix: String = current_row.chars()
-					.take(cutting_index)
-					.collect();
-				suffix_for_last_line = current_row.chars()
-					.skip(cutting_index)
-					.collect();
-
-				let new_row_value = format!("{}{}", prefix, change.lines[0]);
-				file_content_repository.update(change.start.row, new_row_value);
-			}
-			None => {
-				file_content_repository.insert_new_line(change.start.row, Some(change.lines[0].to_owned()));
-			}
-		}
-		for i in 1..(change.lines.len() - 1) as u32 {
-			file_content_repository.insert_new_line(i + change.start.row, Some(change.lines[i as usize].clone()));
+	/// Sends message to room of project if anybody edits it
+	fn notify_room<M>(&self, project_id: i32, msg: M)
+		where M: actix::Message + Send + 'static,
+			  M::Result: Send,
+			  ProjectRoom: Handler<M>,
+			  <ProjectRoom as Actor>::Context: ToEnvelope<ProjectRoom, M> {
+		if let Some(room) = self.rooms.get(&project_id) {
+			room.do_send(msg);
 		}
-		let last_inserted_line = format!("{}{}", change.lines.last().unwrap(), suffix_for_last_line);
-		file_content_repository.insert_new_line(change.start.row + (change.lines.len() as u32) - 1, Some(last_inserted_line));
 	}
 }
 
@@ -307,136 +573,272 @@ impl Actor for EditorServer {
 impl Handler<editor_session::FileDeletionRequest> for EditorServer {
 	type Result = ();
 
-	fn handle(&mut self, msg: FileDeletionRequest, _: &mut Context<Self>) -> Self::Result {
-		let session_data = self.sessions_2.get(&msg.session_id).unwrap();
-		let project_files_service;
-		match crate::services::projects_files::new(session_data.user.id, session_data.project_id) {
-			Ok(service) => project_files_service = service,
-			Err(_) => {
-				self.send_error(&session_data.recipient, "Project does not exist or you don't have access to it".to_owned());
-				return;
-			}
-		}
-		let file;
-		match project_files_service.get(msg.file_id) {
-			Some(f) => {
-				file = f;
-			}
-			None => {
-				warn!("Session {}(user {}) editing project {} tried to delete non existing file {}", session_data.id, session_data.user.id, session_data.project_id, msg.file_id);
-				self.send_error(&session_data.recipient, "File does not exist".to_owned());
-				return;
-			}
-		}
-		match project_files_service.delete(file) {
-			Ok(_) => {
-				info!("Session {}(user{}) deleted file {} from project {}", session_data.id, session_data.user.id, msg.file_id, session_data.project_id);
-				self.sessions_2.values().into_iter()
-					.filter(|session| { session.project_id == session_data.project_id })
-					.for_each(|session_data| {
-						session_data.recipient.do_send(FileDeleted {
-							id: msg.file_id
-						});
-					});
-			}
-			Err(_) => {
-				error!("Unknown error occured while deleting file");
-				self.send_error(&session_data.recipient, "Unknown error occured while deleting file".to_owned());
-			}
-		}
+	fn handle(&mut self, msg: editor_session::FileDeletionRequest, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
 	}
 }
 
-/// Register new session and assign unique id to this session
+/// Register new session and assign unique id to this session. Connection with valid resume token
+/// takes over its previous session if room still remembers all events it missed.
 impl Handler<editor_session::Connect> for EditorServer {
-	type Result = i32;
+	type Result = ResponseActFuture<Self, Connected>;
 
 	fn handle(&mut self, msg: editor_session::Connect, _: &mut Context<Self>) -> Self::Result {
-		let id = self.rng.gen::<i32>();
-		self.send_message(msg.project_id, &format!("1{} {}", id, msg.user.name));
-
-		let session_data = SessionData {
-			id,
-			user: msg.user.clone(),
-			project_id: msg.project_id,
-			recipient: msg.addr.clone(),
+		let session_id;
+		match msg.resume.as_ref().and_then(|resume| self.find_resumable_session(&resume.resume_token, msg.project_id, msg.user.id)) {
+			Some(id) => session_id = id,
+			None => return Box::pin(fut::ready(self.join(msg)))
+		}
+		let resume = Resume {
+			session_id,
+			addr: msg.addr.clone(),
+			last_event_id: msg.resume.as_ref().and_then(|resume| resume.last_event_id),
 		};
-		if self.send_project_info(&msg.addr, &session_data) {
-			self.sessions_2.insert(id, session_data);
-			info!("New session with id {}, current sessions {}", id, self.sessions_2.len());
-		} else {}
-		id
+		Box::pin(self.room(msg.project_id)
+			.send(resume)
+			.into_actor(self)
+			.map(move |res, act, _| {
+				match (res, act.resumable_sessions.get_mut(&session_id)) {
+					(Ok(true), Some(session)) => {
+						if session.disconnected_at.is_none() {
+							session.addr.do_send(CloseSession { reason: "Session was resumed in other connection".to_owned() });
+						}
+						session.addr = msg.addr;
+						session.disconnected_at = None;
+						info!("Session {} resumed", session_id);
+						Connected {
+							session_id,
+							resume_token: session.token.clone(),
+							resumed: true,
+						}
+					}
+					_ => {
+						info!("Session {} could not be resumed, starting new session", session_id);
+						act.join(msg)
+					}
+				}
+			}))
 	}
 }
 
+/// Connection of session was closed. Session is removed if client closed it, otherwise it can be
+/// resumed for RESUME_TIMEOUT.
 impl Handler<editor_session::Disconnect> for EditorServer {
 	type Result = ();
 
-	fn handle(&mut self, msg: editor_session::Disconnect, _: &mut Context<Self>) {
-		info!("Session {} ended", msg.session_id);
-		let project_id;
-		match self.sessions_2.remove(&msg.session_id) {
-			Some(session) => project_id = session.project_id,
-			_ => return
+	fn handle(&mut self, msg: editor_session::Disconnect, ctx: &mut Context<Self>) {
+		match self.resumable_sessions.get_mut(&msg.session_id) {
+			// Connection was already replaced by resumed one
+			Some(session) if session.addr != msg.addr => {}
+			Some(session) if msg.resumable => {
+				if session.disconnected_at.is_some() {
+					return;
+				}
+				info!("Session {} disconnected, it can be resumed for {} seconds", msg.session_id, RESUME_TIMEOUT.as_secs());
+				session.disconnected_at = Some(Instant::now());
+				let session_id = msg.session_id;
+				ctx.run_later(RESUME_TIMEOUT, move |act, _| act.expire_session(session_id));
+			}
+			Some(_) => self.remove_session(msg.session_id),
+			None => {}
 		}
-		self.send_message(project_id, &format!("2{}", msg.session_id));
 	}
 }
 
 impl Handler<editor_session::FileCreationRequest> for EditorServer {
 	type Result = ();
 
-	fn handle(&mut self, msg: FileCreationRequest, _: &mut Context<Self>) -> Self::Result {
-		let session_data = self.sessions_2.get(&msg.session_id).expect("Non existing session id");
-		let projects_files_service;
-		match crate::services::projects_files::new(session_data.user.id, session_data.project_id) {
-			Ok(service) => projects_files_service = service,
-			Err(_) => {
-				self.send_error(&session_data.recipient, "You don't have access to this project".to_owned());
-				return;
+	fn handle(&mut self, msg: editor_session::FileCreationRequest, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
+	}
+}
+
+impl Handler<editor_session::FileNameChangeRequest> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::FileNameChangeRequest, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
+	}
+}
+
+impl Handler<editor_session::FileMoveRequest> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::FileMoveRequest, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
+	}
+}
+
+impl Handler<editor_session::FolderCreationRequest> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::FolderCreationRequest, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
+	}
+}
+
+impl Handler<editor_session::FolderRenameRequest> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::FolderRenameRequest, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
+	}
+}
+
+impl Handler<editor_session::FolderMoveRequest> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::FolderMoveRequest, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
+	}
+}
+
+impl Handler<editor_session::FolderDeletionRequest> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::FolderDeletionRequest, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
+	}
+}
+
+impl Handler<FileCreated> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: FileCreated, _: &mut Context<Self>) {
+		self.notify_room(msg.project_id, msg);
+	}
+}
+
+impl Handler<FileRenamed> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: FileRenamed, _: &mut Context<Self>) {
+		self.notify_room(msg.project_id, msg);
+	}
+}
+
+impl Handler<FileDeleted> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: FileDeleted, _: &mut Context<Self>) {
+		self.notify_room(msg.project_id, msg);
+	}
+}
+
+impl Handler<ReadFileContent> for EditorServer {
+	type Result = ResponseFuture<Option<String>>;
+
+	fn handle(&mut self, msg: ReadFileContent, _: &mut Context<Self>) -> Self::Result {
+		let room = self.rooms.get(&msg.project_id).cloned();
+		Box::pin(async move {
+			match room {
+				Some(room) => room.send(msg).await.unwrap_or(None),
+				None => None
 			}
-		}
-		let new_file = ProjectFile {
-			id: None,
-			name: msg.filename,
-			project_id: session_data.project_id,
-		};
-		let created_file;
-		match projects_files_service.create(new_file) {
-			Ok(file) => {
-				created_file = file;
-				info!("Created new file in project {} with name {} and id {}", created_file.project_id, created_file.name, created_file.id.unwrap());
+		})
+	}
+}
+
+impl Handler<ReadLineAuthors> for EditorServer {
+	type Result = ResponseFuture<Option<(String, Vec<LineAuthor>)>>;
+
+	fn handle(&mut self, msg: ReadLineAuthors, _: &mut Context<Self>) -> Self::Result {
+		let room = self.rooms.get(&msg.project_id).cloned();
+		Box::pin(async move {
+			match room {
+				Some(room) => room.send(msg).await.unwrap_or(None),
+				None => None
 			}
-			Err(err) => {
-				match err {
-					CreationError::IllegalName => {
-						self.send_error(&session_data.recipient, "Illegal new file name".to_owned());
-					}
-					CreationError::DuplicateNames => {
-						self.send_error(&session_data.recipient, "File with that name already exists".to_owned())
-					}
+		})
+	}
+}
+
+impl Handler<WriteFileContent> for EditorServer {
+	type Result = ResponseFuture<Result<document::ContentSaved, DatabaseError>>;
+
+	fn handle(&mut self, msg: WriteFileContent, _: &mut Context<Self>) -> Self::Result {
+		let document_model = self.document_model;
+		match self.rooms.get(&msg.project_id).cloned() {
+			Some(room) => Box::pin(async move {
+				let (file_id, content, author_id) = (msg.file_id, msg.content.clone(), msg.author_id);
+				match room.send(msg).await {
+					Ok(result) => result,
+					// Documents of closed room started their last saves already, so content is saved after them
+					Err(_) => Ok(document::write_content(file_id, document_model, content, author_id))
 				}
-				return;
+			}),
+			// Save is started before room of project can be opened, so documents wait for it
+			None => {
+				let saved = document::write_content(msg.file_id, document_model, msg.content, msg.author_id);
+				Box::pin(async move { Ok(saved) })
 			}
 		}
-		let message = FileCreated {
-			id: created_file.id.expect("That would mean that file was not created"),
-			name: created_file.name,
-		};
+	}
+}
+
+impl Handler<FlushDocuments> for EditorServer {
+	type Result = ResponseFuture<Option<HashMap<i32, i32>>>;
+
+	fn handle(&mut self, msg: FlushDocuments, _: &mut Context<Self>) -> Self::Result {
+		let room = self.rooms.get(&msg.project_id).cloned();
+		Box::pin(async move {
+			let revisions = match room {
+				Some(room) => match room.send(msg).await {
+					Ok(revisions) => revisions?,
+					// Documents of closed room started their last saves already
+					Err(_) => HashMap::new()
+				},
+				None => HashMap::new()
+			};
+			document::started_saves_finished().await;
+			Some(revisions)
+		})
+	}
+}
+
+impl Handler<FolderChanged> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: FolderChanged, _: &mut Context<Self>) {
+		self.notify_room(msg.folder.project_id, msg);
+	}
+}
+
+impl Handler<FolderDeleted> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: FolderDeleted, _: &mut Context<Self>) {
+		self.notify_room(msg.project_id, msg);
+	}
+}
 
-		self.sessions_2.values().into_iter()
-			.filter(|s| { return s.project_id == session_data.project_id; })
-			.for_each(|session| {
-				session.recipient.do_send(message.clone())
-			});
+impl Handler<FileMoved> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: FileMoved, _: &mut Context<Self>) {
+		self.notify_room(msg.project_id, msg);
 	}
 }
 
-impl Handler<ClientMessage> for EditorServer {
+impl Handler<ProjectUpdated> for EditorServer {
 	type Result = ();
 
-	fn handle(&mut self, msg: ClientMessage, _: &mut Context<Self>) {
-		self.send_message(msg.project_id, msg.msg.as_str());
+	fn handle(&mut self, msg: ProjectUpdated, _: &mut Context<Self>) {
+		let room = msg.project.id.and_then(|project_id| self.rooms.get(&project_id));
+		if let Some(room) = room {
+			room.do_send(msg);
+		}
+	}
+}
+
+impl Handler<ProjectDeleted> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: ProjectDeleted, _: &mut Context<Self>) {
+		// Room is closed when last of its sessions disconnects
+		if let Some(room) = self.rooms.get(&msg.project_id) {
+			room.do_send(msg);
+		}
 	}
 }
 
@@ -444,21 +846,95 @@ impl Handler<editor_session::FileContentRequest> for EditorServer {
 	type Result = ();
 
 	fn handle(&mut self, msg: editor_session::FileContentRequest, _: &mut Context<Self>) {
-		let file_content_repository = crate::repositories::file_content_repository::new(msg.file_id);
-		let file_content = file_content_repository.get_content();
-		let session_data;
-		match self.sessions_2.get(&msg.session_id) {
-			Some(data) => session_data = data,
-			None => {
-				error!("Inactive session (or at least not registered in sessions registry) send \"get content\" package");
-				return;
-			}
-		}
-		info!("Sending file {} contents to session {}", msg.file_id, msg.session_id);
-		session_data.recipient.do_send(FileContent {
-			file_id: msg.file_id,
-			content: file_content.join("\n"),
-		});
+		self.forward(msg.session_id, msg);
+	}
+}
+
+impl Handler<editor_session::OfflineChangesRequest> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::OfflineChangesRequest, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
+	}
+}
+
+impl Handler<editor_session::CommentThreadCreationRequest> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::CommentThreadCreationRequest, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
+	}
+}
+
+impl Handler<editor_session::CommentRequest> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::CommentRequest, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
+	}
+}
+
+impl Handler<editor_session::CommentThreadResolutionRequest> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::CommentThreadResolutionRequest, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
+	}
+}
+
+impl Handler<editor_session::SuggestionRequest> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::SuggestionRequest, _: &mut Context<Self>) {
+		self.forward(msg.change.session_id, msg);
+	}
+}
+
+impl Handler<editor_session::SuggestionReviewRequest> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::SuggestionReviewRequest, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
+	}
+}
+
+impl Handler<editor_session::ChatMessageRequest> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::ChatMessageRequest, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
+	}
+}
+
+impl Handler<editor_session::ChatHistoryRequest> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::ChatHistoryRequest, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
+	}
+}
+
+impl Handler<editor_session::FollowRequest> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::FollowRequest, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
+	}
+}
+
+impl Handler<editor_session::PresenceUpdate> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::PresenceUpdate, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
+	}
+}
+
+impl Handler<editor_session::FileChecksumReport> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::FileChecksumReport, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
 	}
 }
 
@@ -466,28 +942,14 @@ impl Handler<editor_session::FileChange> for EditorServer {
 	type Result = ();
 
 	fn handle(&mut self, msg: editor_session::FileChange, _: &mut Context<Self>) {
-		let session_data;
-		match self.sessions_2.get(&msg.session_id) {
-			Some(data) => session_data = data,
-			None => {
-				error!("Not registered session sent change in file");
-				return;
-			}
-		}
-		let change = ChangeInFile {
-			file_id: msg.file_id,
-			start_row: msg.start.row,
-			start_column: msg.start.column,
-			end_row: msg.end.row,
-			end_column: msg.end.column,
-			change_id: self.rng.gen::<i32>(),
-			change: msg.lines.join("\n"),
-		};
-		self.sessions_2
-			.values()
-			.filter(|session| { return session.project_id == session_data.project_id; })
-			.for_each(|session| { session.recipient.do_send(change.clone()) });
-		self.apply_change(&msg);
+		self.forward(msg.session_id, msg);
 	}
 }
 
+impl Handler<editor_session::CrdtOperationsRequest> for EditorServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: editor_session::CrdtOperationsRequest, _: &mut Context<Self>) {
+		self.forward(msg.session_id, msg);
+	}
+}
@@ -1,2 +1,3 @@
 pub mod projects;
-pub mod projects_files;
\ No newline at end of file
+pub mod projects_files;
+pub mod history;
\ No newline at end of file
@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use crate::models::{User, FileRevision, Checkpoint, FileDiff, BlameLine};
+use crate::repositories::DatabaseError;
+use crate::repositories::checkpoints::ICheckpointsRepository;
+use crate::repositories::file_content_repository::LineAuthor;
+use crate::services::projects_files::{IProjectsFilesService, ServiceCreationError};
+use crate::server::DocumentModel;
+use crate::document;
+use crate::diff;
+
+/// Maximal number of characters in name of checkpoint
+const MAX_CHECKPOINT_NAME_LENGTH: usize = 200;
+
+/// History of files and checkpoints of project. This service should be bound to one user and one project.
+pub trait IHistoryService {
+	/// Returns saved revisions of file older than given one, or the last ones, from the newest.
+	/// None if file does not exist in project.
+	fn get_revisions(&self, file_id: i32, before: Option<i32>, limit: i64) -> Result<Option<Vec<FileRevision>>, DatabaseError>;
+	/// Returns None if file does not exist in project or revision is not saved
+	fn get_content(&self, file_id: i32, revision: i32) -> Result<Option<String>, DatabaseError>;
+	/// Creates checkpoint with current revisions of all files of project. Files opened in editor have
+	/// their revisions in `opened_revisions`, keys are file ids.
+	fn create_checkpoint(&self, name: &str, opened_revisions: &HashMap<i32, i32>) -> Result<Checkpoint, CheckpointCreationError>;
+	fn get_checkpoints(&self) -> Result<Vec<Checkpoint>, DatabaseError>;
+	fn get_checkpoint(&self, id: i32) -> Result<Option<Checkpoint>, DatabaseError>;
+	/// Returns ids and content of files in checkpoint, which should be restored. Only given file is
+	/// restored if `file_id` is set.
+	fn get_restored_contents(&self, checkpoint_id: i32, file_id: Option<i32>) -> Result<Vec<(i32, String)>, RestoreError>;
+	/// Compares two versions of file. Files opened in editor have their current revisions in `opened_revisions`.
+	fn diff_file(&self, file_id: i32, from: DiffVersion, to: DiffVersion, opened_revisions: &HashMap<i32, i32>) -> Result<FileDiff, DiffError>;
+	/// Compares files in checkpoint with files in other checkpoint, or with current files if `to_checkpoint`
+	/// is None. Only changed files are returned.
+	fn diff_project(&self, from_checkpoint: i32, to_checkpoint: Option<i32>, opened_revisions: &HashMap<i32, i32>) -> Result<Vec<FileDiff>, DiffError>;
+	/// Returns lines of file with users who modified them last. File opened in editor has its content
+	/// and authors of lines in `opened`, otherwise they are read from database.
+	fn get_blame(&self, file_id: i32, opened: Option<(String, Vec<LineAuthor>)>) -> Result<Vec<BlameLine>, BlameError>;
+}
+
+/// Version of file compared in diff
+#[derive(Debug, Clone, Copy)]
+pub enum DiffVersion {
+	Revision(i32),
+	/// Revision of file saved in checkpoint with given id
+	Checkpoint(i32),
+	/// Current content of file, including changes made in editor
+	Current,
+}
+
+#[derive(Debug)]
+pub enum CheckpointCreationError {
+	IllegalName,
+	DatabaseError(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum RestoreError {
+	/// Users with suggester role can't change files
+	NotAllowed,
+	CheckpointDoesNotExist,
+	FileIsNotInCheckpoint,
+	/// Changes of file made before checkpoint are not saved in database yet
+	RevisionNotSaved,
+	DatabaseError(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum DiffError {
+	FileDoesNotExist,
+	CheckpointDoesNotExist,
+	FileIsNotInCheckpoint,
+	/// Revision does not exist or changes made before it are not saved in database yet
+	RevisionNotSaved,
+	DatabaseError(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum BlameError {
+	FileDoesNotExist,
+	/// Authors of lines are not tracked in CRDT document model
+	NotSupported,
+	DatabaseError(DatabaseError),
+}
+
+impl From<DatabaseError> for CheckpointCreationError {
+	fn from(err: DatabaseError) -> Self {
+		CheckpointCreationError::DatabaseError(err)
+	}
+}
+
+impl From<DatabaseError> for RestoreError {
+	fn from(err: DatabaseError) -> Self {
+		RestoreError::DatabaseError(err)
+	}
+}
+
+impl From<DatabaseError> for DiffError {
+	fn from(err: DatabaseError) -> Self {
+		DiffError::DatabaseError(err)
+	}
+}
+
+impl From<DatabaseError> for BlameError {
+	fn from(err: DatabaseError) -> Self {
+		BlameError::DatabaseError(err)
+	}
+}
+
+pub fn new(user_id: i32, project_id: i32, model: DocumentModel) -> Result<Box<dyn IHistoryService>, ServiceCreationError> {
+	let user;
+	match crate::repositories::users::get_user(user_id)? {
+		Some(u) => user = u,
+		None => return Err(ServiceCreationError::UserDoesNotExists)
+	}
+	Ok(Box::new(HistoryService {
+		user,
+		model,
+		files_service: crate::services::projects_files::new(user_id, project_id)?,
+		checkpoints_repository: crate::repositories::checkpoints::new(project_id),
+	}))
+}
+
+struct HistoryService {
+	user: User,
+	model: DocumentModel,
+	files_service: Box<dyn IProjectsFilesService>,
+	checkpoints_repository: Box<dyn ICheckpointsRepository>,
+}
+
+impl IHistoryService for HistoryService {
+	fn get_revisions(&self, file_id: i32, before: Option<i32>, limit: i64) -> Result<Option<Vec<FileRevision>>, DatabaseError> {
+		if self.files_service.get(file_id)?.is_none() {
+			return Ok(None);
+		}
+		Ok(Some(crate::repositories::file_history::new(file_id).get_revisions(before, limit)?))
+	}
+
+	fn get_content(&self, file_id: i32, revision: i32) -> Result<Option<String>, DatabaseError> {
+		if self.files_service.get(file_id)?.is_none() {
+			return Ok(None);
+		}
+		document::content_in_revision(file_id, revision)
+	}
+
+	fn create_checkpoint(&self, name: &str, opened_revisions: &HashMap<i32, i32>) -> Result<Checkpoint, CheckpointCreationError> {
+		let name = name.trim();
+		if name.is_empty() || name.chars().count() > MAX_CHECKPOINT_NAME_LENGTH {
+			return Err(CheckpointCreationError::IllegalName);
+		}
+		let file_ids: Vec<i32> = self.files_service.get_all()?.iter().filter_map(|file| file.id).collect();
+		let revisions = self.current_revisions(&file_ids, opened_revisions)?;
+		Ok(self.checkpoints_repository.create(name, &self.user, &revisions)?)
+	}
+
+	fn get_checkpoints(&self) -> Result<Vec<Checkpoint>, DatabaseError> {
+		self.checkpoints_repository.get_all()
+	}
+
+	fn get_checkpoint(&self, id: i32) -> Result<Option<Checkpoint>, DatabaseError> {
+		self.checkpoints_repository.get(id)
+	}
+
+	fn get_restored_contents(&self, checkpoint_id: i32, file_id: Option<i32>) -> Result<Vec<(i32, String)>, RestoreError> {
+		if !self.files_service.can_modify() {
+			return Err(RestoreError::NotAllowed);
+		}
+		let checkpoint;
+		match self.checkpoints_repository.get(checkpoint_id)? {
+			Some(c) => checkpoint = c,
+			None => return Err(RestoreError::CheckpointDoesNotExist)
+		}
+		let files: Vec<_> = checkpoint.files.iter()
+			.filter(|file| file_id.is_none() || file_id == Some(file.file_id))
+			.collect();
+		if files.is_empty() && file_id.is_some() {
+			return Err(RestoreError::FileIsNotInCheckpoint);
+		}
+		let mut contents = Vec::new();
+		for file in files {
+			match document::content_in_revision(file.file_id, file.revision)? {
+				Some(content) => contents.push((file.file_id, content)),
+				None => return Err(RestoreError::RevisionNotSaved)
+			}
+		}
+		Ok(contents)
+	}
+
+	fn diff_file(&self, file_id: i32, from: DiffVersion, to: DiffVersion, opened_revisions: &HashMap<i32, i32>) -> Result<FileDiff, DiffError> {
+		let file;
+		match self.files_service.get(file_id)? {
+			Some(f) => file = f,
+			None => return Err(DiffError::FileDoesNotExist)
+		}
+		let from_revision = self.revision_of(file_id, from, opened_revisions)?;
+		let to_revision = self.revision_of(file_id, to, opened_revisions)?;
+		let from_content = self.content_of(file_id, from_revision)?;
+		let to_content = self.content_of(file_id, to_revision)?;
+		Ok(diff::diff_file(file_id, &file.name, Some((from_revision, &from_content)), Some((to_revision, &to_content))))
+	}
+
+	fn diff_project(&self, from_checkpoint: i32, to_checkpoint: Option<i32>, opened_revisions: &HashMap<i32, i32>) -> Result<Vec<FileDiff>, DiffError> {
+		let files = self.files_service.get_all()?;
+		let from_revisions = self.checkpoint_revisions(from_checkpoint)?;
+		let to_revisions = match to_checkpoint {
+			Some(checkpoint_id) => self.checkpoint_revisions(checkpoint_id)?,
+			None => {
+				let file_ids: Vec<i32> = files.iter().filter_map(|file| file.id).collect();
+				self.current_revisions(&file_ids, opened_revisions)?
+			}
+		};
+		let mut diffs = Vec::new();
+		for file in &files {
+			let file_id;
+			match file.id {
+				Some(id) => file_id = id,
+				None => continue
+			}
+			let from_revision = from_revisions.get(&file_id).copied();
+			let to_revision = to_revisions.get(&file_id).copied();
+			if from_revision == to_revision {
+				continue;
+			}
+			let from_content = match from_revision {
+				Some(revision) => Some((revision, self.content_of(file_id, revision)?)),
+				None => None
+			};
+			let to_content = match to_revision {
+				Some(revision) => Some((revision, self.content_of(file_id, revision)?)),
+				None => None
+			};
+			let file_diff = diff::diff_file(
+				file_id,
+				&file.name,
+				from_content.as_ref().map(|(revision, content)| (*revision, content.as_str())),
+				to_content.as_ref().map(|(revision, content)| (*revision, content.as_str())),
+			);
+			if !file_diff.hunks.is_empty() || from_revision.is_none() || to_revision.is_none() {
+				diffs.push(file_diff);
+			}
+		}
+		Ok(diffs)
+	}
+
+	fn get_blame(&self, file_id: i32, opened: Option<(String, Vec<LineAuthor>)>) -> Result<Vec<BlameLine>, BlameError> {
+		if self.model == DocumentModel::Crdt {
+			return Err(BlameError::NotSupported);
+		}
+		if self.files_service.get(file_id)?.is_none() {
+			return Err(BlameError::FileDoesNotExist);
+		}
+		let (content, authors) = match opened {
+			Some(opened) => opened,
+			None => document::read_line_authors(file_id)?
+		};
+		let mut users: HashMap<i32, Option<User>> = HashMap::new();
+		let mut lines = Vec::new();
+		for (index, line) in content.split('\n').enumerate() {
+			let line_author = authors.get(index).copied().unwrap_or_default();
+			let author = match line_author.author_id {
+				Some(author_id) => match users.get(&author_id) {
+					Some(user) => user.clone(),
+					None => {
+						let user = crate::repositories::users::get_user(author_id)?;
+						users.insert(author_id, user.clone());
+						user
+					}
+				},
+				None => None
+			};
+			lines.push(BlameLine {
+				content: line.to_owned(),
+				author,
+				modified_at: line_author.modified_at,
+			});
+		}
+		Ok(lines)
+	}
+}
+
+impl HistoryService {
+	/// Current revisions of files, files opened in editor have their revisions in `opened_revisions`
+	fn current_revisions(&self, file_ids: &[i32], opened_revisions: &HashMap<i32, i32>) -> Result<HashMap<i32, i32>, DatabaseError> {
+		let mut revisions = HashMap::new();
+		for file_id in file_ids {
+			let revision = match opened_revisions.get(file_id) {
+				Some(revision) => *revision,
+				None => document::history_revision(*file_id, self.model)?
+			};
+			revisions.insert(*file_id, revision);
+		}
+		Ok(revisions)
+	}
+
+	/// Revisions of files saved in checkpoint, keys are file ids
+	fn checkpoint_revisions(&self, checkpoint_id: i32) -> Result<HashMap<i32, i32>, DiffError> {
+		match self.checkpoints_repository.get(checkpoint_id)? {
+			Some(checkpoint) => Ok(checkpoint.files.iter().map(|file| (file.file_id, file.revision)).collect()),
+			None => Err(DiffError::CheckpointDoesNotExist)
+		}
+	}
+
+	fn revision_of(&self, file_id: i32, version: DiffVersion, opened_revisions: &HashMap<i32, i32>) -> Result<i32, DiffError> {
+		match version {
+			DiffVersion::Revision(revision) => Ok(revision),
+			DiffVersion::Checkpoint(checkpoint_id) => match self.checkpoint_revisions(checkpoint_id)?.get(&file_id) {
+				Some(revision) => Ok(*revision),
+				None => Err(DiffError::FileIsNotInCheckpoint)
+			},
+			DiffVersion::Current => Ok(self.current_revisions(&[file_id], opened_revisions)?[&file_id])
+		}
+	}
+
+	fn content_of(&self, file_id: i32, revision: i32) -> Result<String, DiffError> {
+		match document::content_in_revision(file_id, revision)? {
+			Some(content) => Ok(content),
+			None => Err(DiffError::RevisionNotSaved)
+		}
+	}
+}
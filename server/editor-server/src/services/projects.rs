@@ -1,5 +1,6 @@
-use crate::models::{Project, User};
+use crate::models::{Project, User, ProjectRole};
 use crate::repositories::projects::{IProjectsRepository, ProjectsUpdateError, AccessManagementError};
+use crate::repositories::DatabaseError;
 use log::{error};
 
 pub fn new(user: User) -> Box<dyn IProjectsService> {
@@ -13,37 +14,82 @@ pub trait IProjectsService {
 	fn create(&self, name: String, description: String) -> Result<Project, SaveError>;
 	fn update(&self, project: Project) -> Result<Project, SaveError>;
 	fn delete(&self, project: Project) -> Result<(), DeleteError>;
-	fn get_owned_projects(&self) -> Vec<Project>;
-	fn get_projects_shared_to_user(&self) -> Vec<Project>;
+	fn get_owned_projects(&self) -> Result<Vec<Project>, DatabaseError>;
+	fn get_projects_shared_to_user(&self) -> Result<Vec<Project>, DatabaseError>;
 	fn get(&self, id: i32) -> Result<Project, GetError>;
-	fn grant_access(&self, project: &Project, user: &User) -> Result<(), AccessGrantingError>;
+	fn grant_access(&self, project: &Project, user: &User, role: ProjectRole) -> Result<(), AccessGrantingError>;
+	/// Role of user of this service in project
+	fn get_role(&self, project: &Project) -> Result<ProjectRole, GetError>;
 	fn revoke_access(&self, project: &Project, user: &User) -> Result<(), AccessRevokingError>;
 }
 
+#[derive(Debug)]
 pub enum GetError {
 	AccessDenied,
 	DoesNotExist,
+	DatabaseError(DatabaseError),
 }
 
+#[derive(Debug)]
 pub enum DeleteError {
-	AccessDenied
+	AccessDenied,
+	DoesNotExist,
+	DatabaseError(DatabaseError),
 }
 
+#[derive(Debug)]
 pub enum AccessGrantingError {
-	NotOwner
+	NotOwner,
+	/// Project can't be shared with owner role
+	IllegalRole,
+	DatabaseError(DatabaseError),
 }
 
+#[derive(Debug)]
 pub enum AccessRevokingError {
 	IsOwner,
 	UserIsNotOwner,
 	UserDoesNotExists,
+	DatabaseError(DatabaseError),
 }
 
+#[derive(Debug)]
 pub enum SaveError {
 	InvalidName,
 	ProjectDoesNotExists,
 	AccessException,
 	ProjectWithSameNaeAlreadyExists,
+	DatabaseError(DatabaseError),
+}
+
+impl From<DatabaseError> for GetError {
+	fn from(err: DatabaseError) -> Self {
+		GetError::DatabaseError(err)
+	}
+}
+
+impl From<DatabaseError> for DeleteError {
+	fn from(err: DatabaseError) -> Self {
+		DeleteError::DatabaseError(err)
+	}
+}
+
+impl From<DatabaseError> for AccessGrantingError {
+	fn from(err: DatabaseError) -> Self {
+		AccessGrantingError::DatabaseError(err)
+	}
+}
+
+impl From<DatabaseError> for AccessRevokingError {
+	fn from(err: DatabaseError) -> Self {
+		AccessRevokingError::DatabaseError(err)
+	}
+}
+
+impl From<DatabaseError> for SaveError {
+	fn from(err: DatabaseError) -> Self {
+		SaveError::DatabaseError(err)
+	}
 }
 
 struct ProjectsService {
@@ -62,9 +108,8 @@ impl IProjectsService for ProjectsService {
 				match error {
 					ProjectsUpdateError::ProjectWithSameNameExists => Err(SaveError::ProjectWithSameNaeAlreadyExists),
 					ProjectsUpdateError::IllegalName => Err(SaveError::InvalidName),
-					_ => {
-						panic!("This, this this this yes i dunno")
-					}
+					ProjectsUpdateError::ProjectDoesNotExist => Err(SaveError::ProjectDoesNotExists),
+					ProjectsUpdateError::DatabaseError(err) => Err(SaveError::DatabaseError(err)),
 				}
 			}
 		};
@@ -74,26 +119,43 @@ impl IProjectsService for ProjectsService {
 		if project.owner != self.user {
 			return Err(SaveError::AccessException);
 		}
-		unimplemented!()
+		return match self.projects_repository.update(project) {
+			Ok(project) => Ok(project),
+			Err(error) => {
+				match error {
+					ProjectsUpdateError::ProjectWithSameNameExists => Err(SaveError::ProjectWithSameNaeAlreadyExists),
+					ProjectsUpdateError::IllegalName => Err(SaveError::InvalidName),
+					ProjectsUpdateError::ProjectDoesNotExist => Err(SaveError::ProjectDoesNotExists),
+					ProjectsUpdateError::DatabaseError(err) => Err(SaveError::DatabaseError(err)),
+				}
+			}
+		};
 	}
 
 	fn delete(&self, project: Project) -> Result<(), DeleteError> {
-		unimplemented!()
+		if project.owner != self.user {
+			return Err(DeleteError::AccessDenied);
+		}
+		return if self.projects_repository.delete(&project)? {
+			Ok(())
+		} else {
+			Err(DeleteError::DoesNotExist)
+		};
 	}
 
-	fn get_owned_projects(&self) -> Vec<Project> {
+	fn get_owned_projects(&self) -> Result<Vec<Project>, DatabaseError> {
 		self.projects_repository.get_all_owned_by(&self.user)
 	}
 
-	fn get_projects_shared_to_user(&self) -> Vec<Project> {
+	fn get_projects_shared_to_user(&self) -> Result<Vec<Project>, DatabaseError> {
 		self.projects_repository.get_all_shared_to(&self.user)
 	}
 
 	fn get(&self, id: i32) -> Result<Project, GetError> {
-		return match self.projects_repository.get(id) {
+		return match self.projects_repository.get(id)? {
 			None => Err(GetError::DoesNotExist),
 			Some(project) => {
-				if self.projects_repository.has_access(&project, &self.user) {
+				if self.projects_repository.has_access(&project, &self.user)? {
 					Ok(project)
 				} else {
 					Err(GetError::AccessDenied)
@@ -102,12 +164,16 @@ impl IProjectsService for ProjectsService {
 		};
 	}
 
-	fn grant_access(&self, project: &Project, user: &User) -> Result<(), AccessGrantingError> {
+	fn grant_access(&self, project: &Project, user: &User, role: ProjectRole) -> Result<(), AccessGrantingError> {
 		if project.owner != self.user {
 			return Err(AccessGrantingError::NotOwner);
 		}
-		return match self.projects_repository.grant_access(project, user) {
+		if role == ProjectRole::Owner {
+			return Err(AccessGrantingError::IllegalRole);
+		}
+		return match self.projects_repository.grant_access(project, user, role) {
 			Ok(_) => Ok(()),
+			Err(AccessManagementError::DatabaseError(err)) => Err(AccessGrantingError::DatabaseError(err)),
 			Err(_) => {
 				error!("Hmm this is bad: this error should not possible occur because it was handled eariler");
 				Err(AccessGrantingError::NotOwner)//again, this never happens, will fix if I will have time for this TODO
@@ -115,6 +181,13 @@ impl IProjectsService for ProjectsService {
 		};
 	}
 
+	fn get_role(&self, project: &Project) -> Result<ProjectRole, GetError> {
+		return match self.projects_repository.get_role(project, &self.user)? {
+			Some(role) => Ok(role),
+			None => Err(GetError::AccessDenied)
+		};
+	}
+
 	fn revoke_access(&self, project: &Project, user: &User) -> Result<(), AccessRevokingError> {
 		if project.owner != self.user {
 			return Err(AccessRevokingError::UserIsNotOwner);
@@ -132,6 +205,7 @@ impl IProjectsService for ProjectsService {
 						error!("This error should not occur because this case was handled earlier");
 						Err(AccessRevokingError::IsOwner)
 					}
+					AccessManagementError::DatabaseError(err) => Err(AccessRevokingError::DatabaseError(err)),
 				}
 			}
 		};
@@ -1,94 +1,246 @@
-use crate::models::{ User, ProjectFile};
+use crate::models::{ User, ProjectFile, Folder, FileTreeNode, ProjectRole};
 use crate::services::projects::GetError;
 use crate::repositories::projects_files::{IProjectsFilesRepository, ProjectFileUpdateError, ProjectFileCreationError};
+use crate::repositories::folders::{IFoldersRepository, FolderUpdateError};
+use crate::repositories::DatabaseError;
 
 /// This service should be bound to one user and one project.
 pub trait IProjectsFilesService {
-	fn get(&self, id: i32) -> Option<ProjectFile>;
-	fn get_all(&self) -> Vec<ProjectFile>;
+	/// Users with suggester role can only suggest changes, they can't change content of files or
+	/// create, update and delete files and folders
+	fn can_modify(&self) -> bool;
+	fn get(&self, id: i32) -> Result<Option<ProjectFile>, DatabaseError>;
+	fn get_all(&self) -> Result<Vec<ProjectFile>, DatabaseError>;
 	fn update(&self, file: ProjectFile) -> Result<ProjectFile, UpdateError>;
 	fn create(&self, file: ProjectFile) -> Result<ProjectFile, CreationError>;
 	fn delete(&self, file: ProjectFile) -> Result<(), DeletionError>;
+	/// Returns folders and files of project as tree
+	fn get_tree(&self) -> Result<Vec<FileTreeNode>, DatabaseError>;
+	fn get_folder(&self, id: i32) -> Result<Option<Folder>, DatabaseError>;
+	fn create_folder(&self, folder: Folder) -> Result<Folder, FolderSaveError>;
+	/// Renames folder and moves it to its parent
+	fn update_folder(&self, folder: Folder) -> Result<Folder, FolderSaveError>;
+	/// Deletes folder with its content and returns ids of deleted files
+	fn delete_folder(&self, folder: Folder) -> Result<Vec<i32>, FolderDeletionError>;
 }
 
+#[derive(Debug)]
 pub enum UpdateError {
+	NotAllowed,
 	DuplicateName,
 	IllegalName,
 	FileDoesNotExists,
+	FolderDoesNotExist,
+	DatabaseError(DatabaseError),
 }
 
+#[derive(Debug)]
 pub enum CreationError {
+	NotAllowed,
 	IllegalName,
 	DuplicateNames,
+	FolderDoesNotExist,
+	DatabaseError(DatabaseError),
 }
 
+#[derive(Debug)]
 pub enum DeletionError {
-	FileDoesNotExistInProject
+	NotAllowed,
+	FileDoesNotExistInProject,
+	DatabaseError(DatabaseError),
 }
 
+#[derive(Debug)]
+pub enum FolderSaveError {
+	NotAllowed,
+	IllegalName,
+	DuplicateName,
+	FolderDoesNotExist,
+	/// Parent folder does not exist or folder would be moved into itself
+	IllegalParent,
+	DatabaseError(DatabaseError),
+}
+
+#[derive(Debug)]
+pub enum FolderDeletionError {
+	NotAllowed,
+	FolderDoesNotExistInProject,
+	DatabaseError(DatabaseError),
+}
+
+#[derive(Debug)]
 pub enum ServiceCreationError {
 	UserDoesNotExists,
 	ProjectDoesNotExists,
 	UserDoesNotHaveAccessToProject,
+	DatabaseError(DatabaseError),
+}
+
+impl From<DatabaseError> for UpdateError {
+	fn from(err: DatabaseError) -> Self {
+		UpdateError::DatabaseError(err)
+	}
+}
+
+impl From<DatabaseError> for CreationError {
+	fn from(err: DatabaseError) -> Self {
+		CreationError::DatabaseError(err)
+	}
+}
+
+impl From<DatabaseError> for DeletionError {
+	fn from(err: DatabaseError) -> Self {
+		DeletionError::DatabaseError(err)
+	}
+}
+
+impl From<DatabaseError> for FolderSaveError {
+	fn from(err: DatabaseError) -> Self {
+		FolderSaveError::DatabaseError(err)
+	}
+}
+
+impl From<DatabaseError> for FolderDeletionError {
+	fn from(err: DatabaseError) -> Self {
+		FolderDeletionError::DatabaseError(err)
+	}
+}
+
+impl From<DatabaseError> for ServiceCreationError {
+	fn from(err: DatabaseError) -> Self {
+		ServiceCreationError::DatabaseError(err)
+	}
 }
 
 pub fn new(user_id: i32, project_id: i32) -> Result<Box<dyn IProjectsFilesService>, ServiceCreationError> {
 	let user;
-	match crate::repositories::users::get_user(user_id) {
+	match crate::repositories::users::get_user(user_id)? {
 		Some(u) => user = u,
 		None => return Err(ServiceCreationError::UserDoesNotExists)
 	}
 	let project_service = crate::services::projects::new(User { id: user_id, name: user.name.clone() });
-	return match project_service.get(project_id) {
-		Ok(project) => Ok(Box::new(ProjectsFilesService {
-			project_files_repository: crate::repositories::projects_files::new(project),
+	return match project_service.get(project_id).and_then(|project| Ok((project_service.get_role(&project)?, project))) {
+		Ok((role, project)) => Ok(Box::new(ProjectsFilesService {
+			role,
+			project_files_repository: crate::repositories::projects_files::new(project.clone()),
+			folders_repository: crate::repositories::folders::new(project),
 		})),
 		Err(err) => match err {
 			GetError::AccessDenied => Err(ServiceCreationError::UserDoesNotHaveAccessToProject),
-			GetError::DoesNotExist => Err(ServiceCreationError::ProjectDoesNotExists)
+			GetError::DoesNotExist => Err(ServiceCreationError::ProjectDoesNotExists),
+			GetError::DatabaseError(err) => Err(ServiceCreationError::DatabaseError(err))
 		}
 	};
 }
 
 struct ProjectsFilesService {
+	role: ProjectRole,
 	project_files_repository: Box<dyn IProjectsFilesRepository>,
+	folders_repository: Box<dyn IFoldersRepository>,
 }
 
 impl IProjectsFilesService for ProjectsFilesService {
-	fn get(&self, id: i32) -> Option<ProjectFile> {
+	fn can_modify(&self) -> bool {
+		self.role != ProjectRole::Suggester
+	}
+
+	fn get(&self, id: i32) -> Result<Option<ProjectFile>, DatabaseError> {
 		self.project_files_repository.get(id)
 	}
 
-	fn get_all(&self) -> Vec<ProjectFile> {
+	fn get_all(&self) -> Result<Vec<ProjectFile>, DatabaseError> {
 		self.project_files_repository.get_all()
 	}
 
 	fn update(&self, file: ProjectFile) -> Result<ProjectFile, UpdateError> {
+		if !self.can_modify() {
+			return Err(UpdateError::NotAllowed);
+		}
 		return match self.project_files_repository.update(&file) {
 			Ok(_) => Ok(file),
 			Err(err) => match err {
 				ProjectFileUpdateError::IllegalName => Err(UpdateError::IllegalName),
 				ProjectFileUpdateError::DuplicateNames => Err(UpdateError::DuplicateName),
-				ProjectFileUpdateError::FileDoesNotExist => Err(UpdateError::FileDoesNotExists)
+				ProjectFileUpdateError::FileDoesNotExist => Err(UpdateError::FileDoesNotExists),
+				ProjectFileUpdateError::FolderDoesNotExist => Err(UpdateError::FolderDoesNotExist),
+				ProjectFileUpdateError::DatabaseError(err) => Err(UpdateError::DatabaseError(err))
 			}
 		};
 	}
 
 	fn create(&self, file: ProjectFile) -> Result<ProjectFile, CreationError> {
+		if !self.can_modify() {
+			return Err(CreationError::NotAllowed);
+		}
 		return match self.project_files_repository.create(file) {
 			Ok(file) => Ok(file),
 			Err(err) => match err {
 				ProjectFileCreationError::IllegalName => Err(CreationError::IllegalName),
-				ProjectFileCreationError::DuplicateNames => Err(CreationError::DuplicateNames)
+				ProjectFileCreationError::DuplicateNames => Err(CreationError::DuplicateNames),
+				ProjectFileCreationError::FolderDoesNotExist => Err(CreationError::FolderDoesNotExist),
+				ProjectFileCreationError::DatabaseError(err) => Err(CreationError::DatabaseError(err))
 			}
 		};
 	}
 
 	fn delete(&self, file: ProjectFile) -> Result<(), DeletionError> {
-		return  if self.project_files_repository.delete(file) {
+		if !self.can_modify() {
+			return Err(DeletionError::NotAllowed);
+		}
+		return  if self.project_files_repository.delete(file)? {
 			Ok(())
 		} else {
 			Err(DeletionError::FileDoesNotExistInProject)
 		}
 	}
+
+	fn get_tree(&self) -> Result<Vec<FileTreeNode>, DatabaseError> {
+		let folders = self.folders_repository.get_all()?;
+		let files = self.project_files_repository.get_all()?;
+		Ok(FileTreeNode::build_tree(&folders, &files))
+	}
+
+	fn get_folder(&self, id: i32) -> Result<Option<Folder>, DatabaseError> {
+		self.folders_repository.get(id)
+	}
+
+	fn create_folder(&self, folder: Folder) -> Result<Folder, FolderSaveError> {
+		if !self.can_modify() {
+			return Err(FolderSaveError::NotAllowed);
+		}
+		return match self.folders_repository.create(folder) {
+			Ok(folder) => Ok(folder),
+			Err(err) => Err(convert_folder_update_error(err))
+		};
+	}
+
+	fn update_folder(&self, folder: Folder) -> Result<Folder, FolderSaveError> {
+		if !self.can_modify() {
+			return Err(FolderSaveError::NotAllowed);
+		}
+		return match self.folders_repository.update(&folder) {
+			Ok(_) => Ok(folder),
+			Err(err) => Err(convert_folder_update_error(err))
+		};
+	}
+
+	fn delete_folder(&self, folder: Folder) -> Result<Vec<i32>, FolderDeletionError> {
+		if !self.can_modify() {
+			return Err(FolderDeletionError::NotAllowed);
+		}
+		return match self.folders_repository.delete(folder)? {
+			Some(file_ids) => Ok(file_ids),
+			None => Err(FolderDeletionError::FolderDoesNotExistInProject)
+		};
+	}
+}
+
+fn convert_folder_update_error(err: FolderUpdateError) -> FolderSaveError {
+	match err {
+		FolderUpdateError::IllegalName => FolderSaveError::IllegalName,
+		FolderUpdateError::DuplicateNames => FolderSaveError::DuplicateName,
+		FolderUpdateError::FolderDoesNotExist => FolderSaveError::FolderDoesNotExist,
+		FolderUpdateError::IllegalParent => FolderSaveError::IllegalParent,
+		FolderUpdateError::DatabaseError(err) => FolderSaveError::DatabaseError(err)
+	}
 }
\ No newline at end of file